@@ -0,0 +1,229 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Follow the bars and beats of a piece of music.
+
+use comments::get_comment;
+
+/// A boundary of the bars and beats of a piece of music.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    /// The start of a beat
+    Beat,
+    /// The start of a bar, which ends the previous one
+    Bar
+}
+
+/**
+ * The tempo of a piece of music.
+ *
+ * The beats are evenly spaced from the first one, and grouped in bars of
+ * `beats_per_bar` beats. A Music reads its tempo from the ACID chunk of a
+ * WAV file, or from the BPM comment in 4/4.
+ *
+ * # Example
+ * ```
+ * use ears::{Tempo, Boundary};
+ *
+ * let mut tempo = Tempo::new(120.);
+ * tempo.beats_per_bar = 3;
+ *
+ * let position = tempo.get_beat_position(2.25);
+ * assert_eq!((position.bar, position.beat), (1, 1));
+ * assert_eq!(tempo.get_next(Boundary::Bar, 2.25), 3.);
+ * ```
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tempo {
+    /// The beats per minute
+    pub bpm: f32,
+    /// The beats in a bar, the upper number of the time signature
+    pub beats_per_bar: u32,
+    /// The note value of a beat, the lower number of the time signature
+    pub beat_unit: u32,
+    /// The time of the first beat in seconds, after a silence or a pickup
+    pub first_beat: f32
+}
+
+/// A position in the bars and beats of a piece of music, counted from 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeatPosition {
+    /// The bar, negative before the first beat
+    pub bar: i32,
+    /// The beat in the bar
+    pub beat: u32,
+    /// The part of the beat elapsed, between 0. and 1.
+    pub phase: f32
+}
+
+impl Tempo {
+    /**
+     * Create a tempo in 4/4 with the first beat at the start.
+     *
+     * # Argument
+     * * `bpm` - The beats per minute
+     */
+    pub fn new(bpm: f32) -> Tempo {
+        Tempo {
+            bpm: bpm,
+            beats_per_bar: 4,
+            beat_unit: 4,
+            first_beat: 0.
+        }
+    }
+
+    /**
+     * Check that the tempo can be followed.
+     *
+     * # Return
+     * Ok(()) if the tempo is valid, Err(String) otherwise.
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.bpm.is_finite() || self.bpm <= 0. {
+            return Err(format!("Invalid tempo of {} BPM.", self.bpm));
+        }
+        if self.beats_per_bar == 0 || self.beat_unit == 0 {
+            return Err(format!("Invalid time signature {}/{}.",
+                               self.beats_per_bar, self.beat_unit));
+        }
+        if !self.first_beat.is_finite() || self.first_beat < 0. {
+            return Err(format!("Invalid first beat at {} seconds.", self.first_beat));
+        }
+        Ok(())
+    }
+
+    /// Get the duration of a beat in seconds.
+    pub fn get_beat_duration(&self) -> f32 {
+        60. / self.bpm
+    }
+
+    /// Get the duration of a bar in seconds.
+    pub fn get_bar_duration(&self) -> f32 {
+        self.get_beat_duration() * self.beats_per_bar as f32
+    }
+
+    /**
+     * Get the bar and beat at a time.
+     *
+     * # Argument
+     * * `time` - The time in seconds from the start of the music
+     */
+    pub fn get_beat_position(&self, time: f32) -> BeatPosition {
+        let beats = (time as f64 - self.first_beat as f64) / self.get_beat_duration() as f64;
+        let beat = beats.floor();
+        let beats_per_bar = self.beats_per_bar as f64;
+        BeatPosition {
+            bar: (beat / beats_per_bar).floor() as i32,
+            beat: (beat - (beat / beats_per_bar).floor() * beats_per_bar) as u32,
+            phase: (beats - beat) as f32
+        }
+    }
+
+    /**
+     * Get the time of the next beat or bar.
+     *
+     * # Arguments
+     * * `boundary` - The boundary looked for
+     * * `time` - The time in seconds from the start of the music
+     *
+     * # Return
+     * The time of the first boundary at or after `time`.
+     */
+    pub fn get_next(&self, boundary: Boundary, time: f32) -> f32 {
+        let period = match boundary {
+            Boundary::Beat => self.get_beat_duration(),
+            Boundary::Bar  => self.get_bar_duration()
+        } as f64;
+        let periods = ((time as f64 - self.first_beat as f64) / period).ceil();
+        (self.first_beat as f64 + periods * period) as f32
+    }
+
+    /**
+     * Read the tempo from the BPM Vorbis comment, in 4/4.
+     *
+     * # Return
+     * None if there's no valid BPM comment.
+     */
+    #[doc(hidden)]
+    pub fn from_comments(comments: &[(String, String)]) -> Option<Tempo> {
+        let bpm = match get_comment(comments, "BPM").and_then(|v| v.trim().parse::<f32>().ok()) {
+            Some(bpm) => bpm,
+            None      => return None
+        };
+        let tempo = Tempo::new(bpm);
+        match tempo.validate() {
+            Ok(_)  => Some(tempo),
+            Err(_) => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use beat_clock::{Tempo, Boundary, BeatPosition};
+
+    #[test]
+    fn beat_clock_beat_position_OK() -> () {
+        let mut tempo = Tempo::new(120.);
+        tempo.first_beat = 1.;
+
+        assert_eq!(tempo.get_beat_position(1.), BeatPosition { bar: 0, beat: 0, phase: 0. });
+        assert_eq!(tempo.get_beat_position(4.25), BeatPosition { bar: 1, beat: 2, phase: 0.5 });
+        assert_eq!(tempo.get_beat_position(0.75), BeatPosition { bar: -1, beat: 3, phase: 0.5 });
+    }
+
+    #[test]
+    fn beat_clock_next_OK() -> () {
+        let mut tempo = Tempo::new(90.);
+        tempo.beats_per_bar = 3;
+
+        assert_eq!(tempo.get_next(Boundary::Beat, 0.), 0.);
+        assert_eq!(tempo.get_next(Boundary::Beat, 0.1), 2. / 3.);
+        assert_eq!(tempo.get_next(Boundary::Bar, 0.1), 2.);
+        assert_eq!(tempo.get_next(Boundary::Bar, 2.5), 4.);
+    }
+
+    #[test]
+    fn beat_clock_validate_FAIL() -> () {
+        let mut tempo = Tempo::new(0.);
+        assert!(tempo.validate().is_err());
+
+        tempo.bpm = 120.;
+        tempo.beats_per_bar = 0;
+        assert!(tempo.validate().is_err());
+
+        tempo.beats_per_bar = 4;
+        tempo.first_beat = -1.;
+        assert!(tempo.validate().is_err());
+    }
+
+    #[test]
+    fn beat_clock_from_comments_OK() -> () {
+        let comments = vec![("BPM".to_string(), " 128 ".to_string())];
+        assert_eq!(Tempo::from_comments(&comments), Some(Tempo::new(128.)));
+
+        let comments = vec![("BPM".to_string(), "fast".to_string())];
+        assert_eq!(Tempo::from_comments(&comments), None);
+    }
+}
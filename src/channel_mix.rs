@@ -0,0 +1,172 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Change the channel count of the samples while loading them.
+
+use openal::al;
+
+/// How the channels are mixed down to a single one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Downmix {
+    /// Keep a single channel, given by its index
+    Channel(i32),
+    /// Average the channels, the level of a sound identical on every
+    /// channel is kept and the mix never clips
+    Average,
+    /// Sum the channels with a gain of 1/sqrt(channels), keeping the
+    /// loudness of uncorrelated channels, clipped to full scale
+    EqualPower
+}
+
+/**
+ * The channel conversion applied to the samples of a SoundData or a Music.
+ *
+ * OpenAL only spatializes mono sounds: use `ChannelMix::Mono` to load
+ * stereo assets played with `set_position`.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{SoundData, LoadOptions, ChannelMix, Downmix};
+ *
+ * let mut options = LoadOptions::default();
+ * options.channel_mix = ChannelMix::Mono(Downmix::Average);
+ * let snd_data = SoundData::new_with_options("path/to/stereo.wav", &options).unwrap();
+ * ```
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChannelMix {
+    /// Keep the channels of the file
+    Keep,
+    /// Mix all the channels down to a single one
+    Mono(Downmix),
+    /// Output the given number of channels, each one copying the input
+    /// channel with the same index modulo the input channel count, e.g. a
+    /// mono sound is duplicated on every channel. OpenAL only plays 1, 2,
+    /// 4, 6 (5.1), 7 (6.1) or 8 (7.1) channels
+    Duplicate(i32)
+}
+
+impl Default for ChannelMix {
+    fn default() -> ChannelMix {
+        ChannelMix::Keep
+    }
+}
+
+impl ChannelMix {
+    /// Check if the channels are kept as they are.
+    pub fn is_keep(&self) -> bool {
+        *self == ChannelMix::Keep
+    }
+
+    /**
+     * Get the channel count after the conversion.
+     *
+     * # Argument
+     * * `channels` - The channel count of the file
+     *
+     * # Return
+     * A `Result` containing Ok(channels) on success, Err(String) if the
+     * conversion can't be applied to the file.
+     */
+    pub fn get_channels(&self, channels: i32) -> Result<i32, String> {
+        match *self {
+            ChannelMix::Keep => Ok(channels),
+            ChannelMix::Mono(Downmix::Channel(index)) if index < 0 || index >= channels =>
+                Err(format!("Cannot keep channel {} of a sound with {} channels.",
+                            index, channels)),
+            ChannelMix::Mono(_) => Ok(1),
+            ChannelMix::Duplicate(target) if al::get_channels_format(target).is_none() =>
+                Err(format!("Cannot duplicate the channels to {} channels, OpenAL only plays \
+                             1, 2, 4, 6, 7 or 8 channels.", target)),
+            ChannelMix::Duplicate(target) => Ok(target)
+        }
+    }
+
+    /**
+     * Convert interleaved samples.
+     *
+     * # Arguments
+     * * `samples` - The interleaved samples, made of whole frames
+     * * `channels` - The channel count of the samples
+     *
+     * # Return
+     * The converted interleaved samples.
+     */
+    pub fn apply(&self, samples: &[f32], channels: i32) -> Vec<f32> {
+        let channels = channels as usize;
+        let frames = samples.chunks(channels);
+        match *self {
+            ChannelMix::Keep => samples.to_vec(),
+            ChannelMix::Mono(Downmix::Channel(index)) =>
+                frames.map(|frame| frame[index as usize]).collect(),
+            ChannelMix::Mono(Downmix::Average) =>
+                frames.map(|frame| frame.iter().sum::<f32>() / channels as f32).collect(),
+            ChannelMix::Mono(Downmix::EqualPower) => {
+                let gain = 1. / (channels as f32).sqrt();
+                frames.map(|frame| (frame.iter().sum::<f32>() * gain).max(-1.).min(1.)).collect()
+            },
+            ChannelMix::Duplicate(target) => {
+                let mut out = Vec::with_capacity(samples.len() / channels * target as usize);
+                for frame in frames {
+                    for c in 0..target as usize {
+                        out.push(frame[c % channels]);
+                    }
+                }
+                out
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use channel_mix::{ChannelMix, Downmix};
+
+    #[test]
+    fn channelmix_mono() -> () {
+        let stereo = [0.5, 1., -0.5, 0.5];
+
+        assert_eq!(ChannelMix::Mono(Downmix::Channel(1)).apply(&stereo, 2), vec![1., 0.5]);
+        assert_eq!(ChannelMix::Mono(Downmix::Average).apply(&stereo, 2), vec![0.75, 0.]);
+        assert_eq!(ChannelMix::Mono(Downmix::EqualPower).apply(&[0.5, 0.5], 2),
+                   vec![1. / 2f32.sqrt()]);
+        assert_eq!(ChannelMix::Mono(Downmix::EqualPower).apply(&[1., 1.], 2), vec![1.]);
+    }
+
+    #[test]
+    fn channelmix_duplicate() -> () {
+        assert_eq!(ChannelMix::Duplicate(2).apply(&[0.1, 0.2], 1), vec![0.1, 0.1, 0.2, 0.2]);
+        assert_eq!(ChannelMix::Duplicate(4).apply(&[0.1, 0.2], 2), vec![0.1, 0.2, 0.1, 0.2]);
+    }
+
+    #[test]
+    fn channelmix_get_channels_FAIL() -> () {
+        assert!(ChannelMix::Mono(Downmix::Channel(2)).get_channels(2).is_err());
+        assert!(ChannelMix::Duplicate(9).get_channels(1).is_err());
+        assert!(ChannelMix::Duplicate(3).get_channels(1).is_err());
+        assert!(ChannelMix::Duplicate(5).get_channels(1).is_err());
+        assert_eq!(ChannelMix::Duplicate(8).get_channels(1), Ok(8));
+        assert_eq!(ChannelMix::Mono(Downmix::Average).get_channels(6), Ok(1));
+    }
+}
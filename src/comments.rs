@@ -0,0 +1,347 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Read the Vorbis comments of Ogg and FLAC files.
+//!
+//! libsndfile only exposes a few standard tags, this module reads all the
+//! comments so the loop points, gains or tempo stored by the tools can be used.
+
+use std::cmp;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+
+/// Never read more than this amount of bytes looking for the comments.
+const MAX_HEADER_SIZE: usize = 16 * 1024 * 1024;
+
+/**
+ * Read the Vorbis comments of an Ogg Vorbis, Ogg Opus or FLAC stream.
+ *
+ * The reader is read from the start.
+ *
+ * # Return
+ * The comments as (key, value) pairs, with uppercase keys. Empty if the
+ * format has no Vorbis comment.
+ */
+pub fn read_comments<R: Read + Seek + ?Sized>(reader: &mut R) -> Vec<(String, String)> {
+    read_comments_within(reader, u64::max_value())
+}
+
+/**
+ * Read the Vorbis comments without reading or seeking past a position of
+ * the stream.
+ *
+ * The comments past the limit are ignored, as if the format had none.
+ *
+ * # Arguments
+ * * `reader` - The stream, read from the start
+ * * `limit` - The position where the reads stop
+ */
+pub fn read_comments_within<R: Read + Seek + ?Sized>(reader: &mut R,
+                                                     limit: u64) -> Vec<(String, String)> {
+    let reader = &mut Bounded { inner: reader, limit: limit };
+    if reader.seek(SeekFrom::Start(0)).is_err() {
+        return Vec::new();
+    }
+
+    let mut magic = [0u8; 4];
+    if reader.read_exact(&mut magic).is_err() {
+        return Vec::new();
+    }
+
+    let block = match &magic {
+        b"OggS" => read_ogg_comment_packet(reader),
+        b"fLaC" => read_flac_comment_block(reader),
+        b"ID3\x02" | b"ID3\x03" | b"ID3\x04" => {
+            if skip_id3(reader).is_none() || reader.read_exact(&mut magic).is_err()
+                || &magic != b"fLaC" {
+                None
+            } else {
+                read_flac_comment_block(reader)
+            }
+        },
+        _ => None
+    };
+
+    match block {
+        Some(block) => parse_comment_block(&block).unwrap_or_default(),
+        None => Vec::new()
+    }
+}
+
+/// A reader stopping at a position of the stream.
+struct Bounded<'r, R: 'r + ?Sized> {
+    inner: &'r mut R,
+    limit: u64
+}
+
+impl<'r, R: Read + Seek + ?Sized> Read for Bounded<'r, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let position = self.inner.seek(SeekFrom::Current(0))?;
+        if position >= self.limit {
+            return Ok(0);
+        }
+        let len = cmp::min(buf.len() as u64, self.limit - position) as usize;
+        self.inner.read(&mut buf[..len])
+    }
+}
+
+impl<'r, R: Read + Seek + ?Sized> Seek for Bounded<'r, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset)   => Some(offset),
+            SeekFrom::Current(offset) => {
+                let position = self.inner.seek(SeekFrom::Current(0))?;
+                Some((position as i64 + offset) as u64)
+            },
+            SeekFrom::End(_)          => None
+        };
+        match target {
+            Some(target) if target > self.limit =>
+                Err(io::Error::new(io::ErrorKind::Other, "Can't seek past the limit.")),
+            _ => self.inner.seek(pos)
+        }
+    }
+}
+
+/**
+ * Get the value of a comment.
+ *
+ * # Arguments
+ * * `comments` - The comments returned by read_comments
+ * * `key` - The key of the comment, case insensitive
+ */
+pub fn get_comment<'r>(comments: &'r [(String, String)], key: &str) -> Option<&'r str> {
+    let key = key.to_uppercase();
+    comments.iter().find(|&&(ref k, _)| *k == key).map(|&(_, ref value)| value.as_str())
+}
+
+/// Skip an ID3v2 tag, the magic being already read.
+fn skip_id3<R: Read + Seek + ?Sized>(reader: &mut R) -> Option<()> {
+    let mut header = [0u8; 6];
+    reader.read_exact(&mut header).ok()?;
+    let size = header[2..6].iter().fold(0u64, |size, &b| (size << 7) | (b & 0x7f) as u64);
+    reader.seek(SeekFrom::Start(10 + size)).ok().map(|_| ())
+}
+
+/// Find the VORBIS_COMMENT metadata block of a FLAC stream.
+fn read_flac_comment_block<R: Read + Seek + ?Sized>(reader: &mut R) -> Option<Vec<u8>> {
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header).ok()?;
+        let last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7f;
+        let len = ((header[1] as usize) << 16) | ((header[2] as usize) << 8) | header[3] as usize;
+
+        if block_type == 4 {
+            let mut block = vec![0u8; len];
+            reader.read_exact(&mut block).ok()?;
+            return Some(block);
+        }
+        if last {
+            return None;
+        }
+        reader.seek(SeekFrom::Current(len as i64)).ok()?;
+    }
+}
+
+/// Assemble the second packet of the first logical Ogg stream, the magic of
+/// the first page being already read.
+fn read_ogg_comment_packet<R: Read + Seek + ?Sized>(reader: &mut R) -> Option<Vec<u8>> {
+    let mut serial = None;
+    let mut packet_index = 0;
+    let mut packet = Vec::new();
+    let mut first_page = true;
+
+    loop {
+        if !first_page {
+            let mut magic = [0u8; 4];
+            reader.read_exact(&mut magic).ok()?;
+            if &magic != b"OggS" {
+                return None;
+            }
+        }
+        first_page = false;
+
+        // version, type, granule position, serial, sequence, crc, segments
+        let mut header = [0u8; 23];
+        reader.read_exact(&mut header).ok()?;
+        let page_serial = read_u32_le(&header[14..18]);
+        let mut table = vec![0u8; header[22] as usize];
+        reader.read_exact(&mut table).ok()?;
+        let mut data = vec![0u8; table.iter().map(|&s| s as usize).sum()];
+        reader.read_exact(&mut data).ok()?;
+
+        if *serial.get_or_insert(page_serial) != page_serial {
+            continue;
+        }
+
+        let mut offset = 0;
+        for &segment in table.iter() {
+            if packet_index == 1 {
+                packet.extend_from_slice(&data[offset..offset + segment as usize]);
+                if packet.len() > MAX_HEADER_SIZE {
+                    return None;
+                }
+            }
+            offset += segment as usize;
+            if segment < 255 {
+                if packet_index == 1 {
+                    return strip_packet_magic(packet);
+                }
+                packet_index += 1;
+            }
+        }
+    }
+}
+
+/// Remove the Vorbis or Opus magic in front of a comment packet.
+fn strip_packet_magic(packet: Vec<u8>) -> Option<Vec<u8>> {
+    if packet.starts_with(b"\x03vorbis") {
+        Some(packet[7..].to_vec())
+    } else if packet.starts_with(b"OpusTags") {
+        Some(packet[8..].to_vec())
+    } else {
+        None
+    }
+}
+
+fn read_u32_le(bytes: &[u8]) -> u32 {
+    bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24
+}
+
+/// Parse a Vorbis comment block: vendor string then `KEY=value` strings.
+fn parse_comment_block(block: &[u8]) -> Option<Vec<(String, String)>> {
+    let mut offset = 0;
+    let mut next_bytes = |len: usize| -> Option<&[u8]> {
+        if offset + len > block.len() {
+            return None;
+        }
+        offset += len;
+        Some(&block[offset - len..offset])
+    };
+
+    let vendor_len = read_u32_le(next_bytes(4)?) as usize;
+    next_bytes(vendor_len)?;
+    let count = read_u32_le(next_bytes(4)?);
+
+    let mut comments = Vec::new();
+    for _ in 0..count {
+        let len = read_u32_le(next_bytes(4)?) as usize;
+        let comment = String::from_utf8_lossy(next_bytes(len)?);
+        if let Some(separator) = comment.find('=') {
+            comments.push((comment[..separator].to_uppercase(),
+                           comment[separator + 1..].to_string()));
+        }
+    }
+    Some(comments)
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use std::io::Cursor;
+    use comments::{read_comments, read_comments_within, get_comment};
+
+    fn comment_block(comments: &[&str]) -> Vec<u8> {
+        let mut block = Vec::new();
+        let push_string = |block: &mut Vec<u8>, s: &[u8]| {
+            block.extend_from_slice(&[s.len() as u8, 0, 0, 0]);
+            block.extend_from_slice(s);
+        };
+        push_string(&mut block, b"ears");
+        block.extend_from_slice(&[comments.len() as u8, 0, 0, 0]);
+        for comment in comments {
+            push_string(&mut block, comment.as_bytes());
+        }
+        block
+    }
+
+    fn ogg_page(packets: &[&[u8]]) -> Vec<u8> {
+        let mut table = Vec::new();
+        let mut data = Vec::new();
+        for packet in packets {
+            let mut len = packet.len();
+            while len >= 255 {
+                table.push(255);
+                len -= 255;
+            }
+            table.push(len as u8);
+            data.extend_from_slice(packet);
+        }
+        let mut page = b"OggS".to_vec();
+        page.extend_from_slice(&[0u8; 23]);
+        page[26] = table.len() as u8;
+        page.extend_from_slice(&table);
+        page.extend_from_slice(&data);
+        page
+    }
+
+    #[test]
+    fn comments_read_ogg_OK() -> () {
+        let mut packet = b"\x03vorbis".to_vec();
+        packet.extend_from_slice(&comment_block(&["LOOPSTART=1000", "loopLength=5000"]));
+        let mut stream = ogg_page(&[b"\x01vorbis-identification"]);
+        stream.extend_from_slice(&ogg_page(&[&packet, b"\x05vorbis-setup"]));
+
+        let comments = read_comments(&mut Cursor::new(stream));
+
+        assert_eq!(get_comment(&comments, "LOOPSTART"), Some("1000"));
+        assert_eq!(get_comment(&comments, "looplength"), Some("5000"));
+    }
+
+    #[test]
+    fn comments_read_flac_OK() -> () {
+        let block = comment_block(&["BPM=120"]);
+        let mut stream = b"fLaC".to_vec();
+        // STREAMINFO then VORBIS_COMMENT as last block
+        stream.extend_from_slice(&[0, 0, 0, 34]);
+        stream.extend_from_slice(&[0u8; 34]);
+        stream.extend_from_slice(&[0x84, 0, 0, block.len() as u8]);
+        stream.extend_from_slice(&block);
+
+        let comments = read_comments(&mut Cursor::new(stream));
+
+        assert_eq!(get_comment(&comments, "bpm"), Some("120"));
+    }
+
+    #[test]
+    fn comments_read_within_OK() -> () {
+        let block = comment_block(&["BPM=120"]);
+        let mut stream = b"fLaC".to_vec();
+        stream.extend_from_slice(&[0x84, 0, 0, block.len() as u8]);
+        stream.extend_from_slice(&block);
+        let len = stream.len() as u64;
+        let mut reader = Cursor::new(stream);
+
+        assert!(read_comments_within(&mut reader, len - 1).is_empty());
+        assert!(reader.position() <= len - 1);
+        let comments = read_comments_within(&mut reader, len);
+        assert_eq!(get_comment(&comments, "bpm"), Some("120"));
+    }
+
+    #[test]
+    fn comments_read_FAIL() -> () {
+        assert!(read_comments(&mut Cursor::new(b"RIFF....WAVE".to_vec())).is_empty());
+        assert!(read_comments(&mut Cursor::new(b"OggS".to_vec())).is_empty());
+    }
+}
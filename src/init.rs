@@ -61,6 +61,25 @@ pub fn init_in() -> Result<RecordContext, String> {
     return OpenAlData::check_al_input_context()
 }
 
+/**
+ * Get the mixing frequency of the output device
+ *
+ * Use it as the `resample_rate` of the LoadOptions to avoid any resampling
+ * while playing.
+ *
+ * # Return
+ * `Some(frequency)` in Hz, `None` if the context can't be created
+ *
+ * # Example
+ * ```no_run
+ * let mut options = ears::LoadOptions::default();
+ * options.resample_rate = ears::get_device_frequency();
+ * ```
+ */
+pub fn get_device_frequency() -> Option<i32> {
+    OpenAlData::get_device_frequency()
+}
+
 #[cfg(test)]
 mod test {
     #![allow(non_snake_case)]
@@ -0,0 +1,1084 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Play the stems of a piece of music in sample lock, with a gain per layer.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+use sndfile::{SndFile, SndInfo};
+use sndfile::OpenMode::Read;
+use sndfile::SeekMode::SeekSet;
+use states::State;
+use states::State::{Initial, Playing, Paused, Stopped};
+use audio_controller::AudioController;
+use sample::{SampleType, Samples};
+use load_options::{LoadOptions, validate_header, has_known_length};
+use stream_config::StreamConfig;
+use streamer;
+use streamer::{Stream, StreamId};
+use music::{fill_samples, clamp_loop_points};
+
+/**
+ * Play the stems of a piece of music in sample lock.
+ *
+ * Each stem is a layer with its own source, so its volume can change or
+ * fade at any time, while the layers share the transport controls: they
+ * are decoded chunk by chunk together and started at the same sample.
+ *
+ * The stems must have the same sample rate and length. The sample type, the
+ * loop points and the StreamConfig of the load options are used, the other
+ * options are ignored.
+ *
+ * # Example
+ * ```no_run
+ * use std::time::Duration;
+ * use ears::{InteractiveMusic, AudioController};
+ *
+ * let mut music = InteractiveMusic::new(&["path/to/strings.ogg",
+ *                                         "path/to/percussion.ogg",
+ *                                         "path/to/brass.ogg"]).unwrap();
+ * music.set_layer_volume(1, 0.).unwrap();
+ * music.set_layer_volume(2, 0.).unwrap();
+ * music.set_looping(true);
+ * music.play();
+ *
+ * // The fight begins
+ * music.fade_layer(1, 1., Duration::from_secs(2)).unwrap();
+ * ```
+ */
+pub struct InteractiveMusic {
+    /// The stems, in the order they were given
+    layers: Vec<Layer>,
+    /// The volumes of the layers, shared with the stream
+    mixer: Arc<Mutex<Mixer>>,
+    /// The sample rate of the stems
+    sample_rate: i32,
+    /// The length of the stems in frames
+    frames: u64,
+    /// Type of the samples sent to OpenAL
+    sample_type: SampleType,
+    /// The buffering of the stream
+    stream_config: StreamConfig,
+    is_looping: Arc<Mutex<bool>>,
+    /// The start and end frames of the loop
+    loop_points: Option<(u64, u64)>,
+    /// The stream registered with the streaming thread
+    stream_id: Option<StreamId>
+}
+
+/// A stem of an InteractiveMusic.
+struct Layer {
+    al_source: u32,
+    al_buffers: Vec<u32>,
+    file: SndFile,
+    channels: i32
+}
+
+impl InteractiveMusic {
+    /**
+     * Create a new InteractiveMusic
+     *
+     * # Argument
+     * * `paths` - The paths of the stems, one layer each
+     *
+     * # Return
+     * A `Result` containing Ok(InteractiveMusic) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new(paths: &[&str]) -> Result<InteractiveMusic, String> {
+        InteractiveMusic::new_with_options(paths, &LoadOptions::default())
+    }
+
+    /**
+     * Create a new InteractiveMusic with custom load options
+     *
+     * # Arguments
+     * * `paths` - The paths of the stems, one layer each
+     * * `options` - The options used to decode the stems
+     *
+     * # Return
+     * A `Result` containing Ok(InteractiveMusic) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new_with_options(paths: &[&str],
+                            options: &LoadOptions) -> Result<InteractiveMusic, String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        if paths.is_empty() {
+            return Err("An InteractiveMusic needs at least one stem.".into());
+        }
+        if let Err(err) = options.stream_config.validate() {
+            return Err(err);
+        }
+        let sample_type = options.sample_type.resolve();
+
+        let mut files = Vec::new();
+        for path in paths.iter() {
+            let file = match SndFile::new(path, Read) {
+                Ok(file) => file,
+                Err(err) => return Err(format!("Error while loading stem {}: {}", path, err))
+            };
+            let infos = file.get_sndinfo();
+            if let Err(err) = validate_header(&infos) {
+                return Err(err);
+            }
+            if let Err(err) = check_stem(&infos, &files.first().map(|f: &SndFile| f.get_sndinfo())) {
+                return Err(format!("Stem {}: {}", path, err));
+            }
+            if sample_type.get_format(infos.channels).is_none() {
+                return Err("Unrecognized music format.".into());
+            }
+            files.push(file);
+        }
+
+        let infos = files[0].get_sndinfo();
+        let loop_points = match options.loop_points {
+            Some(region) => clamp_loop_points(region, infos.samplerate, infos.frames as u64),
+            None => match files[0].get_loop_points() {
+                Ok(loop_points) => loop_points,
+                Err(err)        => return Err(err)
+            }
+        };
+
+        let mut layers = Vec::new();
+        for file in files.into_iter() {
+            let mut source_id = 0;
+            let mut buffer_ids = vec![0; options.stream_config.buffer_count];
+            al::alGenSources(1, &mut source_id);
+            al::alGenBuffers(buffer_ids.len() as i32, &mut buffer_ids[0]);
+            layers.push(Layer {
+                al_source: source_id,
+                al_buffers: buffer_ids,
+                channels: file.get_sndinfo().channels,
+                file: file
+            });
+        }
+
+        if let Some(err) = al::openal_has_error() {
+            // Release the sources and buffers created before the error
+            for layer in layers.iter_mut() {
+                unsafe {
+                    ffi::alDeleteBuffers(layer.al_buffers.len() as i32, &mut layer.al_buffers[0]);
+                    ffi::alDeleteSources(1, &mut layer.al_source);
+                }
+            }
+            return Err(format!("Internal OpenAL error: {}", err));
+        };
+
+        Ok(InteractiveMusic {
+            mixer: Arc::new(Mutex::new(Mixer::new(layers.len()))),
+            layers: layers,
+            sample_rate: infos.samplerate,
+            frames: infos.frames as u64,
+            sample_type: sample_type,
+            stream_config: options.stream_config,
+            is_looping: Arc::new(Mutex::new(false)),
+            loop_points: loop_points,
+            stream_id: None
+        })
+    }
+
+    /// Get the number of layers.
+    pub fn get_layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Get the length of the stems in seconds.
+    pub fn get_duration(&self) -> f32 {
+        self.frames as f32 / self.sample_rate as f32
+    }
+
+    /**
+     * Set the volume of a layer, cancelling its fade.
+     *
+     * The volume of the layer is multiplied by the volume of the
+     * InteractiveMusic.
+     *
+     * # Arguments
+     * * `layer` - The index of the layer
+     * * `volume` - The new volume of the layer, should be between 0. and 1.
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if there is no such layer.
+     */
+    pub fn set_layer_volume(&mut self, layer: usize, volume: f32) -> Result<(), String> {
+        self.fade_layer(layer, volume, Duration::from_millis(0))
+    }
+
+    /**
+     * Get the volume of a layer, following its fade.
+     *
+     * # Argument
+     * * `layer` - The index of the layer
+     */
+    pub fn get_layer_volume(&self, layer: usize) -> Option<f32> {
+        let mixer = self.mixer.lock().unwrap();
+        mixer.layers.get(layer).map(|gain| gain.get(Instant::now()))
+    }
+
+    /**
+     * Fade the volume of a layer.
+     *
+     * The fade goes on while the InteractiveMusic is paused or stopped. The
+     * volume is updated each time the stream is refilled, at the poll
+     * interval of the StreamConfig.
+     *
+     * # Arguments
+     * * `layer` - The index of the layer
+     * * `volume` - The volume at the end of the fade
+     * * `duration` - The duration of the fade
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if there is no such layer.
+     */
+    pub fn fade_layer(&mut self,
+                      layer: usize,
+                      volume: f32,
+                      duration: Duration) -> Result<(), String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        let mut mixer = self.mixer.lock().unwrap();
+        let now = Instant::now();
+        let gain = match mixer.layers.get_mut(layer) {
+            Some(gain) => {
+                gain.fade_to(volume, duration, now);
+                gain.get(now)
+            },
+            None => return Err(format!("The InteractiveMusic has no layer {}.", layer))
+        };
+        al::alSourcef(self.layers[layer].al_source, ffi::AL_GAIN, gain * mixer.volume);
+        Ok(())
+    }
+
+    /// Get the sources of the layers.
+    fn get_sources(&self) -> Vec<u32> {
+        self.layers.iter().map(|layer| layer.al_source).collect()
+    }
+
+    /// Start streaming the stems from their beginning.
+    fn start(&mut self) -> () {
+        let chunk_samples = self.stream_config.get_chunk_samples(self.sample_rate, 1);
+        let mut stems = Vec::new();
+        for layer in self.layers.iter_mut() {
+            layer.file.seek(0, SeekSet);
+            stems.push(Stem {
+                al_source: layer.al_source,
+                file: layer.file.clone(),
+                channels: layer.channels,
+                samples: Samples::new(SampleType::Float32, chunk_samples * layer.channels as usize)
+            });
+        }
+
+        let mut stream = StemsStream {
+            stems: stems,
+            mixer: self.mixer.clone(),
+            sample_rate: self.sample_rate,
+            sample_type: self.sample_type,
+            position: 0,
+            at_end: false,
+            is_looping: self.is_looping.clone(),
+            loop_points: self.loop_points,
+            poll_interval: self.stream_config.poll_interval
+        };
+        stream.apply_gains();
+
+        let mut queued = 0;
+        for index in 0..self.stream_config.buffer_count {
+            let buffers: Vec<u32> = self.layers.iter().map(|layer| layer.al_buffers[index]).collect();
+            if !stream.queue_chunk(&buffers) {
+                break;
+            }
+            queued += 1;
+        }
+        if queued == 0 {
+            return;
+        }
+        al::alSourcePlayv(&self.get_sources());
+        self.stream_id = Some(streamer::register(Box::new(stream)));
+    }
+}
+
+/// Check that a stem can be played in sample lock with the first one.
+fn check_stem(infos: &SndInfo, first: &Option<SndInfo>) -> Result<(), String> {
+    let first = match *first {
+        Some(ref first) => first,
+        None            => return Ok(())
+    };
+    if infos.samplerate != first.samplerate {
+        return Err(format!("sample rate {} differs from the first stem ({}).",
+                           infos.samplerate, first.samplerate));
+    }
+    if !has_known_length(infos) || !has_known_length(first) {
+        return Err("the length of the stems is unknown.".into());
+    }
+    if infos.frames != first.frames {
+        return Err(format!("length of {} frames differs from the first stem ({}).",
+                           infos.frames, first.frames));
+    }
+    Ok(())
+}
+
+/// A fade of the volume of a layer.
+#[derive(Clone, Copy)]
+struct Fade {
+    from: f32,
+    start: Instant,
+    duration: Duration
+}
+
+/// The volume of a layer.
+#[derive(Clone, Copy)]
+struct LayerGain {
+    /// The volume, or the volume at the end of the fade
+    volume: f32,
+    fade: Option<Fade>
+}
+
+impl LayerGain {
+    /// Get the volume at a given time.
+    fn get(&self, now: Instant) -> f32 {
+        match self.fade {
+            Some(fade) => fade_volume(fade.from,
+                                      self.volume,
+                                      now.duration_since(fade.start),
+                                      fade.duration),
+            None => self.volume
+        }
+    }
+
+    /// Fade from the current volume, an instant fade sets the volume.
+    fn fade_to(&mut self, volume: f32, duration: Duration, now: Instant) -> () {
+        self.fade = if duration > Duration::from_millis(0) {
+            Some(Fade {
+                from: self.get(now),
+                start: now,
+                duration: duration
+            })
+        } else {
+            None
+        };
+        self.volume = volume;
+    }
+}
+
+/// The volumes of the layers and of the whole InteractiveMusic.
+struct Mixer {
+    volume: f32,
+    layers: Vec<LayerGain>
+}
+
+impl Mixer {
+    fn new(layers: usize) -> Mixer {
+        Mixer {
+            volume: 1.,
+            layers: vec![LayerGain { volume: 1., fade: None }; layers]
+        }
+    }
+}
+
+/// Interpolate the volume during a fade.
+fn fade_volume(from: f32, to: f32, elapsed: Duration, duration: Duration) -> f32 {
+    let to_secs = |d: Duration| d.as_secs() as f32 + d.subsec_nanos() as f32 / 1e9;
+    let duration = to_secs(duration);
+    if duration <= 0. {
+        return to;
+    }
+    let t = to_secs(elapsed) / duration;
+    if t >= 1. { to } else { from + (to - from) * t }
+}
+
+/// The decoder of a stem, used by the streaming thread.
+struct Stem {
+    al_source: u32,
+    /// A handle to the file, sharing its position with the layer
+    file: SndFile,
+    channels: i32,
+    /// The buffer the file is decoded in
+    samples: Samples
+}
+
+/// The state of a playing InteractiveMusic, updated by the streaming thread.
+struct StemsStream {
+    stems: Vec<Stem>,
+    mixer: Arc<Mutex<Mixer>>,
+    sample_rate: i32,
+    sample_type: SampleType,
+    /// The next frame read from the stems
+    position: u64,
+    /// True once the stems are entirely read
+    at_end: bool,
+    is_looping: Arc<Mutex<bool>>,
+    loop_points: Option<(u64, u64)>,
+    poll_interval: Duration
+}
+
+impl StemsStream {
+    /// Set the gain of the sources, following the fades.
+    fn apply_gains(&mut self) -> () {
+        let now = Instant::now();
+        let mut mixer = self.mixer.lock().unwrap();
+        let volume = mixer.volume;
+        for (stem, gain) in self.stems.iter().zip(mixer.layers.iter_mut()) {
+            al::alSourcef(stem.al_source, ffi::AL_GAIN, gain.get(now) * volume);
+            if let Some(fade) = gain.fade {
+                if now.duration_since(fade.start) >= fade.duration {
+                    gain.fade = None;
+                }
+            }
+        }
+    }
+
+    /**
+     * Decode the next chunk of every stem and queue it on its source.
+     *
+     * # Return
+     * False if the stems are entirely played, or if a chunk can't be
+     * uploaded, in which case no stem is queued.
+     */
+    fn queue_chunk(&mut self, buffers: &[u32]) -> bool {
+        let is_looping = *self.is_looping.lock().unwrap();
+        if self.at_end && !is_looping {
+            return false;
+        }
+
+        // Read the same frames from every stem, the ones ending first are
+        // padded with silence
+        let mut frames = 0;
+        let mut reads = Vec::new();
+        for stem in self.stems.iter_mut() {
+            let mut position = self.position;
+            let read = fill_samples(&mut stem.file,
+                                    &mut stem.samples,
+                                    &mut position,
+                                    is_looping,
+                                    self.loop_points,
+                                    stem.channels,
+                                    &mut Vec::new());
+            frames = frames.max(read / stem.channels as usize);
+            reads.push((read, position));
+        }
+        self.position = reads.iter().map(|&(_, position)| position).max().unwrap_or(0);
+        self.at_end = (frames * self.stems[0].channels as usize) < self.stems[0].samples.len();
+        if frames == 0 {
+            return false;
+        }
+
+        for ((stem, &(read, _)), buffer) in self.stems.iter_mut().zip(reads.iter()).zip(buffers) {
+            let len = frames * stem.channels as usize;
+            let mut samples = stem.samples.to_f32();
+            for sample in samples[read..len].iter_mut() {
+                *sample = 0.;
+            }
+            let samples = Samples::from_f32(self.sample_type, &samples[..len]);
+            if samples.buffer_data(*buffer, len, stem.channels, self.sample_rate).is_err() {
+                return false;
+            }
+        }
+        // Queue the stems once they are all uploaded, to keep them in lock
+        for (stem, buffer) in self.stems.iter().zip(buffers) {
+            al::alSourceQueueBuffers(stem.al_source, 1, buffer);
+        }
+        true
+    }
+}
+
+impl Stream for StemsStream {
+    fn update(&mut self) -> bool {
+        self.apply_gains();
+
+        let sources: Vec<u32> = self.stems.iter().map(|stem| stem.al_source).collect();
+        let status = al::alGetState(sources[0]);
+        if status == ffi::AL_PAUSED {
+            return true;
+        }
+        // The sources run out of buffers at the same sample
+        let starved = status == ffi::AL_STOPPED;
+
+        // Refill the chunks played by every source
+        let processed = sources.iter().map(|source| {
+            let mut processed = 0;
+            al::alGetSourcei(*source, ffi::AL_BUFFERS_PROCESSED, &mut processed);
+            processed
+        }).min().unwrap_or(0);
+
+        let mut refilled = 0;
+        for _ in 0..processed {
+            let mut buffers = vec![0; sources.len()];
+            for (source, buffer) in sources.iter().zip(buffers.iter_mut()) {
+                al::alSourceUnqueueBuffers(*source, 1, buffer);
+            }
+            if self.queue_chunk(&buffers) {
+                refilled += 1;
+            }
+        }
+
+        if starved {
+            if refilled == 0 {
+                return false;
+            }
+            // Restart the layers together
+            al::alSourcePlayv(&sources);
+        }
+        true
+    }
+
+    fn get_poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+}
+
+impl Drop for StemsStream {
+    /// Release the buffers of the sources.
+    fn drop(&mut self) -> () {
+        let sources: Vec<u32> = self.stems.iter().map(|stem| stem.al_source).collect();
+        al::alSourceStopv(&sources);
+        for source in sources.iter() {
+            al::alSourcei(*source, ffi::AL_BUFFER, 0);
+        }
+    }
+}
+
+impl AudioController for InteractiveMusic {
+    /**
+     * Play or resume the InteractiveMusic.
+     *
+     * The layers are started together from the beginning of the stems, or
+     * resumed together if the InteractiveMusic is paused.
+     */
+    fn play(&mut self) -> () {
+        check_openal_context!(());
+
+        match self.get_state() {
+            Paused => { al::alSourcePlayv(&self.get_sources()); return; },
+            _      => {
+                self.stop();
+                self.start();
+            }
+        }
+    }
+
+    /**
+     * Pause the InteractiveMusic.
+     */
+    fn pause(&mut self) -> () {
+        check_openal_context!(());
+
+        al::alSourcePausev(&self.get_sources())
+    }
+
+    /**
+     * Stop the InteractiveMusic.
+     */
+    fn stop(&mut self) -> () {
+        check_openal_context!(());
+
+        if let Some(id) = self.stream_id.take() {
+            streamer::unregister(id);
+        }
+        al::alSourceStopv(&self.get_sources());
+    }
+
+    /**
+     * Check if the InteractiveMusic is playing or not.
+     *
+     * # Return
+     * True if the InteractiveMusic is playing, false otherwise.
+     */
+    fn is_playing(&self) -> bool {
+        match self.get_state() {
+            Playing     => true,
+            _           => false
+        }
+    }
+
+    /**
+     * Get the current state of the InteractiveMusic
+     *
+     * # Return
+     * The state of the InteractiveMusic as a variant of the enum State
+     */
+    fn get_state(&self) -> State {
+        check_openal_context!(Initial);
+
+        let state  = al::alGetState(self.layers[0].al_source);
+
+        match state {
+            ffi::AL_INITIAL => Initial,
+            ffi::AL_PLAYING => Playing,
+            ffi::AL_PAUSED  => Paused,
+            ffi::AL_STOPPED => Stopped,
+            _               => unreachable!()
+        }
+    }
+
+    /**
+     * Set the volume of the InteractiveMusic.
+     *
+     * A value of 1.0 means unattenuated. Each division by 2 equals an attenuation
+     * of about -6dB. Each multiplicaton by 2 equals an amplification of about
+     * +6dB.
+     *
+     * The volume of each layer is multiplied by this volume.
+     *
+     * # Argument
+     * * `volume` - The volume of the InteractiveMusic, should be between 0. and 1.
+     */
+    fn set_volume(&mut self, volume: f32) -> () {
+        check_openal_context!(());
+
+        let mut mixer = self.mixer.lock().unwrap();
+        mixer.volume = volume;
+        let now = Instant::now();
+        for (layer, gain) in self.layers.iter().zip(mixer.layers.iter()) {
+            al::alSourcef(layer.al_source, ffi::AL_GAIN, gain.get(now) * volume);
+        }
+    }
+
+    /**
+     * Get the volume of the InteractiveMusic.
+     *
+     * # Return
+     * The volume of the InteractiveMusic between 0. and 1.
+     */
+    fn get_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        self.mixer.lock().unwrap().volume
+    }
+
+    /**
+     * Set the minimal volume for a InteractiveMusic.
+     *
+     * The minimum volume allowed for a InteractiveMusic, after distance and cone
+     * attenation is applied (if applicable).
+     *
+     * # Argument
+     * * `min_volume` - The new minimal volume of the InteractiveMusic should be
+     * between 0. and 1.
+     */
+    fn set_min_volume(&mut self, min_volume: f32) -> () {
+        check_openal_context!(());
+
+        for layer in self.layers.iter() {
+            al::alSourcef(layer.al_source, ffi::AL_MIN_GAIN, min_volume);
+        }
+    }
+
+    /**
+     * Get the minimal volume of the InteractiveMusic.
+     *
+     * # Return
+     * The minimal volume of the InteractiveMusic between 0. and 1.
+     */
+    fn get_min_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut volume : f32 = 0.;
+        al::alGetSourcef(self.layers[0].al_source, ffi::AL_MIN_GAIN, &mut volume);
+        volume
+    }
+
+    /**
+     * Set the maximal volume for a InteractiveMusic.
+     *
+     * The maximum volume allowed for a InteractiveMusic, after distance and cone
+     * attenation is applied (if applicable).
+     *
+     * # Argument
+     * * `max_volume` - The new maximal volume of the InteractiveMusic should be
+     * between 0. and 1.
+     */
+    fn set_max_volume(&mut self, max_volume: f32) -> () {
+        check_openal_context!(());
+
+        for layer in self.layers.iter() {
+            al::alSourcef(layer.al_source, ffi::AL_MAX_GAIN, max_volume);
+        }
+    }
+
+    /**
+     * Get the maximal volume of the InteractiveMusic.
+     *
+     * # Return
+     * The maximal volume of the InteractiveMusic between 0. and 1.
+     */
+    fn get_max_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut volume : f32 = 0.;
+        al::alGetSourcef(self.layers[0].al_source, ffi::AL_MAX_GAIN, &mut volume);
+        volume
+    }
+
+    /**
+     * Set the InteractiveMusic looping or not
+     *
+     * The default looping is false.
+     *
+     * # Arguments
+     * `looping` - The new looping state.
+     */
+    fn set_looping(&mut self, looping: bool) -> () {
+        *self.is_looping.lock().unwrap() = looping;
+    }
+
+    /**
+     * Check if the InteractiveMusic is looping or not
+     *
+     * # Return
+     * True if the InteractiveMusic is looping, false otherwise.
+     */
+    fn is_looping(&self) -> bool {
+        *self.is_looping.lock().unwrap()
+    }
+
+    /**
+     * Set the pitch of the InteractiveMusic.
+     *
+     * A multiplier for the frequency (sample rate) of the InteractiveMusic's buffer.
+     *
+     * Default pitch is 1.0.
+     *
+     * # Argument
+     * * `new_pitch` - The new pitch of the InteractiveMusic in the range [0.5 - 2.0]
+     */
+    fn set_pitch(&mut self, pitch: f32) -> () {
+        check_openal_context!(());
+
+        for layer in self.layers.iter() {
+            al::alSourcef(layer.al_source, ffi::AL_PITCH, pitch);
+        }
+    }
+
+    /**
+     * Set the pitch of the InteractiveMusic.
+     *
+     * # Return
+     * The pitch of the InteractiveMusic in the range [0.5 - 2.0]
+     */
+    fn get_pitch(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut pitch = 0.;
+        al::alGetSourcef(self.layers[0].al_source, ffi::AL_PITCH, &mut pitch);
+        pitch
+    }
+
+    /**
+     * Set the position of the InteractiveMusic relative to the listener or absolute.
+     *
+     * Default position is absolute.
+     *
+     * # Argument
+     * `relative` - True to set InteractiveMusic relative to the listener false to set the
+     * InteractiveMusic position absolute.
+     */
+    fn set_relative(&mut self, relative: bool) -> () {
+        check_openal_context!(());
+
+        let relative = match relative {
+            true    => ffi::ALC_TRUE as i32,
+            false   => ffi::ALC_FALSE as i32
+        };
+        for layer in self.layers.iter() {
+            al::alSourcei(layer.al_source, ffi::AL_SOURCE_RELATIVE, relative);
+        }
+    }
+
+    /**
+     * Is the InteractiveMusic relative to the listener or not?
+     *
+     * # Return
+     * True if the InteractiveMusic is relative to the listener false otherwise
+     */
+    fn is_relative(&mut self) -> bool {
+        check_openal_context!(false);
+
+        let mut boolean = 0;
+        al::alGetSourcei(self.layers[0].al_source, ffi::AL_SOURCE_RELATIVE, &mut boolean);
+        match boolean as i8 {
+            ffi::ALC_TRUE  => true,
+            ffi::ALC_FALSE => false,
+            _              => unreachable!()
+        }
+    }
+
+    /**
+     * Set the InteractiveMusic location in three dimensional space.
+     *
+     * OpenAL, like OpenGL, uses a right handed coordinate system, where in a
+     * frontal default view X (thumb) points right, Y points up (index finger),
+     * and Z points towards the viewer/camera (middle finger).
+     * To switch from a left handed coordinate system, flip the sign on the Z
+     * coordinate.
+     *
+     * Default position is [0., 0., 0.].
+     *
+     * # Argument
+     * * `position` - A three dimensional vector of f32 containing the position
+     * of the listener [x, y, z].
+     */
+    fn set_position(&mut self, position: [f32; 3]) -> () {
+        check_openal_context!(());
+
+        for layer in self.layers.iter() {
+            al::alSourcefv(layer.al_source, ffi::AL_POSITION, &position[0]);
+        }
+    }
+
+    /**
+     * Get the position of the InteractiveMusic in three dimensional space.
+     *
+     * # Return
+     * A three dimensional vector of f32 containing the position of the
+     * listener [x, y, z].
+     */
+    fn get_position(&self) -> [f32; 3] {
+        check_openal_context!([0.; 3]);
+
+        let mut position : [f32; 3] = [0.; 3];
+        al::alGetSourcefv(self.layers[0].al_source, ffi::AL_POSITION, &mut position[0]);
+        position
+    }
+
+    /**
+     * Set the direction of the InteractiveMusic.
+     *
+     * Specifies the current direction in local space.
+     *
+     * The default direction is: [0., 0., 0.]
+     *
+     * # Argument
+     * `direction` - The new direction of the InteractiveMusic.
+     */
+    fn set_direction(&mut self, direction: [f32; 3]) -> () {
+        check_openal_context!(());
+
+        for layer in self.layers.iter() {
+            al::alSourcefv(layer.al_source, ffi::AL_DIRECTION, &direction[0]);
+        }
+    }
+
+    /**
+     * Get the direction of the InteractiveMusic.
+     *
+     * # Return
+     * The current direction of the InteractiveMusic.
+     */
+    fn get_direction(&self)  -> [f32; 3] {
+        check_openal_context!([0.; 3]);
+
+        let mut direction : [f32; 3] = [0.; 3];
+        al::alGetSourcefv(self.layers[0].al_source, ffi::AL_DIRECTION, &mut direction[0]);
+        direction
+    }
+
+    /**
+     * Set the maximum distance of the InteractiveMusic.
+     *
+     * The distance above which the source is not attenuated any further with a
+     * clamped distance model, or where attenuation reaches 0.0 gain for linear
+     * distance models with a default rolloff factor.
+     *
+     * The default maximum distance is +inf.
+     *
+     * # Argument
+     * `max_distance` - The new maximum distance in the range [0., +inf]
+     */
+    fn set_max_distance(&mut self, max_distance: f32) -> () {
+        check_openal_context!(());
+
+        for layer in self.layers.iter() {
+            al::alSourcef(layer.al_source, ffi::AL_MAX_DISTANCE, max_distance);
+        }
+    }
+
+    /**
+     * Get the maximum distance of the InteractiveMusic.
+     *
+     * # Return
+     * The maximum distance of the InteractiveMusic in the range [0., +inf]
+     */
+    fn get_max_distance(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut max_distance = 0.;
+        al::alGetSourcef(self.layers[0].al_source, ffi::AL_MAX_DISTANCE, &mut max_distance);
+        max_distance
+    }
+
+    /**
+     * Set the reference distance of the InteractiveMusic.
+     *
+     * The distance in units that no attenuation occurs.
+     * At 0.0, no distance attenuation ever occurs on non-linear
+     * attenuation models.
+     *
+     * The default distance reference is 1.
+     *
+     * # Argument
+     * * `ref_distance` - The new reference distance of the InteractiveMusic.
+     */
+    fn set_reference_distance(&mut self, ref_distance: f32) -> () {
+        check_openal_context!(());
+
+        for layer in self.layers.iter() {
+            al::alSourcef(layer.al_source, ffi::AL_REFERENCE_DISTANCE, ref_distance);
+        }
+    }
+
+    /**
+     * Get the reference distance of the InteractiveMusic.
+     *
+     * # Return
+     * The current reference distance of the InteractiveMusic.
+     */
+    fn get_reference_distance(&self) -> f32 {
+        check_openal_context!(1.);
+
+        let mut ref_distance = 0.;
+        al::alGetSourcef(self.layers[0].al_source,
+                         ffi::AL_REFERENCE_DISTANCE,
+                         &mut ref_distance);
+        ref_distance
+    }
+
+    /**
+     * Set the attenuation of a InteractiveMusic.
+     *
+     * Multiplier to exaggerate or diminish distance attenuation.
+     * At 0.0, no distance attenuation ever occurs.
+     *
+     * The default attenuation is 1.
+     *
+     * # Arguments
+     * `attenuation` - The new attenuation for the InteractiveMusic in the range [0., 1.].
+     */
+    fn set_attenuation(&mut self, attenuation: f32) -> () {
+        check_openal_context!(());
+
+        for layer in self.layers.iter() {
+            al::alSourcef(layer.al_source, ffi::AL_ROLLOFF_FACTOR, attenuation);
+        }
+    }
+
+    /**
+     * Get the attenuation of a InteractiveMusic.
+     *
+     * # Return
+     * The current attenuation for the InteractiveMusic in the range [0., 1.].
+     */
+    fn get_attenuation(&self) -> f32 {
+        check_openal_context!(1.);
+
+        let mut attenuation = 0.;
+        al::alGetSourcef(self.layers[0].al_source,
+                         ffi::AL_ROLLOFF_FACTOR,
+                         &mut attenuation);
+        attenuation
+    }
+}
+
+impl Drop for InteractiveMusic {
+    /// Destroy all the resources of the InteractiveMusic.
+    fn drop(&mut self) -> () {
+        if let Some(id) = self.stream_id.take() {
+            streamer::unregister(id);
+        }
+        unsafe {
+            for layer in self.layers.iter_mut() {
+                al::alSourcei(layer.al_source, ffi::AL_BUFFER, 0);
+                ffi::alDeleteBuffers(layer.al_buffers.len() as i32, &mut layer.al_buffers[0]);
+                ffi::alDeleteSources(1, &mut layer.al_source);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use std::time::Duration;
+
+    use interactive_music::{InteractiveMusic, fade_volume};
+    use states::State::{Playing, Paused, Stopped};
+    use audio_controller::AudioController;
+
+    #[test]
+    fn interactive_music_fade_volume_OK() -> () {
+        let second = Duration::from_secs(1);
+
+        assert_eq!(fade_volume(0., 1., Duration::from_millis(0), second), 0.);
+        assert_eq!(fade_volume(0., 1., Duration::from_millis(500), second), 0.5);
+        assert_eq!(fade_volume(1., 0., Duration::from_millis(250), second), 0.75);
+        assert_eq!(fade_volume(0., 1., Duration::from_secs(2), second), 1.);
+        assert_eq!(fade_volume(0., 1., second, Duration::from_millis(0)), 1.);
+    }
+
+    #[test]
+    #[ignore]
+    fn interactive_music_create_OK() -> () {
+        let music = InteractiveMusic::new(&["res/shot.wav", "res/shot.wav"]).unwrap();
+
+        assert_eq!(music.get_layer_count(), 2);
+        assert_eq!(music.get_layer_volume(1), Some(1.));
+        assert_eq!(music.get_layer_volume(2), None);
+    }
+
+    #[test]
+    #[ignore]
+    fn interactive_music_create_FAIL() -> () {
+        assert!(InteractiveMusic::new(&[]).is_err());
+        assert!(InteractiveMusic::new(&["toto.wav"]).is_err());
+        assert!(InteractiveMusic::new(&["res/shot.wav", "res/explosion.ogg"]).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn interactive_music_transport_OK() -> () {
+        let mut music = InteractiveMusic::new(&["res/shot.wav", "res/shot.wav"]).unwrap();
+
+        music.play();
+        assert_eq!(music.get_state(), Playing);
+        music.pause();
+        assert_eq!(music.get_state(), Paused);
+        music.stop();
+        assert_eq!(music.get_state(), Stopped);
+    }
+
+    #[test]
+    #[ignore]
+    fn interactive_music_layer_volume_OK() -> () {
+        let mut music = InteractiveMusic::new(&["res/shot.wav", "res/shot.wav"]).unwrap();
+
+        music.set_layer_volume(1, 0.25).unwrap();
+        assert_eq!(music.get_layer_volume(1), Some(0.25));
+        assert!(music.set_layer_volume(2, 0.25).is_err());
+        music.fade_layer(1, 1., Duration::from_secs(10)).unwrap();
+        let volume = music.get_layer_volume(1).unwrap();
+        assert!(volume >= 0.25 && volume < 1.);
+    }
+}
@@ -102,6 +102,29 @@ impl OpenAlData {
         }
     }
 
+    /// Get the mixing frequency of the output device.
+    ///
+    /// # Return
+    /// The frequency in Hz, or None if the context can't be created.
+    pub fn get_device_frequency() -> Option<i32> {
+        if OpenAlData::check_al_context().is_err() {
+            return None;
+        }
+        match AL_CONTEXT.lock() {
+            Ok(guard) => match *guard {
+                Ok(ref context) => {
+                    let mut frequency = 0;
+                    unsafe {
+                        ffi::alcGetIntegerv(context.al_device, ffi::ALC_FREQUENCY, 1, &mut frequency);
+                    }
+                    if frequency > 0 { Some(frequency) } else { None }
+                },
+                Err(_) => None
+            },
+            Err(_) => None
+        }
+    }
+
     fn is_input_context_init() -> Result<RecordContext, String> {
         match AL_CONTEXT.lock() {
             Ok(mut guard) => {
@@ -95,11 +95,31 @@ extern crate libc;
 extern crate lazy_static;
 
 // Reexport public API
-pub use einit::{init, init_in};
+pub use einit::{init, init_in, get_device_frequency};
 pub use music::Music;
+pub use playlist::{Playlist, PlaylistEvent, Repeat};
+pub use procedural::{ProceduralSound, Generator};
+pub use streaming_source::StreamingSource;
+pub use interactive_music::InteractiveMusic;
 pub use sound::Sound;
 pub use states::State;
 pub use sound_data::SoundData;
+pub use sample::{Sample, SampleType};
+pub use sample_buffer::SampleBuffer;
+pub use load_options::LoadOptions;
+pub use stream_config::{StreamConfig, Underrun, UnderrunStats};
+pub use beat_clock::{Tempo, BeatPosition, Boundary};
+pub use channel_mix::{ChannelMix, Downmix};
+pub use normalization::{Normalization, ReplayGain, LoudnessMeter};
+pub use resampler::{Resampler, resample, get_resamplers, get_default_resampler};
+pub use time_stretch::{TimeStretcher, time_stretch, MIN_SPEED, MAX_SPEED};
+pub use loaded_audio::{load_audio, LoadedAudio};
+pub use sprite::{SpriteSheet, Region};
+pub use sound_cache::{SoundCache, CachePolicy};
+pub use loader::{Loader, LoadHandle};
+pub use voice_manager::{VoiceManager, VoiceParams, VoiceId};
+pub use oneshot::{play_oneshot, OneShotParams};
+pub use random_sound::RandomSound;
 pub use audio_controller::AudioController;
 pub use audio_tags::{AudioTags, Tags};
 pub use recorder::Recorder;
@@ -110,6 +130,8 @@ pub use record_context::RecordContext;
 mod internal;
 mod openal;
 mod sndfile;
+mod comments;
+mod pipe_reader;
 
 // The public ears API
 
@@ -118,7 +140,29 @@ mod einit;
 pub mod listener;
 mod sound;
 mod music;
+mod playlist;
+mod procedural;
+mod streaming_source;
+mod interactive_music;
 mod sound_data;
+mod sample;
+mod sample_buffer;
+mod load_options;
+mod stream_config;
+mod beat_clock;
+mod streamer;
+mod channel_mix;
+mod normalization;
+mod resampler;
+mod time_stretch;
+mod loaded_audio;
+mod sprite;
+mod sound_cache;
+mod loader;
+mod voice_manager;
+mod oneshot;
+mod random_sound;
+mod rng;
 mod states;
 mod audio_controller;
 mod audio_tags;
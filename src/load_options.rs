@@ -0,0 +1,188 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! The options used to load a SoundData or a Music.
+
+use std::i32;
+
+use sample::SampleType;
+use sprite::{SpriteSheet, Region};
+use sndfile::SndInfo;
+use channel_mix::ChannelMix;
+use normalization::{Normalization, ReplayGain};
+use stream_config::StreamConfig;
+
+/// The highest channel count supported by the OpenAL formats.
+const MAX_CHANNELS: i32 = 8;
+
+/**
+ * Options used to decode a file into a SoundData or a Music.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{SoundData, LoadOptions, SampleType};
+ *
+ * let mut options = LoadOptions::default();
+ * options.sample_type = SampleType::Float32;
+ * let snd_data = SoundData::new_with_options("path/to/my/sound.wav", &options).unwrap();
+ * ```
+ */
+#[derive(Clone, Debug, Default)]
+pub struct LoadOptions {
+    /// The type of the samples stored in the OpenAL buffers
+    pub sample_type: SampleType,
+    /// The named regions to extract from a SoundData
+    pub sprites: SpriteSheet,
+    /// Add a region for each cue point of the file to a SoundData, running
+    /// until the next cue point or the end of the file
+    pub cue_regions: bool,
+    /// The loop points, overriding the ones stored in the file
+    pub loop_points: Option<Region>,
+    /// Keep the decoded samples of a SoundData in memory, to analyse them
+    /// without decoding the file again
+    pub keep_samples: bool,
+    /// The maximum size in bytes of the samples decoded in a SoundData
+    pub max_bytes: Option<usize>,
+    /// The maximum duration in seconds of a SoundData
+    pub max_duration: Option<f32>,
+    /// The conversion of the channels of the file
+    pub channel_mix: ChannelMix,
+    /// The sample rate to convert the samples to, e.g. the device frequency
+    pub resample_rate: Option<i32>,
+    /// The level the sound is brought to, applied as a gain under the volume
+    pub normalization: Normalization,
+    /// Use the ReplayGain tags of the file when it has them, instead of
+    /// the normalization
+    pub replay_gain: Option<ReplayGain>,
+    /// The buffering of a Music
+    pub stream_config: StreamConfig
+}
+
+impl LoadOptions {
+    /**
+     * Get the sample rate of the samples once loaded.
+     *
+     * # Argument
+     * * `sample_rate` - The sample rate of the file
+     */
+    #[doc(hidden)]
+    pub fn get_sample_rate(&self, sample_rate: i32) -> i32 {
+        match self.resample_rate {
+            Some(rate) if rate > 0 => rate,
+            _ => sample_rate
+        }
+    }
+
+    /**
+     * Check that decoded samples fit in the load limits.
+     *
+     * The size of an OpenAL buffer is always limited to 2 GB.
+     *
+     * # Return
+     * Ok(()) if the samples fit, Err(String) explaining which limit is
+     * exceeded otherwise.
+     */
+    #[doc(hidden)]
+    pub fn check_limits(&self,
+                        frames: u64,
+                        channels: i32,
+                        sample_rate: i32,
+                        sample_type: SampleType) -> Result<(), String> {
+        let bytes = frames.saturating_mul(channels as u64)
+                          .saturating_mul(sample_type.get_size() as u64);
+        if bytes > i32::MAX as u64 {
+            return Err(format!("The sound is too big for an OpenAL buffer: {} bytes decoded.",
+                               bytes));
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            if bytes > max_bytes as u64 {
+                return Err(format!("The sound exceeds the load limit: {} bytes decoded, \
+                                    the limit is {} bytes.", bytes, max_bytes));
+            }
+        }
+        if let Some(max_duration) = self.max_duration {
+            let duration = frames as f64 / sample_rate as f64;
+            if duration > max_duration as f64 {
+                return Err(format!("The sound exceeds the load limit: {:.2} seconds long, \
+                                    the limit is {:.2} seconds.", duration, max_duration));
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Check that the values of a file header are usable.
+ *
+ * # Return
+ * Ok(()) if the header is valid, Err(String) describing the invalid value
+ * otherwise.
+ */
+#[doc(hidden)]
+pub fn validate_header(infos: &SndInfo) -> Result<(), String> {
+    if infos.channels < 1 || infos.channels > MAX_CHANNELS {
+        return Err(format!("Invalid channel count in the file header: {}.", infos.channels));
+    }
+    if infos.samplerate <= 0 {
+        return Err(format!("Invalid sample rate in the file header: {}.", infos.samplerate));
+    }
+    Ok(())
+}
+
+/**
+ * Check if the length announced by a file header can be trusted.
+ *
+ * libsndfile reports an unknown length as the largest count.
+ */
+#[doc(hidden)]
+pub fn has_known_length(infos: &SndInfo) -> bool {
+    infos.frames > 0 && infos.frames < i64::max_value() / MAX_CHANNELS as i64
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use load_options::LoadOptions;
+    use sample::SampleType;
+
+    #[test]
+    fn loadoptions_check_limits_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.max_bytes = Some(4 * 44100);
+        options.max_duration = Some(1.);
+
+        assert!(options.check_limits(44100, 2, 44100, SampleType::Int16).is_ok());
+    }
+
+    #[test]
+    fn loadoptions_check_limits_FAIL() -> () {
+        let mut options = LoadOptions::default();
+        assert!(options.check_limits(u64::max_value(), 2, 44100, SampleType::Int16).is_err());
+
+        options.max_bytes = Some(1000);
+        assert!(options.check_limits(501, 1, 44100, SampleType::Int16).is_err());
+
+        options.max_bytes = None;
+        options.max_duration = Some(0.5);
+        assert!(options.check_limits(22051, 1, 44100, SampleType::UInt8).is_err());
+    }
+}
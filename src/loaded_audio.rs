@@ -0,0 +1,128 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Load a file as a SoundData, or stream it if it's too big.
+
+use internal::OpenAlData;
+use sndfile::SndFile;
+use sndfile::OpenMode::Read;
+use sound_data::SoundData;
+use music::Music;
+use load_options::{LoadOptions, validate_header, has_known_length};
+
+/// A file loaded whole in memory or streamed.
+pub enum LoadedAudio {
+    /// The file fits in the load limits and is decoded in memory
+    Sound(SoundData),
+    /// The file exceeds the load limits or has an unknown length, and is
+    /// streamed
+    Music(Music)
+}
+
+/**
+ * Load a file as a SoundData, falling back to a Music when it exceeds the
+ * load limits.
+ *
+ * # Arguments
+ * * `path` - The path of the file to load
+ * * `options` - The options used to decode the file, with the limits
+ *
+ * # Return
+ * A `Result` containing Ok(LoadedAudio) on success, Err(String)
+ * if there has been an error.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{load_audio, LoadedAudio, LoadOptions, Sound, AudioController};
+ * use std::rc::Rc;
+ * use std::cell::RefCell;
+ *
+ * let mut options = LoadOptions::default();
+ * options.max_duration = Some(30.);
+ *
+ * match load_audio("path/to/user/file.ogg", &options).unwrap() {
+ *     LoadedAudio::Sound(data) => {
+ *         let mut snd = Sound::new_with_data(Rc::new(RefCell::new(data))).unwrap();
+ *         snd.play();
+ *     },
+ *     LoadedAudio::Music(mut msc) => msc.play()
+ * }
+ * ```
+ */
+pub fn load_audio(path: &str, options: &LoadOptions) -> Result<LoadedAudio, String> {
+    check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+    let fits = {
+        let file = match SndFile::new(path, Read) {
+            Ok(file) => file,
+            Err(err) => {
+                return Err(format!("Error while loading file: {}", err));
+            }
+        };
+        let infos = file.get_sndinfo();
+        file.close();
+
+        if let Err(err) = validate_header(&infos) {
+            return Err(err);
+        }
+        has_known_length(&infos) && options.check_limits(infos.frames as u64,
+                                                         infos.channels,
+                                                         infos.samplerate,
+                                                         options.sample_type.resolve())
+                                           .is_ok()
+    };
+
+    if fits {
+        SoundData::new_with_options(path, options).map(LoadedAudio::Sound)
+    } else {
+        Music::new_with_options(path, options).map(LoadedAudio::Music)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use loaded_audio::{load_audio, LoadedAudio};
+    use load_options::LoadOptions;
+
+    #[test]
+    #[ignore]
+    fn loadedaudio_load_SOUND() -> () {
+        match load_audio("res/shot.wav", &LoadOptions::default()).unwrap() {
+            LoadedAudio::Sound(_) => {},
+            LoadedAudio::Music(_) => panic!("Expected a SoundData")
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn loadedaudio_load_MUSIC() -> () {
+        let mut options = LoadOptions::default();
+        options.max_bytes = Some(16);
+
+        match load_audio("res/shot.wav", &options).unwrap() {
+            LoadedAudio::Sound(_) => panic!("Expected a Music"),
+            LoadedAudio::Music(_) => {}
+        }
+    }
+}
@@ -0,0 +1,384 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Load SoundDatas and Musics in background threads.
+
+use std::thread;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex, Condvar};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender, Receiver};
+
+use sound_data;
+use sound_data::SoundData;
+use music::Music;
+use load_options::LoadOptions;
+
+/// A job run by the workers of a Loader.
+type Job = Box<dyn FnOnce() + Send>;
+
+/**
+ * Progress of a loading, shared between the worker and the LoadHandle.
+ */
+#[doc(hidden)]
+pub struct Progress {
+    decoded: AtomicUsize,
+    total: AtomicUsize,
+    cancelled: AtomicBool
+}
+
+impl Progress {
+    pub fn new() -> Progress {
+        Progress {
+            decoded: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false)
+        }
+    }
+
+    /// Set the total number of samples to decode.
+    pub fn set_total(&self, total: usize) -> () {
+        self.total.store(total, Ordering::SeqCst);
+    }
+
+    /// Set the number of samples already decoded.
+    pub fn set_decoded(&self, decoded: usize) -> () {
+        self.decoded.store(decoded, Ordering::SeqCst);
+    }
+
+    /// Check if the loading should be aborted.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn cancel(&self) -> () {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    fn get(&self) -> f32 {
+        let total = self.total.load(Ordering::SeqCst);
+        if total == 0 {
+            0.
+        } else {
+            self.decoded.load(Ordering::SeqCst) as f32 / total as f32
+        }
+    }
+}
+
+/// State shared between a worker and a LoadHandle.
+struct Shared<T> {
+    progress: Progress,
+    result: Mutex<Option<Result<T, String>>>,
+    done: Condvar
+}
+
+/**
+ * Handle to a SoundData or a Music loading in the background.
+ *
+ * The handle can be polled each frame, or waited on.
+ */
+pub struct LoadHandle<T> {
+    shared: Arc<Shared<T>>,
+    /// The result already taken with poll
+    taken: bool
+}
+
+impl<T> LoadHandle<T> {
+    /**
+     * Get the progress of the loading.
+     *
+     * # Return
+     * The ratio of the file already decoded, between 0. and 1.
+     */
+    pub fn progress(&self) -> f32 {
+        self.shared.progress.get()
+    }
+
+    /**
+     * Check if the loading is finished, successfully or not.
+     *
+     * # Return
+     * True if the result is available, false otherwise.
+     */
+    pub fn is_done(&self) -> bool {
+        self.taken || self.shared.result.lock().unwrap().is_some()
+    }
+
+    /**
+     * Take the result of the loading if it's finished.
+     *
+     * # Return
+     * `None` while the loading is running or once the result has been taken,
+     * `Some(Ok(value))` on success, `Some(Err(String))` if the file cannot
+     * be loaded or if the loading has been cancelled.
+     */
+    pub fn poll(&mut self) -> Option<Result<T, String>> {
+        let result = self.shared.result.lock().unwrap().take();
+        if result.is_some() {
+            self.taken = true;
+        }
+        result
+    }
+
+    /**
+     * Block until the loading is finished.
+     *
+     * # Return
+     * `Ok(value)` on success, `Err(String)` if the file cannot be loaded or
+     * if the loading has been cancelled.
+     */
+    pub fn wait(self) -> Result<T, String> {
+        if self.taken {
+            return Err("The result of the loading has already been taken.".into());
+        }
+        let mut result = self.shared.result.lock().unwrap();
+        while result.is_none() {
+            result = self.shared.done.wait(result).unwrap();
+        }
+        result.take().unwrap()
+    }
+
+    /**
+     * Cancel the loading.
+     *
+     * The decoding stops as soon as possible and the result becomes an
+     * error. Does nothing if the loading is already finished.
+     */
+    pub fn cancel(&self) -> () {
+        self.shared.progress.cancel();
+    }
+}
+
+/**
+ * Pool of worker threads loading SoundDatas and Musics.
+ *
+ * Decoding a long file can take hundreds of milliseconds, the Loader decodes
+ * it in the background and uploads the samples to OpenAL once the decoding
+ * is finished.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{Loader, LoadOptions, Sound, AudioController};
+ * use std::rc::Rc;
+ * use std::cell::RefCell;
+ *
+ * let loader = Loader::new(2);
+ * let mut handle = loader.load_sound_data("path/to/my/sound.ogg",
+ *                                         &LoadOptions::default());
+ *
+ * loop {
+ *     // Draw the loading screen here
+ *     println!("Loading: {}%", handle.progress() * 100.);
+ *     if let Some(result) = handle.poll() {
+ *         let data = Rc::new(RefCell::new(result.unwrap()));
+ *         let mut snd = Sound::new_with_data(data).unwrap();
+ *         snd.play();
+ *         break;
+ *     }
+ * }
+ * ```
+ */
+pub struct Loader {
+    /// Channel to send the jobs to the workers
+    job_sender: Option<Sender<Job>>,
+    /// The worker threads
+    workers: Vec<thread::JoinHandle<()>>
+}
+
+impl Loader {
+    /**
+     * Create a new Loader.
+     *
+     * # Argument
+     * * `threads` - The number of worker threads, at least one is created
+     */
+    pub fn new(threads: usize) -> Loader {
+        let (job_sender, job_receiver): (Sender<Job>, Receiver<Job>) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..threads.max(1)).map(|_| {
+            let job_receiver = job_receiver.clone();
+            thread::spawn(move || {
+                loop {
+                    let job = match job_receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        // The Loader has been dropped
+                        Err(_)  => break
+                    };
+                    job();
+                }
+            })
+        }).collect();
+
+        Loader {
+            job_sender: Some(job_sender),
+            workers: workers
+        }
+    }
+
+    /**
+     * Load a SoundData in the background.
+     *
+     * # Arguments
+     * * `path` - The path of the file to load
+     * * `options` - The options used to decode the file
+     *
+     * # Return
+     * A LoadHandle to retrieve the SoundData.
+     */
+    pub fn load_sound_data(&self, path: &str, options: &LoadOptions) -> LoadHandle<SoundData> {
+        let path = path.to_string();
+        let options = options.clone();
+        self.spawn(move |progress| {
+            sound_data::load_with_progress(&path, &options, progress)
+        })
+    }
+
+    /**
+     * Create a Music in the background.
+     *
+     * # Arguments
+     * * `path` - The path of the file to load
+     * * `options` - The options used to decode the file
+     *
+     * # Return
+     * A LoadHandle to retrieve the Music.
+     */
+    pub fn load_music(&self, path: &str, options: &LoadOptions) -> LoadHandle<Music> {
+        let path = path.to_string();
+        let options = options.clone();
+        self.spawn(move |progress| {
+            progress.set_total(1);
+            let music = Music::new_with_options(&path, &options);
+            progress.set_decoded(1);
+            music
+        })
+    }
+
+    fn spawn<T, F>(&self, load: F) -> LoadHandle<T>
+        where T: Send + 'static,
+              F: FnOnce(&Progress) -> Result<T, String> + Send + 'static {
+        let shared = Arc::new(Shared {
+            progress: Progress::new(),
+            result: Mutex::new(None),
+            done: Condvar::new()
+        });
+
+        let job_shared = shared.clone();
+        let job: Job = Box::new(move || {
+            let result = if job_shared.progress.is_cancelled() {
+                Err("Loading cancelled.".into())
+            } else {
+                // A panic must still give a result to the handle, and keep
+                // the worker alive
+                match panic::catch_unwind(AssertUnwindSafe(|| load(&job_shared.progress))) {
+                    Ok(result) => result,
+                    Err(_)     => Err("The loading panicked.".into())
+                }
+            };
+            *job_shared.result.lock().unwrap() = Some(result);
+            job_shared.done.notify_all();
+        });
+        let sent = match self.job_sender {
+            Some(ref sender) => sender.send(job).is_ok(),
+            None             => false
+        };
+        if !sent {
+            *shared.result.lock().unwrap() = Some(Err("The workers of the Loader have stopped.".into()));
+        }
+
+        LoadHandle {
+            shared: shared,
+            taken: false
+        }
+    }
+}
+
+impl Drop for Loader {
+    /// Finish the pending loadings and stop the worker threads.
+    fn drop(&mut self) -> () {
+        self.job_sender.take();
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                println!("A worker of the Loader panicked.");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use loader::Loader;
+    use load_options::LoadOptions;
+
+    #[test]
+    #[ignore]
+    fn loader_load_sound_data_OK() -> () {
+        let loader = Loader::new(1);
+        let handle = loader.load_sound_data("res/shot.wav", &LoadOptions::default());
+
+        assert!(handle.wait().is_ok());
+    }
+
+    #[test]
+    #[ignore]
+    fn loader_load_sound_data_FAIL() -> () {
+        let loader = Loader::new(1);
+        let handle = loader.load_sound_data("toto.wav", &LoadOptions::default());
+
+        assert!(handle.wait().is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn loader_load_music_OK() -> () {
+        let loader = Loader::new(1);
+        let mut handle = loader.load_music("res/music.ogg", &LoadOptions::default());
+
+        while !handle.is_done() {}
+        assert!(handle.poll().unwrap().is_ok());
+        assert_eq!(handle.progress(), 1.);
+    }
+
+    #[test]
+    #[ignore]
+    fn loader_cancel_OK() -> () {
+        let loader = Loader::new(1);
+        let handle = loader.load_sound_data("res/music.ogg", &LoadOptions::default());
+        handle.cancel();
+
+        assert!(handle.wait().is_err());
+    }
+
+    #[test]
+    fn loader_panic_FAIL() -> () {
+        let loader = Loader::new(1);
+        let handle = loader.spawn(|_| -> Result<(), String> { panic!("load") });
+        assert!(handle.wait().is_err());
+
+        // The worker keeps loading
+        let handle = loader.spawn(|_| Ok(1));
+        assert_eq!(handle.wait(), Ok(1));
+    }
+}
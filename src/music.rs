@@ -21,13 +21,14 @@
 
 //! Play Music easily.
 
-use std::thread::sleep;
-use std::mem;
-use std::thread;
-use std::time::Duration;
-use libc::c_void;
 use std::vec::Vec;
 use std::sync::mpsc::{channel, Sender, Receiver};
+use std::io;
+use std::cmp;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 
 use internal::OpenAlData;
 use openal::{ffi, al};
@@ -38,6 +39,31 @@ use states::State;
 use states::State::{Initial, Playing, Paused, Stopped};
 use audio_controller::AudioController;
 use audio_tags::{Tags, AudioTags, get_sound_tags};
+use sample::{SampleType, Samples};
+use load_options::{LoadOptions, validate_header};
+use channel_mix::ChannelMix;
+use normalization::{Normalization, compute_base_gain, gain_headroom};
+use resampler::{Resampler, set_source_resampler, get_source_resampler};
+use sprite::Region;
+use stream_config::{StreamConfig, Underrun, UnderrunStats};
+use streamer;
+use streamer::{QueueStream, Producer, StreamId};
+use beat_clock::{Tempo, BeatPosition, Boundary};
+use time_stretch::{TimeStretcher, validate_speed};
+
+/// A chunk queued on the source.
+struct Chunk {
+    /// The frames sent to OpenAL
+    frames: usize,
+    /// The speed the file is played at
+    speed: f32,
+    /// The frames of the file starting in the chunk, as (frame of the chunk,
+    /// frame of the file)
+    starts: Vec<(usize, u64)>
+}
+
+/// The chunks queued on the source, in order.
+type Timeline = VecDeque<Chunk>;
 
 /**
  * Play Music easily.
@@ -63,24 +89,55 @@ pub struct Music {
     /// The internal OpenAL source identifier
     al_source: u32,
     /// The internal OpenAL buffers
-    al_buffers: [u32; 2],
+    al_buffers: Vec<u32>,
     /// The file open with libmscfile
     file: Option<Box<SndFile>>,
     /// Information of the file
     file_infos: SndInfo,
-    /// Quantity of sample to read each time
-    sample_to_read: i32,
-    /// Format of the sample
-    sample_format: i32,
+    /// The buffering of the stream
+    stream_config: StreamConfig,
+    /// The statistics of the underruns of the stream
+    underrun_stats: Arc<Mutex<UnderrunStats>>,
+    /// Channel the thread reports the underruns to
+    underrun_sender: Sender<Underrun>,
+    /// The underruns not yet polled
+    underrun_receiver: Receiver<Underrun>,
+    /// Type of the samples sent to OpenAL
+    sample_type: SampleType,
+    /// The conversion of the channels of the file
+    channel_mix: ChannelMix,
+    /// The sample rate sent to OpenAL
+    sample_rate: i32,
+    /// The gain bringing the Music to the normalized level
+    base_gain: f32,
     /// Audio tags
     sound_tags: Tags,
 
     is_looping: bool,
+    /// False if the Music is read from a pipe, which can't loop
+    is_seekable: bool,
     /// Channel to tell the thread, if is_looping changed
     looping_sender: Option<Sender<bool>>,
-
-    /// Thread which streams the music file
-    thread_handle: Option<thread::JoinHandle<()>>,
+    /// The start and end frames of the loop
+    loop_points: Option<(u64, u64)>,
+    /// Channel to tell the thread, if the loop points changed
+    loop_points_sender: Option<Sender<Option<(u64, u64)>>>,
+
+    /// The speed the file is played at, without changing the pitch
+    speed: f32,
+    /// Channel to tell the thread, if the speed changed
+    speed_sender: Option<Sender<f32>>,
+    /// The tempo, to follow the bars and beats
+    tempo: Option<Tempo>,
+    /// The chunks queued by the thread, to know the frame heard
+    timeline: Arc<Mutex<Timeline>>,
+    /// Tells the thread the source was stopped by the stop timer
+    is_stopping: Arc<AtomicBool>,
+    /// The timer stopping the Music at a bar or a beat
+    stop_timer: Option<StreamId>,
+
+    /// The stream registered with the streaming thread
+    stream_id: Option<StreamId>,
 }
 
 impl Music {
@@ -95,6 +152,21 @@ impl Music {
      * if there has been an error.
      */
     pub fn new(path: &str) -> Result<Music, String> {
+        Music::new_with_options(path, &LoadOptions::default())
+    }
+
+    /**
+     * Create a new Music with custom load options
+     *
+     * # Arguments
+     * * `path` - The path of the file to load the music
+     * * `options` - The options used to decode the file
+     *
+     * # Return
+     * A `Result` containing Ok(Music) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new_with_options(path: &str, options: &LoadOptions) -> Result<Music, String> {
         // Check that OpenAL is launched
         check_openal_context!(Err("Invalid OpenAL context.".into()));
 
@@ -105,134 +177,1077 @@ impl Music {
                 return Err(format!("Error while loading music file: {}", err));
             }
         };
+
+        Music::from_file(file, options)
+    }
+
+    /**
+     * Create a new Music streamed from a reader
+     *
+     * The reader is decoded progressively while the Music plays, so it must
+     * stay seekable for the whole life of the Music, e.g. an entry of an
+     * archive or a `Cursor` over bytes embedded with `include_bytes!`.
+     *
+     * # Argument
+     * * `reader` - The reader containing the encoded music
+     *
+     * # Return
+     * A `Result` containing Ok(Music) on success, Err(String)
+     * if there has been an error.
+     *
+     * # Example
+     * ```no_run
+     * use std::io::Cursor;
+     * use ears::{Music, AudioController};
+     *
+     * let bytes = std::fs::read("path/to/music.ogg").unwrap();
+     * let mut msc = Music::from_reader(Cursor::new(bytes)).unwrap();
+     * msc.play();
+     * ```
+     */
+    pub fn from_reader<R: io::Read + io::Seek + Send + 'static>(reader: R)
+                                                               -> Result<Music, String> {
+        Music::from_reader_with_options(reader, &LoadOptions::default())
+    }
+
+    /**
+     * Create a new Music streamed from a reader, with custom load options
+     *
+     * # Arguments
+     * * `reader` - The reader containing the encoded music
+     * * `options` - The options used to decode the file
+     *
+     * # Return
+     * A `Result` containing Ok(Music) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn from_reader_with_options<R>(reader: R, options: &LoadOptions) -> Result<Music, String>
+        where R: io::Read + io::Seek + Send + 'static {
+        // Check that OpenAL is launched
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        let file = match SndFile::from_reader(reader) {
+            Ok(file)    => Box::new(file),
+            Err(err)    => {
+                return Err(format!("Error while loading music file: {}", err));
+            }
+        };
+
+        Music::from_file(file, options)
+    }
+
+    /**
+     * Create a new Music streamed from a reader which can't seek
+     *
+     * Pipes, sockets, the standard input or the output of a child process
+     * are read forward only: the Music can't loop, and once stopped it
+     * resumes where it stopped instead of restarting. The normalization and
+     * the loop points of the load options are ignored.
+     *
+     * # Argument
+     * * `reader` - The reader containing the encoded music
+     *
+     * # Return
+     * A `Result` containing Ok(Music) on success, Err(String)
+     * if there has been an error.
+     *
+     * # Example
+     * ```no_run
+     * use std::process::{Command, Stdio};
+     * use ears::{Music, AudioController};
+     *
+     * let decoder = Command::new("my-decoder")
+     *                       .arg("--wav")
+     *                       .stdout(Stdio::piped())
+     *                       .spawn()
+     *                       .unwrap();
+     * let mut msc = Music::from_pipe(decoder.stdout.unwrap()).unwrap();
+     * msc.play();
+     * ```
+     */
+    pub fn from_pipe<R: io::Read + Send + 'static>(reader: R) -> Result<Music, String> {
+        Music::from_pipe_with_options(reader, &LoadOptions::default())
+    }
+
+    /**
+     * Create a new Music streamed from a reader which can't seek, with
+     * custom load options
+     *
+     * # Arguments
+     * * `reader` - The reader containing the encoded music
+     * * `options` - The options used to decode the file
+     *
+     * # Return
+     * A `Result` containing Ok(Music) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn from_pipe_with_options<R>(reader: R, options: &LoadOptions) -> Result<Music, String>
+        where R: io::Read + Send + 'static {
+        // Check that OpenAL is launched
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        let file = match SndFile::from_pipe(reader) {
+            Ok(file)    => Box::new(file),
+            Err(err)    => {
+                return Err(format!("Error while loading music file: {}", err));
+            }
+        };
+
+        // Measuring the level would consume the whole stream
+        let mut options = options.clone();
+        options.normalization = Normalization::Off;
+        options.loop_points = None;
+        match Music::from_file(file, &options) {
+            Ok(mut music) => {
+                music.is_seekable = false;
+                music.loop_points = None;
+                Ok(music)
+            },
+            Err(err) => Err(err)
+        }
+    }
+
+    /// Create the OpenAL source and buffers used to stream the file.
+    fn from_file(mut file: Box<SndFile>, options: &LoadOptions) -> Result<Music, String> {
         let infos = file.get_sndinfo();
+        if let Err(err) = validate_header(&infos) {
+            return Err(err);
+        }
+        if let Err(err) = options.stream_config.validate() {
+            return Err(err);
+        }
+
+        let tempo = match file.get_tempo() {
+            Ok(tempo) => tempo,
+            Err(err)  => return Err(err)
+        };
+        let loop_points = match options.loop_points {
+            Some(region) => clamp_loop_points(region, infos.samplerate, infos.frames as u64),
+            None => match file.get_loop_points() {
+                Ok(loop_points) => loop_points,
+                Err(err)        => return Err(err)
+            }
+        };
+        let base_gain = match compute_base_gain(&mut *file, options, None) {
+            Ok(base_gain) => base_gain,
+            Err(err)      => return Err(err)
+        };
+
+        let out_channels = match options.channel_mix.get_channels(infos.channels) {
+            Ok(channels) => channels,
+            Err(err) => return Err(err)
+        };
+
+        // Check the format is supported
+        let sample_type = options.sample_type.resolve();
+        if sample_type.get_format(out_channels).is_none() {
+            return Err("Unrecognized music format.".into());
+        }
 
         // create the source and the buffers
         let mut source_id = 0;
-        let mut buffer_ids = [0; 2];
+        let mut buffer_ids = vec![0; options.stream_config.buffer_count];
         // create the source
         al::alGenSources(1, &mut source_id);
         // create the buffers
-        al::alGenBuffers(2, &mut buffer_ids[0]);
-
-        // Retrieve format information
-        let format =  match al::get_channels_format(infos.channels) {
-            Some(fmt) => fmt,
-            None => {
-                return Err("Unrecognized music format.".into());
-            }
-        };
+        al::alGenBuffers(buffer_ids.len() as i32, &mut buffer_ids[0]);
 
         // Check if there is OpenAL internal error
         if let Some(err) = al::openal_has_error() {
-             return Err(format!("Internal OpenAL error: {}", err));
+            unsafe {
+                ffi::alDeleteBuffers(buffer_ids.len() as i32, &mut buffer_ids[0]);
+                ffi::alDeleteSources(1, &mut source_id);
+            }
+            return Err(format!("Internal OpenAL error: {}", err));
         };
 
         let sound_tags = get_sound_tags(&*file);
+        let sample_rate = options.get_sample_rate(infos.samplerate);
+        al::alSourcef(source_id, ffi::AL_GAIN, base_gain);
+        al::alSourcef(source_id, ffi::AL_MAX_GAIN, gain_headroom(base_gain));
+
+        let (underrun_sender, underrun_receiver) = channel();
 
         Ok(Music {
             al_source: source_id,
             al_buffers: buffer_ids,
             file: Some(file),
             file_infos: infos,
-            sample_to_read: 50000,
-            sample_format: format,
+            stream_config: options.stream_config,
+            underrun_stats: Arc::new(Mutex::new(UnderrunStats::default())),
+            underrun_sender: underrun_sender,
+            underrun_receiver: underrun_receiver,
+            sample_type: sample_type,
+            channel_mix: options.channel_mix,
+            sample_rate: sample_rate,
+            base_gain: base_gain,
             sound_tags: sound_tags,
             is_looping: false,
+            is_seekable: true,
             looping_sender: None,
-            thread_handle: None,
+            loop_points: loop_points,
+            loop_points_sender: None,
+            speed: 1.,
+            speed_sender: None,
+            tempo: tempo,
+            timeline: Arc::new(Mutex::new(VecDeque::new())),
+            is_stopping: Arc::new(AtomicBool::new(false)),
+            stop_timer: None,
+            stream_id: None,
         })
     }
 
-    fn process_music(&mut self) -> () {
-        let (chan, port) = channel();
-        let sample_t_r = self.sample_to_read;
-        let sample_rate = self.file_infos.samplerate;
-        let sample_format = self.sample_format;
+    /**
+     * Set the part of the Music repeated when it's looping.
+     *
+     * The Music plays from the start, then repeats the part between the loop
+     * points, so it can have an intro played only once.
+     * The loop points are read from the file when it's loaded, unless they
+     * are given in the LoadOptions.
+     *
+     * # Argument
+     * * `loop_points` - The bounds of the loop, or None to loop the whole
+     * Music.
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the loop points are invalid or the
+     * Music is read from a pipe.
+     *
+     * # Example
+     * ```no_run
+     * use ears::{Music, AudioController, Region};
+     *
+     * let mut msc = Music::new("path/to/music.ogg").unwrap();
+     * msc.set_loop_points(Some(Region::Seconds(12.5, 60.))).unwrap();
+     * msc.set_looping(true);
+     * msc.play();
+     * ```
+     */
+    pub fn set_loop_points(&mut self, loop_points: Option<Region>) -> Result<(), String> {
+        if !self.is_seekable {
+            return Err("A Music read from a pipe can't loop.".into());
+        }
+        let bounds = match loop_points {
+            Some(region) => match clamp_loop_points(region,
+                                                    self.file_infos.samplerate,
+                                                    self.file_infos.frames as u64) {
+                Some(bounds) => Some(bounds),
+                None => return Err("The loop end must be after the loop start.".into())
+            },
+            None => None
+        };
+
+        if let Some(ref sender) = self.loop_points_sender {
+            sender.send(bounds);
+        }
+        self.loop_points = bounds;
+        Ok(())
+    }
+
+    /**
+     * Get the gain bringing the Music to the normalized level.
+     *
+     * The gain comes from the `normalization` and `replay_gain` load
+     * options, and is applied under the volume. The max volume is raised to
+     * let a gain above 1 be heard.
+     *
+     * # Return
+     * The base gain, 1. if the level of the file is kept.
+     */
+    pub fn get_base_gain(&self) -> f32 {
+        self.base_gain
+    }
+
+    /**
+     * Get the loop points of the Music.
+     *
+     * # Return
+     * The start and end frames of the loop, or None if the whole Music is
+     * looped.
+     */
+    pub fn get_loop_points(&self) -> Option<(u64, u64)> {
+        self.loop_points
+    }
+
+    /**
+     * Select the resampler OpenAL uses to play the Music.
+     *
+     * Requires the AL_SOFT_source_resampler extension.
+     *
+     * # Argument
+     * `resampler` - The index of the resampler in the list returned by
+     * `ears::get_resamplers`.
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the resampler doesn't exist.
+     *
+     * # Example
+     * ```no_run
+     * let mut msc = ears::Music::new("path/to/music.ogg").unwrap();
+     * let resamplers = ears::get_resamplers();
+     * if let Some(index) = resamplers.iter().position(|name| name.contains("Sinc")) {
+     *     msc.set_resampler(index).unwrap();
+     * }
+     * ```
+     */
+    pub fn set_resampler(&mut self, resampler: usize) -> Result<(), String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        set_source_resampler(self.al_source, resampler)
+    }
+
+    /**
+     * Get the resampler OpenAL uses to play the Music.
+     *
+     * # Return
+     * The index of the resampler, or None if the AL_SOFT_source_resampler
+     * extension is missing.
+     */
+    pub fn get_resampler(&self) -> Option<usize> {
+        check_openal_context!(None);
+
+        get_source_resampler(self.al_source)
+    }
+
+    /**
+     * Set the speed of the Music, without changing its pitch.
+     *
+     * Unlike the pitch, which plays the samples faster or slower, the speed
+     * is changed by time-stretching the samples as they are decoded. The new
+     * speed is heard once the chunks already queued are played.
+     *
+     * Default speed is 1.0.
+     *
+     * # Argument
+     * * `speed` - The new speed of the Music, from 0.5 (twice slower) to 2.
+     * (twice faster)
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the speed is out of range.
+     *
+     * # Example
+     * ```no_run
+     * use ears::{Music, AudioController};
+     *
+     * let mut msc = Music::new("path/to/lesson.ogg").unwrap();
+     * msc.set_speed(0.75).unwrap();
+     * msc.play();
+     * ```
+     */
+    pub fn set_speed(&mut self, speed: f32) -> Result<(), String> {
+        if let Err(err) = validate_speed(speed) {
+            return Err(err);
+        }
+        if let Some(ref sender) = self.speed_sender {
+            sender.send(speed);
+        }
+        self.speed = speed;
+        Ok(())
+    }
+
+    /**
+     * Get the speed of the Music.
+     *
+     * # Return
+     * The speed of the Music, between 0.5 and 2.
+     */
+    pub fn get_speed(&self) -> f32 {
+        self.speed
+    }
+
+    /**
+     * Change the buffering of the Music.
+     *
+     * The Music must be stopped, the new configuration is used the next time
+     * it's played.
+     *
+     * # Argument
+     * * `config` - The new streaming configuration
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the configuration is invalid or the
+     * Music is playing.
+     */
+    pub fn set_stream_config(&mut self, config: StreamConfig) -> Result<(), String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        if let Err(err) = config.validate() {
+            return Err(err);
+        }
+        match self.get_state() {
+            Playing | Paused => return Err("Can't change the buffering of a playing Music.".into()),
+            _                => {}
+        }
+        if let Some(id) = self.stream_id.take() {
+            streamer::unregister(id);
+        }
+
+        al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+        unsafe {
+            ffi::alDeleteBuffers(self.al_buffers.len() as i32, &mut self.al_buffers[0]);
+        }
+        self.al_buffers = vec![0; config.buffer_count];
+        al::alGenBuffers(self.al_buffers.len() as i32, &mut self.al_buffers[0]);
+        self.stream_config = config;
+
+        match al::openal_has_error() {
+            Some(err) => Err(format!("Internal OpenAL error: {}", err)),
+            None      => Ok(())
+        }
+    }
+
+    /// Get the buffering of the Music.
+    pub fn get_stream_config(&self) -> StreamConfig {
+        self.stream_config
+    }
+
+    /**
+     * Get the number of underruns of the Music.
+     *
+     * An underrun happens when every queued chunk was played before the
+     * streaming thread refilled them, which is heard as a gap. Frequent
+     * underruns call for more or longer chunks in the StreamConfig.
+     *
+     * # Return
+     * The number of underruns since the Music was created.
+     */
+    pub fn get_underrun_count(&self) -> usize {
+        self.get_underrun_stats().count
+    }
+
+    /**
+     * Get the statistics of the underruns of the Music.
+     *
+     * # Return
+     * The statistics since the Music was created.
+     */
+    pub fn get_underrun_stats(&self) -> UnderrunStats {
+        *self.underrun_stats.lock().unwrap()
+    }
+
+    /**
+     * Get the underruns which happened since the last call.
+     *
+     * The streaming thread refills the buffers and restarts the Music by
+     * itself after an underrun, the events tell where the gaps were heard.
+     *
+     * # Return
+     * The underruns in the order they happened.
+     *
+     * # Example
+     * ```no_run
+     * use ears::{Music, AudioController};
+     *
+     * let mut msc = Music::new("path/to/music.ogg").unwrap();
+     * msc.play();
+     * while msc.is_playing() {
+     *     for underrun in msc.poll_underruns() {
+     *         println!("Underrun at frame {}", underrun.position);
+     *     }
+     * }
+     * ```
+     */
+    pub fn poll_underruns(&self) -> Vec<Underrun> {
+        self.underrun_receiver.try_iter().collect()
+    }
+
+    /**
+     * Set the tempo of the Music.
+     *
+     * The tempo is read from the file when it's loaded, see Tempo.
+     *
+     * # Argument
+     * * `tempo` - The new tempo, or None if the Music has no beat
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the tempo is invalid.
+     */
+    pub fn set_tempo(&mut self, tempo: Option<Tempo>) -> Result<(), String> {
+        if let Some(ref tempo) = tempo {
+            if let Err(err) = tempo.validate() {
+                return Err(err);
+            }
+        }
+        self.tempo = tempo;
+        Ok(())
+    }
+
+    /// Get the tempo of the Music.
+    pub fn get_tempo(&self) -> Option<Tempo> {
+        self.tempo
+    }
+
+    /**
+     * Get the position heard in the Music.
+     *
+     * The position follows the loops, and is the start of the Music while
+     * playing the silence of `play_after`.
+     *
+     * # Return
+     * The position in seconds from the start of the file, or None if the
+     * Music is neither playing nor paused.
+     */
+    pub fn get_playback_position(&self) -> Option<f32> {
+        check_openal_context!(None);
+
+        match self.get_state() {
+            Playing | Paused => {},
+            _                => return None
+        }
+        let frame = streamer::synchronized(|| {
+            let mut offset = 0;
+            al::alGetSourcei(self.al_source, ffi::AL_SAMPLE_OFFSET, &mut offset);
+            locate(&self.timeline.lock().unwrap(),
+                   offset as usize,
+                   self.file_infos.samplerate,
+                   self.sample_rate)
+        });
+        frame.map(|frame| frame as f32 / self.file_infos.samplerate as f32)
+    }
+
+    /**
+     * Get the bar and beat heard in the Music.
+     *
+     * # Return
+     * The position in the bars and beats, or None if the Music has no tempo
+     * or is neither playing nor paused.
+     */
+    pub fn get_beat_position(&self) -> Option<BeatPosition> {
+        match (self.tempo, self.get_playback_position()) {
+            (Some(tempo), Some(position)) => Some(tempo.get_beat_position(position)),
+            _                             => None
+        }
+    }
+
+    /**
+     * Get the time left before the next beat or bar.
+     *
+     * The time takes the pitch and speed into account, the loop points should be on
+     * the boundaries for the time to stay right across the loop.
+     *
+     * # Argument
+     * * `boundary` - The boundary to wait for
+     *
+     * # Return
+     * The time left, or None if the Music has no tempo or is neither playing
+     * nor paused.
+     *
+     * # Example
+     * ```no_run
+     * use ears::{Music, Sound, AudioController, Boundary};
+     *
+     * let mut msc = Music::new("path/to/music.ogg").unwrap();
+     * let mut stinger = Sound::new("path/to/stinger.wav").unwrap();
+     * msc.play();
+     *
+     * // Play the stinger on the next beat
+     * if let Some(delay) = msc.get_time_to_next(Boundary::Beat) {
+     *     stinger.play_after(delay);
+     * }
+     * ```
+     */
+    pub fn get_time_to_next(&self, boundary: Boundary) -> Option<Duration> {
+        let tempo = match self.tempo {
+            Some(tempo) => tempo,
+            None        => return None
+        };
+        let position = match self.get_playback_position() {
+            Some(position) => position,
+            None           => return None
+        };
+        let pitch = self.get_pitch();
+        let seconds = (tempo.get_next(boundary, position) - position)
+                      / if pitch > 0. { pitch } else { 1. } / self.speed;
+        Some(Duration::from_nanos((seconds.max(0.) as f64 * 1e9) as u64))
+    }
+
+    /**
+     * Play the Music from its start after a delay.
+     *
+     * The Music is started right away with silence, so it starts playing at
+     * the sample after the delay.
+     *
+     * # Argument
+     * * `delay` - The time before the start of the Music
+     */
+    pub fn play_after(&mut self, delay: Duration) -> () {
+        self.play_at(Instant::now() + delay);
+    }
+
+    /**
+     * Stop the Music at the next beat or bar.
+     *
+     * Stopping at the next bar ends the Music with the current bar. Playing
+     * or stopping the Music before cancels it.
+     *
+     * # Argument
+     * * `boundary` - The boundary to stop at
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the Music has no tempo or is neither
+     * playing nor paused.
+     */
+    pub fn stop_at_next(&mut self, boundary: Boundary) -> Result<(), String> {
+        match self.get_time_to_next(boundary) {
+            Some(delay) => {
+                self.stop_at(Instant::now() + delay);
+                Ok(())
+            },
+            None => Err("The Music has no tempo or isn't playing.".into())
+        }
+    }
+
+    /**
+     * Switch to another Music at the next beat or bar.
+     *
+     * The Music stops at the boundary, and the next one starts from its
+     * beginning at the same sample.
+     *
+     * # Arguments
+     * * `boundary` - The boundary to switch at
+     * * `next` - The Music to play next
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the Music has no tempo or is neither
+     * playing nor paused.
+     *
+     * # Example
+     * ```no_run
+     * use ears::{Music, AudioController, Boundary};
+     *
+     * let mut explore = Music::new("path/to/explore.ogg").unwrap();
+     * let mut combat = Music::new("path/to/combat.ogg").unwrap();
+     * explore.set_looping(true);
+     * explore.play();
+     *
+     * // The enemies are in sight
+     * explore.switch_at_next(Boundary::Bar, &mut combat).unwrap();
+     * ```
+     */
+    pub fn switch_at_next(&mut self,
+                          boundary: Boundary,
+                          next: &mut Music) -> Result<(), String> {
+        match self.get_time_to_next(boundary) {
+            Some(delay) => {
+                let time = Instant::now() + delay;
+                next.play_at(time);
+                self.stop_at(time);
+                Ok(())
+            },
+            None => Err("The Music has no tempo or isn't playing.".into())
+        }
+    }
+
+    /// Play the Music from its start at a given time.
+    fn play_at(&mut self, time: Instant) -> () {
+        check_openal_context!(());
+
+        self.stop();
+        if self.is_seekable {
+            self.file.as_mut().unwrap().seek(0, SeekSet);
+        }
+        self.process_music(Some(time));
+    }
+
+    /// Stop the Music at a given time, from the streaming thread.
+    fn stop_at(&mut self, time: Instant) -> () {
+        if let Some(id) = self.stop_timer.take() {
+            streamer::unregister(id);
+        }
+        let is_stopping = self.is_stopping.clone();
         let al_source = self.al_source;
-        let al_buffers = self.al_buffers;
-
-        // create buff
-        let mut samples = vec![0i16; sample_t_r as usize];// as u32, 0i16);
-
-        // full buff1
-        let mut len = mem::size_of::<i16>() *
-            self.file.as_mut().unwrap().read_i16(&mut samples[..], sample_t_r as i64) as usize;
-        al::alBufferData(al_buffers[0],
-                         sample_format,
-                         samples.as_ptr() as *mut c_void,
-                         len as i32,
-                         sample_rate);
-
-        // full buff2
-        samples.clear();
-        len = mem::size_of::<i16>() *
-            self.file.as_mut().unwrap().read_i16(&mut samples[..], sample_t_r as i64) as usize;
-        al::alBufferData(al_buffers[1],
-                         sample_format,
-                         samples.as_ptr() as *mut c_void,
-                         len as i32,
-                         sample_rate);
-
-        // Queue the buffers
-        al::alSourceQueueBuffers(al_source, 2, &al_buffers[0]);
-
-        // Launche the Music
-        al::alSourcePlay(al_source);
+        self.stop_timer = Some(streamer::schedule(time, move || {
+            // The stream ends once it sees the source stopped
+            is_stopping.store(true, Ordering::SeqCst);
+            al::alSourceStop(al_source);
+        }));
+    }
+
+    /// Start streaming the file, after silence if a start time is given.
+    fn process_music(&mut self, start: Option<Instant>) -> () {
+        let channels = self.file_infos.channels;
+        let sample_t_r = self.stream_config.get_chunk_samples(self.file_infos.samplerate,
+                                                              channels);
+
+        let mut converter = Converter {
+            channels: channels,
+            channel_mix: self.channel_mix,
+            sample_type: self.sample_type,
+            sample_rate: self.sample_rate,
+            resampler: if self.sample_rate != self.file_infos.samplerate {
+                let out_channels = self.channel_mix.get_channels(channels).unwrap_or(channels);
+                Some(Resampler::new(self.file_infos.samplerate, self.sample_rate, out_channels))
+            } else {
+                None
+            },
+            stretcher: None
+        };
+        converter.set_speed(self.speed, self.file_infos.samplerate);
+
+        // Decode floats when the samples are converted
+        let decode_type = if converter.is_converting() {
+            SampleType::Float32
+        } else {
+            self.sample_type
+        };
 
         let (looping_sender, looping_receiver): (Sender<bool>, Receiver<bool>) = channel();
         self.looping_sender = Some(looping_sender);
-        let is_looping_clone = self.is_looping.clone();
-
-        self.thread_handle = Some(thread::spawn(move|| {
-            match OpenAlData::check_al_context() {
-                Ok(_)       => {},
-                Err(err)    => { println!("{}", err);}
-            };
-            let mut file : SndFile = port.recv().ok().unwrap();
-            let mut samples = vec![0i16; sample_t_r as usize];
-            let mut status = ffi::AL_PLAYING;
-            let mut i = 0;
-            let mut buf = 0;
-            let mut is_looping = is_looping_clone;
-
-            while status != ffi::AL_STOPPED {
-                // wait a bit
-                sleep(Duration::from_millis(50));
-                if status == ffi::AL_PLAYING {
-                    if let Ok(new_is_looping) = looping_receiver.try_recv() {
-                        is_looping = new_is_looping;
-                    }
-                    al::alGetSourcei(al_source,
-                                     ffi::AL_BUFFERS_PROCESSED,
-                                     &mut i);
-                    if i != 0 {
-                        samples.clear();
-                        al::alSourceUnqueueBuffers(al_source, 1, &mut buf);
-                        let mut read = file.read_i16(&mut samples[..], sample_t_r as i64) *
-                                       mem::size_of::<i16>() as i64;
-                        if is_looping && read == 0 {
-                            file.seek(0, SeekSet);
-                            read = file.read_i16(&mut samples[..], sample_t_r as i64) *
-                                   mem::size_of::<i16>() as i64;
-                        }
-                        al::alBufferData(buf,
-                                         sample_format,
-                                         samples.as_ptr() as *mut c_void,
-                                         read as i32,
-                                         sample_rate);
-                        al::alSourceQueueBuffers(al_source, 1, &buf);
-                    }
-                }
-                // Get source status
-                status = al::alGetState(al_source);
+        let (loop_points_sender, loop_points_receiver) = channel();
+        self.loop_points_sender = Some(loop_points_sender);
+        let (speed_sender, speed_receiver) = channel();
+        self.speed_sender = Some(speed_sender);
+        self.timeline = Arc::new(Mutex::new(VecDeque::new()));
+        self.is_stopping = Arc::new(AtomicBool::new(false));
+
+        // The frames of silence until the start time, played at the pitch
+        let frames_until = |time: Instant, rate: i32, pitch: f32| {
+            let now = Instant::now();
+            if time > now {
+                let delay = time - now;
+                let seconds = delay.as_secs() as f64 + delay.subsec_nanos() as f64 / 1e9;
+                (seconds * rate as f64 * pitch as f64).round() as usize
+            } else {
+                0
             }
-            al::alSourcei(al_source, ffi::AL_BUFFER, 0);
-        }));
-        let file = self.file.as_ref().unwrap().clone();
-        chan.send(*file);
+        };
+        let pitch = self.get_pitch();
+        let lead_in = start.map_or(0, |time| frames_until(time, self.sample_rate, pitch));
+
+        let producer = MusicProducer {
+            file: *self.file.as_ref().unwrap().clone(),
+            samples: Samples::new(decode_type, sample_t_r),
+            converter: converter,
+            channels: channels,
+            position: 0,
+            at_end: false,
+            is_looping: self.is_looping,
+            looping_receiver: looping_receiver,
+            loop_points: self.loop_points,
+            loop_points_receiver: loop_points_receiver,
+            speed_receiver: speed_receiver,
+            underrun_stats: self.underrun_stats.clone(),
+            underrun_sender: self.underrun_sender.clone(),
+            lead_in: lead_in,
+            file_rate: self.file_infos.samplerate,
+            timeline: self.timeline.clone(),
+            is_stopping: self.is_stopping.clone()
+        };
+
+        // fill the buffers, and launch the Music
+        if let Some(stream) = QueueStream::start(self.al_source,
+                                                 &self.al_buffers,
+                                                 producer,
+                                                 self.stream_config.poll_interval) {
+            self.stream_id = Some(streamer::register(Box::new(stream)));
+        }
+
+        // Skip the silence played too long while the buffers were filled
+        if let Some(time) = start {
+            let late = lead_in.saturating_sub(frames_until(time, self.sample_rate, pitch));
+            if late > 0 && late < lead_in {
+                al::alSourcei(self.al_source, ffi::AL_SAMPLE_OFFSET, late as i32);
+            }
+        }
+    }
+
+}
+
+/**
+ * Find the frame of the file heard at an offset in the queued chunks.
+ *
+ * # Arguments
+ * * `timeline` - The chunks queued on the source
+ * * `offset` - The offset in the queue, in frames sent to OpenAL
+ * * `file_rate` - The sample rate of the file
+ * * `sample_rate` - The sample rate sent to OpenAL
+ *
+ * # Return
+ * The frame of the file, the first one in the silence before it starts, or
+ * None if the offset is past the queued chunks.
+ */
+fn locate(timeline: &Timeline, offset: usize, file_rate: i32, sample_rate: i32) -> Option<u64> {
+    let mut offset = offset;
+    for chunk in timeline.iter() {
+        if offset < chunk.frames {
+            let rate = chunk.speed as f64 * file_rate as f64 / sample_rate as f64;
+            return Some(match chunk.starts.iter().filter(|&&(start, _)| start <= offset).last() {
+                Some(&(start, frame)) => frame + ((offset - start) as f64 * rate) as u64,
+                None                  => 0
+            });
+        }
+        offset -= chunk.frames;
+    }
+    None
+}
+
+/// Convert loop points to frames, clamping the end to the music length.
+pub fn clamp_loop_points(region: Region, sample_rate: i32, frames: u64) -> Option<(u64, u64)> {
+    let (start, end) = region.to_frames(sample_rate);
+    let end = cmp::min(end, frames);
+    if start < end {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+/**
+ * Read the next samples of the file, jumping back to the loop start when
+ * looping and reaching the loop end or the end of the file.
+ *
+ * The frames of the buffer where the reading starts or jumps back are
+ * pushed to `starts` with the frames of the file read there.
+ *
+ * # Return
+ * The number of samples read, less than the buffer size only at the end of
+ * a Music which isn't looping.
+ */
+pub fn fill_samples(file: &mut SndFile,
+                samples: &mut Samples,
+                position: &mut u64,
+                is_looping: bool,
+                loop_points: Option<(u64, u64)>,
+                channels: i32,
+                starts: &mut Vec<(usize, u64)>) -> usize {
+    let channels = channels as usize;
+    let len = samples.len();
+    let mut filled = 0;
+    let mut empty_reads = 0;
+    starts.push((0, *position));
+
+    while filled < len {
+        let mut end = len;
+        if let (true, Some((_, loop_end))) = (is_looping, loop_points) {
+            let remaining = loop_end.saturating_sub(*position) as usize * channels;
+            end = cmp::min(end, filled + remaining);
+        }
+
+        let count = if end > filled { samples.read_part(file, filled, end) } else { 0 };
+        filled += count;
+        *position += (count / channels) as u64;
+
+        if filled < len {
+            // Reached the loop end or the end of the file
+            if !is_looping {
+                break;
+            }
+            // Nothing to read even after a seek: don't spin forever
+            empty_reads = if count == 0 { empty_reads + 1 } else { 0 };
+            if empty_reads > 1 {
+                break;
+            }
+            let loop_start = loop_points.map_or(0, |(start, _)| start);
+            file.seek(loop_start as i64, SeekSet);
+            *position = loop_start;
+            starts.push((filled / channels, loop_start));
+        }
+    }
+    filled
+}
+
+/// Convert the samples read from the file before uploading them to OpenAL.
+struct Converter {
+    /// The channel count of the file
+    channels: i32,
+    /// The conversion of the channels
+    channel_mix: ChannelMix,
+    /// The type of the samples sent to OpenAL
+    sample_type: SampleType,
+    /// The sample rate sent to OpenAL
+    sample_rate: i32,
+    /// The resampler, if the sample rate of the file is converted
+    resampler: Option<Resampler>,
+    /// The time-stretcher, once the speed was changed
+    stretcher: Option<TimeStretcher>
+}
+
+impl Converter {
+    /// Check if the samples must be decoded as floats to be converted.
+    fn is_converting(&self) -> bool {
+        !self.channel_mix.is_keep() || self.resampler.is_some() || self.stretcher.is_some()
+    }
+
+    /// Set the speed, stretching the samples from now on if it's not 1.
+    fn set_speed(&mut self, speed: f32, file_rate: i32) -> () {
+        if self.stretcher.is_none() && speed == 1. {
+            return;
+        }
+        let out_channels = self.channel_mix.get_channels(self.channels).unwrap_or(self.channels);
+        self.stretcher.get_or_insert_with(|| TimeStretcher::new(out_channels, file_rate))
+                      .set_speed(speed);
+    }
+
+    /// Get the speed the samples are played at.
+    fn get_speed(&self) -> f32 {
+        self.stretcher.as_ref().map_or(1., |stretcher| stretcher.get_speed())
+    }
+
+    /**
+     * Upload the samples read in an OpenAL buffer.
+     *
+     * `at_end` flushes the time-stretcher and the resampler, the file being
+     * entirely read.
+     *
+     * # Return
+     * The number of samples uploaded.
+     */
+    fn upload(&mut self,
+              samples: &Samples,
+              read: usize,
+              at_end: bool,
+              buffer: u32) -> Result<usize, String> {
+        if !self.is_converting() {
+            return samples.buffer_data(buffer, read, self.channels, self.sample_rate)
+                          .map(|_| read);
+        }
+
+        let out_channels = match self.channel_mix.get_channels(self.channels) {
+            Ok(out_channels) => out_channels,
+            Err(err) => return Err(err)
+        };
+        let mut converted = self.channel_mix.apply(&samples.to_f32()[..read], self.channels);
+        if let Some(ref mut stretcher) = self.stretcher {
+            let mut stretched = stretcher.process(&converted);
+            if at_end {
+                stretched.extend(stretcher.flush());
+            }
+            converted = stretched;
+        }
+        if let Some(ref mut resampler) = self.resampler {
+            let mut resampled = resampler.process(&converted);
+            if at_end {
+                resampled.extend(resampler.flush());
+            }
+            converted = resampled;
+        }
+        Samples::from_f32(self.sample_type, &converted).buffer_data(buffer,
+                                                                    converted.len(),
+                                                                    out_channels,
+                                                                    self.sample_rate)
+                                                       .map(|_| converted.len())
     }
+}
 
+/// Decode the chunks of a playing Music, called by the streaming thread.
+struct MusicProducer {
+    /// A handle to the file, sharing its position with the Music
+    file: SndFile,
+    /// The buffer the file is decoded in
+    samples: Samples,
+    converter: Converter,
+    /// The channel count of the file
+    channels: i32,
+    /// The next frame read from the file
+    position: u64,
+    /// True once the whole file is read
+    at_end: bool,
+    is_looping: bool,
+    looping_receiver: Receiver<bool>,
+    loop_points: Option<(u64, u64)>,
+    loop_points_receiver: Receiver<Option<(u64, u64)>>,
+    speed_receiver: Receiver<f32>,
+    underrun_stats: Arc<Mutex<UnderrunStats>>,
+    underrun_sender: Sender<Underrun>,
+    /// The frames of silence to play before the file
+    lead_in: usize,
+    /// The sample rate of the file
+    file_rate: i32,
+    timeline: Arc<Mutex<Timeline>>,
+    is_stopping: Arc<AtomicBool>
+}
+
+impl MusicProducer {
+    /// Upload a chunk of the silence played before the file.
+    fn produce_lead_in(&mut self, buffer: u32) -> bool {
+        let out_channels = self.converter.channel_mix.get_channels(self.channels)
+                                                     .unwrap_or(self.channels);
+        let frames = cmp::min(self.lead_in, self.samples.len() / self.channels as usize);
+        let silence = vec![0.; frames * out_channels as usize];
+        self.lead_in -= frames;
+        match Samples::from_f32(self.converter.sample_type, &silence).buffer_data(
+                buffer, silence.len(), out_channels, self.converter.sample_rate) {
+            Ok(_)  => {
+                self.timeline.lock().unwrap().push_back(Chunk {
+                    frames: frames,
+                    speed: 1.,
+                    starts: Vec::new()
+                });
+                true
+            },
+            Err(_) => false
+        }
+    }
+}
+
+impl Producer for MusicProducer {
+    fn produce(&mut self, buffer: u32) -> bool {
+        if self.is_stopping.load(Ordering::SeqCst) {
+            return false;
+        }
+        if self.lead_in > 0 {
+            return self.produce_lead_in(buffer);
+        }
+        if let Ok(is_looping) = self.looping_receiver.try_recv() {
+            self.is_looping = is_looping;
+        }
+        if let Ok(loop_points) = self.loop_points_receiver.try_recv() {
+            self.loop_points = loop_points;
+        }
+        if let Some(speed) = self.speed_receiver.try_iter().last() {
+            self.converter.set_speed(speed, self.file_rate);
+        }
+        if self.at_end && !self.is_looping {
+            return false;
+        }
+
+        let mut starts = Vec::new();
+        let uploaded = loop {
+            starts.clear();
+            let read = fill_samples(&mut self.file,
+                                    &mut self.samples,
+                                    &mut self.position,
+                                    self.is_looping,
+                                    self.loop_points,
+                                    self.channels,
+                                    &mut starts);
+            self.at_end = read < self.samples.len();
+            match self.converter.upload(&self.samples, read, self.at_end, buffer) {
+                // The time-stretcher holds the first samples of the stream
+                Ok(0) if !self.at_end => continue,
+                Ok(uploaded) if uploaded > 0 => break uploaded,
+                _ => return false
+            }
+        };
+
+        // Place the frames of the file read in the chunk sent
+        let out_channels = self.converter.channel_mix.get_channels(self.channels)
+                                                     .unwrap_or(self.channels);
+        let speed = self.converter.get_speed();
+        let scale = self.converter.sample_rate as f64 / self.file_rate as f64 / speed as f64;
+        let starts = starts.into_iter().map(|(start, frame)| {
+            ((start as f64 * scale) as usize, frame)
+        }).collect();
+        self.timeline.lock().unwrap().push_back(Chunk {
+            frames: uploaded / out_channels as usize,
+            speed: speed,
+            starts: starts
+        });
+        true
+    }
+
+    fn on_unqueued(&mut self) -> () {
+        self.timeline.lock().unwrap().pop_front();
+    }
+
+    fn on_underrun(&mut self, recovered: bool) -> () {
+        let underrun = Underrun {
+            position: self.position,
+            recovered: recovered
+        };
+        self.underrun_stats.lock().unwrap().add(&underrun);
+        self.underrun_sender.send(underrun);
+    }
 }
 
 impl AudioTags for Music {
@@ -257,13 +1272,11 @@ impl AudioController for Music {
         match self.get_state() {
             Paused   => { al::alSourcePlay(self.al_source); return; },
             _       => {
-                if self.is_playing() {
-                    al::alSourceStop(self.al_source);
-                    // wait a bit for openal terminate
-                    sleep(Duration::from_millis(50));
+                self.stop();
+                if self.is_seekable {
+                    self.file.as_mut().unwrap().seek(0, SeekSet);
                 }
-                self.file.as_mut().unwrap().seek(0, SeekSet);
-                self.process_music();
+                self.process_music(None);
             }
         }
     }
@@ -283,6 +1296,12 @@ impl AudioController for Music {
     fn stop(&mut self) -> () {
         check_openal_context!(());
 
+        if let Some(id) = self.stop_timer.take() {
+            streamer::unregister(id);
+        }
+        if let Some(id) = self.stream_id.take() {
+            streamer::unregister(id);
+        }
         al::alSourceStop(self.al_source);
     }
 
@@ -324,7 +1343,7 @@ impl AudioController for Music {
      *
      * A value of 1.0 means unattenuated. Each division by 2 equals an attenuation
      * of about -6dB. Each multiplicaton by 2 equals an amplification of about
-     * +6dB.
+     * +6dB. The normalization gain of the Music is applied on top of it.
      *
      * # Argument
      * * `volume` - The volume of the Music, should be between 0. and 1.
@@ -332,7 +1351,7 @@ impl AudioController for Music {
     fn set_volume(&mut self, volume: f32) -> () {
         check_openal_context!(());
 
-        al::alSourcef(self.al_source, ffi::AL_GAIN, volume);
+        al::alSourcef(self.al_source, ffi::AL_GAIN, volume * self.base_gain);
     }
 
     /**
@@ -346,7 +1365,7 @@ impl AudioController for Music {
 
         let mut volume : f32 = 0.;
         al::alGetSourcef(self.al_source, ffi::AL_GAIN, &mut volume);
-        volume
+        if self.base_gain > 0. { volume / self.base_gain } else { volume }
     }
 
     /**
@@ -383,7 +1402,8 @@ impl AudioController for Music {
      * Set the maximal volume for a Music.
      *
      * The maximum volume allowed for a Music, after distance and cone
-     * attenation is applied (if applicable).
+     * attenation is applied (if applicable). It's raised by a normalization
+     * gain above 1.
      *
      * # Argument
      * * `max_volume` - The new maximal volume of the Music should be
@@ -392,7 +1412,8 @@ impl AudioController for Music {
     fn set_max_volume(&mut self, max_volume: f32) -> () {
         check_openal_context!(());
 
-        al::alSourcef(self.al_source, ffi::AL_MAX_GAIN, max_volume);
+        let headroom = gain_headroom(self.base_gain);
+        al::alSourcef(self.al_source, ffi::AL_MAX_GAIN, max_volume * headroom);
     }
 
     /**
@@ -406,18 +1427,21 @@ impl AudioController for Music {
 
         let mut volume : f32 = 0.;
         al::alGetSourcef(self.al_source, ffi::AL_MAX_GAIN, &mut volume);
-        volume
+        volume / gain_headroom(self.base_gain)
     }
 
     /**
      * Set the Music looping or not
      *
-     * The default looping is false.
+     * The default looping is false. A Music read from a pipe never loops.
      *
      * # Arguments
      * `looping` - The new looping state.
      */
     fn set_looping(&mut self, looping: bool) -> () {
+        if !self.is_seekable {
+            return;
+        }
         if let Some(ref sender) = self.looping_sender {
             sender.send(looping);
         }
@@ -675,12 +1699,12 @@ impl Drop for Music {
     /// Destroy all the resources of the Music.
     fn drop(&mut self) -> () {
         self.stop();
-        if let Some(handle) = self.thread_handle.take() {
-            handle.join();
+        if let Some(id) = self.stream_id.take() {
+            streamer::unregister(id);
         }
         unsafe {
             al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
-            ffi::alDeleteBuffers(2, &mut self.al_buffers[0]);
+            ffi::alDeleteBuffers(self.al_buffers.len() as i32, &mut self.al_buffers[0]);
             ffi::alDeleteSources(1, &mut self.al_source);
         }
     }
@@ -690,7 +1714,16 @@ impl Drop for Music {
 mod test {
     #![allow(non_snake_case)]
 
-    use music::Music;
+    use std::io::Cursor;
+    use std::fs::File;
+    use std::time::Duration;
+    use std::collections::VecDeque;
+    use music::{Music, Chunk, locate};
+    use beat_clock::{Tempo, Boundary};
+    use load_options::LoadOptions;
+    use sample::SampleType;
+    use sprite::Region;
+    use channel_mix::{ChannelMix, Downmix};
     use states::State::{Playing, Paused, Stopped};
     use audio_controller::AudioController;
 
@@ -710,6 +1743,66 @@ mod test {
         assert!(msc.is_err());
     }
 
+    #[test]
+    #[ignore]
+    fn music_create_with_options_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.sample_type = SampleType::Float32;
+        let msc = Music::new_with_options("res/shot.wav", &options);
+
+        assert!(msc.is_ok());
+    }
+
+    #[test]
+    #[ignore]
+    fn music_create_mono_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.channel_mix = ChannelMix::Mono(Downmix::Average);
+        let msc = Music::new_with_options("res/shot.wav", &options);
+
+        assert!(msc.is_ok());
+    }
+
+    #[test]
+    #[ignore]
+    fn music_from_reader_OK() -> () {
+        let bytes = include_bytes!("../res/shot.wav");
+        let msc = Music::from_reader(Cursor::new(&bytes[..]));
+
+        assert!(msc.is_ok());
+    }
+
+    #[test]
+    #[ignore]
+    fn music_from_reader_FAIL() -> () {
+        let msc = Music::from_reader(Cursor::new(b"not a music file".to_vec()));
+
+        assert!(msc.is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn music_from_pipe_OK() -> () {
+        let file = File::open("res/shot.wav").unwrap();
+        let mut msc = Music::from_pipe(file).unwrap();
+
+        msc.set_looping(true);
+        assert_eq!(msc.is_looping(), false);
+        assert!(msc.set_loop_points(Some(Region::Frames(0, 100))).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn music_loop_points_OK() -> () {
+        let mut msc = Music::new("res/shot.wav").expect("Cannot create Music");
+
+        msc.set_loop_points(Some(Region::Frames(100, 1000))).unwrap();
+        assert_eq!(msc.get_loop_points(), Some((100, 1000)));
+        assert!(msc.set_loop_points(Some(Region::Frames(1000, 100))).is_err());
+        msc.set_loop_points(None).unwrap();
+        assert_eq!(msc.get_loop_points(), None);
+    }
+
     #[test]
     #[ignore]
     fn music_play_OK() -> () {
@@ -883,4 +1976,89 @@ mod test {
         println!("{}", &msc.get_attenuation());
         assert_eq!(&msc.get_attenuation(), &0.5f32);
     }
+
+    #[test]
+    fn music_locate_OK() -> () {
+        let chunk = |speed: f32, starts: Vec<(usize, u64)>| Chunk {
+            frames: 100,
+            speed: speed,
+            starts: starts
+        };
+        let mut timeline = VecDeque::new();
+        timeline.push_back(chunk(1., Vec::new()));
+        timeline.push_back(chunk(1., vec![(0, 0)]));
+        timeline.push_back(chunk(1., vec![(0, 100), (50, 20)]));
+
+        assert_eq!(locate(&timeline, 10, 44100, 44100), Some(0));
+        assert_eq!(locate(&timeline, 110, 44100, 44100), Some(10));
+        assert_eq!(locate(&timeline, 240, 44100, 44100), Some(140));
+        assert_eq!(locate(&timeline, 260, 44100, 44100), Some(30));
+        assert_eq!(locate(&timeline, 300, 44100, 44100), None);
+        assert_eq!(locate(&timeline, 110, 22050, 44100), Some(5));
+
+        timeline.pop_front();
+        timeline.push_back(chunk(0.5, vec![(0, 1000)]));
+        assert_eq!(locate(&timeline, 240, 44100, 44100), Some(1020));
+    }
+
+    #[test]
+    #[ignore]
+    fn music_set_speed_OK() -> () {
+        let mut msc = Music::new("res/shot.wav").expect("Cannot create Music");
+
+        assert!(msc.set_speed(0.5).is_ok());
+        msc.play();
+        assert!(msc.set_speed(1.5).is_ok());
+        assert_eq!(msc.get_speed(), 1.5);
+        assert!(msc.set_speed(2.5).is_err());
+        assert_eq!(msc.get_speed(), 1.5);
+    }
+
+    #[test]
+    #[ignore]
+    fn music_set_tempo_OK() -> () {
+        let mut msc = Music::new("res/shot.wav").expect("Cannot create Music");
+
+        assert!(msc.set_tempo(Some(Tempo::new(120.))).is_ok());
+        assert_eq!(msc.get_tempo(), Some(Tempo::new(120.)));
+        assert!(msc.set_tempo(Some(Tempo::new(-1.))).is_err());
+        assert_eq!(msc.get_tempo(), Some(Tempo::new(120.)));
+    }
+
+    #[test]
+    #[ignore]
+    fn music_beat_position_OK() -> () {
+        let mut msc = Music::new("res/music.ogg").expect("Cannot create Music");
+        msc.set_tempo(Some(Tempo::new(120.))).unwrap();
+
+        assert_eq!(msc.get_beat_position(), None);
+        msc.play();
+        let position = msc.get_beat_position().unwrap();
+        assert_eq!(position.bar, 0);
+        assert!(msc.get_time_to_next(Boundary::Bar).unwrap() <= Duration::from_secs(2));
+    }
+
+    #[test]
+    #[ignore]
+    fn music_stop_at_next_FAIL() -> () {
+        let mut msc = Music::new("res/music.ogg").expect("Cannot create Music");
+        msc.set_tempo(None).unwrap();
+        msc.play();
+
+        assert!(msc.stop_at_next(Boundary::Bar).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn music_switch_at_next_OK() -> () {
+        let mut msc = Music::new("res/music.ogg").expect("Cannot create Music");
+        let mut next = Music::new("res/shot.wav").expect("Cannot create Music");
+        msc.set_tempo(Some(Tempo::new(240.))).unwrap();
+        msc.play();
+
+        assert!(msc.switch_at_next(Boundary::Beat, &mut next).is_ok());
+        assert_eq!(next.get_state(), Playing);
+        ::std::thread::sleep(Duration::from_millis(300));
+        assert_eq!(msc.get_state(), Stopped);
+    }
 }
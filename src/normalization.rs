@@ -0,0 +1,404 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Compute the gain bringing a sound to a target level.
+
+use std::f64::consts::PI;
+
+use sndfile::SndFile;
+use sndfile::SeekMode::SeekSet;
+use comments::get_comment;
+use load_options::LoadOptions;
+
+/// Blocks quieter than this are ignored by the loudness measure, in LUFS.
+const ABSOLUTE_GATE: f64 = -70.;
+/// Blocks this much quieter than the average are ignored, in LU.
+const RELATIVE_GATE: f64 = -10.;
+/// Frames read at once when a file is measured.
+const MEASURE_CHUNK_FRAMES: usize = 65536;
+
+/// The level a sound is normalized to when it's loaded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normalization {
+    /// Keep the level of the file
+    Off,
+    /// Bring the highest sample to the given level, in dBFS (e.g. -1.)
+    Peak(f32),
+    /// Bring the integrated loudness, measured like EBU R128, to the given
+    /// level in LUFS (e.g. -23.)
+    Loudness(f32)
+}
+
+impl Default for Normalization {
+    fn default() -> Normalization {
+        Normalization::Off
+    }
+}
+
+/// The ReplayGain tags to use when a file has them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReplayGain {
+    /// Use REPLAYGAIN_TRACK_GAIN, to play tracks at the same loudness
+    Track,
+    /// Use REPLAYGAIN_ALBUM_GAIN, keeping the level differences of an album,
+    /// falling back on the track gain
+    Album
+}
+
+/**
+ * Measure the integrated loudness of a sound, like EBU R128 does.
+ *
+ * The samples are K-weighted, split in 400 ms blocks overlapping by 75%,
+ * then the blocks below the absolute and relative gates are ignored.
+ */
+#[derive(Clone, Debug)]
+pub struct LoudnessMeter {
+    channels: usize,
+    /// The weight of each channel in the sum
+    weights: Vec<f64>,
+    /// The K-weighting filters of each channel
+    filters: Vec<[Biquad; 2]>,
+    /// The number of frames in 100 ms
+    step_frames: usize,
+    /// The frames accumulated in the current 100 ms step
+    step_count: usize,
+    /// The weighted energy accumulated in the current step
+    step_energy: f64,
+    /// The mean energy of the last steps
+    steps: Vec<f64>,
+    /// The mean energy of each 400 ms block
+    blocks: Vec<f64>
+}
+
+impl LoudnessMeter {
+    /**
+     * Create a new LoudnessMeter.
+     *
+     * # Arguments
+     * * `channels` - The number of interleaved channels
+     * * `sample_rate` - The sample rate of the samples
+     */
+    pub fn new(channels: i32, sample_rate: i32) -> LoudnessMeter {
+        let channels = if channels < 1 { 1 } else { channels as usize };
+        let rate = if sample_rate < 1 { 1. } else { sample_rate as f64 };
+        // The surround channels of a 5.1 sound count more, the LFE is ignored
+        let weights = if channels == 6 {
+            vec![1., 1., 1., 0., 1.41, 1.41]
+        } else {
+            vec![1.; channels]
+        };
+
+        LoudnessMeter {
+            channels: channels,
+            weights: weights,
+            filters: vec![[Biquad::high_shelf(rate), Biquad::high_pass(rate)]; channels],
+            step_frames: (rate / 10.).round().max(1.) as usize,
+            step_count: 0,
+            step_energy: 0.,
+            steps: Vec::new(),
+            blocks: Vec::new()
+        }
+    }
+
+    /// Measure the next interleaved samples.
+    pub fn process(&mut self, samples: &[f32]) -> () {
+        for frame in samples.chunks(self.channels) {
+            for (c, &sample) in frame.iter().enumerate() {
+                let shelved = self.filters[c][0].process(sample as f64);
+                let filtered = self.filters[c][1].process(shelved);
+                self.step_energy += self.weights[c] * filtered * filtered;
+            }
+            self.step_count += 1;
+
+            if self.step_count == self.step_frames {
+                self.steps.push(self.step_energy / self.step_frames as f64);
+                self.step_count = 0;
+                self.step_energy = 0.;
+                if self.steps.len() >= 4 {
+                    let block = self.steps[self.steps.len() - 4..].iter().sum::<f64>() / 4.;
+                    self.blocks.push(block);
+                }
+            }
+        }
+    }
+
+    /**
+     * Get the integrated loudness of the samples measured so far.
+     *
+     * # Return
+     * The loudness in LUFS, or None if the sound is silent or shorter than
+     * 400 ms.
+     */
+    pub fn integrated(&self) -> Option<f32> {
+        let gated: Vec<f64> = self.blocks.iter()
+            .cloned()
+            .filter(|&energy| loudness(energy) > ABSOLUTE_GATE)
+            .collect();
+        if gated.is_empty() {
+            return None;
+        }
+
+        let threshold = loudness(mean(&gated)) + RELATIVE_GATE;
+        let gated: Vec<f64> = gated.into_iter()
+            .filter(|&energy| loudness(energy) > threshold)
+            .collect();
+        if gated.is_empty() {
+            return None;
+        }
+        Some(loudness(mean(&gated)) as f32)
+    }
+}
+
+fn loudness(energy: f64) -> f64 {
+    if energy <= 0. { ::std::f64::NEG_INFINITY } else { -0.691 + 10. * energy.log10() }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// A second order filter of the K-weighting.
+#[derive(Clone, Copy, Debug)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2]
+}
+
+impl Biquad {
+    /// The high shelf modelling the head, for any sample rate.
+    fn high_shelf(rate: f64) -> Biquad {
+        let f0 = 1681.974450955533;
+        let gain = 3.999843853973347;
+        let q = 0.7071752369554196;
+        let k = (PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain / 20.);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1. + k / q + k * k;
+        Biquad {
+            b: [(vh + vb * k / q + k * k) / a0,
+                2. * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+            z: [0., 0.]
+        }
+    }
+
+    /// The high pass removing the lowest frequencies, for any sample rate.
+    fn high_pass(rate: f64) -> Biquad {
+        let f0 = 38.13547087602444;
+        let q = 0.5003270373238773;
+        let k = (PI * f0 / rate).tan();
+        let a0 = 1. + k / q + k * k;
+        Biquad {
+            b: [1., -2., 1.],
+            a: [2. * (k * k - 1.) / a0, (1. - k / q + k * k) / a0],
+            z: [0., 0.]
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// Convert decibels to a linear gain.
+fn db_to_gain(db: f64) -> f32 {
+    10f64.powf(db / 20.) as f32
+}
+
+/// Parse a ReplayGain value like "-6.48 dB".
+fn parse_replay_gain(value: &str) -> Option<f64> {
+    value.trim().trim_end_matches("dB").trim_end_matches("db").trim().parse::<f64>().ok()
+}
+
+/**
+ * Get the gain given by the ReplayGain tags of a file.
+ *
+ * The gain is lowered if needed so the peak tag doesn't clip.
+ */
+fn replay_gain(comments: &[(String, String)], mode: ReplayGain) -> Option<f32> {
+    let (gain_key, peak_key) = match mode {
+        ReplayGain::Album if get_comment(comments, "REPLAYGAIN_ALBUM_GAIN").is_some() =>
+            ("REPLAYGAIN_ALBUM_GAIN", "REPLAYGAIN_ALBUM_PEAK"),
+        _ => ("REPLAYGAIN_TRACK_GAIN", "REPLAYGAIN_TRACK_PEAK")
+    };
+
+    let gain = match get_comment(comments, gain_key).and_then(parse_replay_gain) {
+        Some(db) => db_to_gain(db),
+        None => return None
+    };
+    match get_comment(comments, peak_key).and_then(|peak| peak.trim().parse::<f32>().ok()) {
+        Some(peak) if peak > 0. => Some(gain.min(1. / peak)),
+        _ => Some(gain)
+    }
+}
+
+/**
+ * Compute the base gain of a sound from the load options.
+ *
+ * The ReplayGain tags are used first if requested, then the normalization.
+ * The file is read from the start to measure the loudness when the samples
+ * aren't decoded yet, and left at the start.
+ *
+ * # Arguments
+ * * `file` - The file of the sound
+ * * `options` - The load options
+ * * `decoded` - The decoded samples, with their channel count and sample
+ * rate, if they are available
+ *
+ * # Return
+ * The gain to apply under the volume, 1. if the level is kept. Err(String)
+ * if the comments of the file can't be read.
+ */
+#[doc(hidden)]
+pub fn compute_base_gain(file: &mut SndFile,
+                         options: &LoadOptions,
+                         decoded: Option<(&[f32], i32, i32)>) -> Result<f32, String> {
+    if let Some(mode) = options.replay_gain {
+        let comments = match file.get_comments() {
+            Ok(comments) => comments,
+            Err(err)     => return Err(err)
+        };
+        if let Some(gain) = replay_gain(&comments, mode) {
+            return Ok(gain);
+        }
+    }
+
+    Ok(match options.normalization {
+        Normalization::Off => 1.,
+        Normalization::Peak(target) => {
+            let peak = match decoded {
+                Some((samples, _, _)) =>
+                    samples.iter().fold(0f32, |peak, s| peak.max(s.abs())) as f64,
+                None => file.calc_norm_signal_max().unwrap_or(0.)
+            };
+            if peak > 0. { db_to_gain(target as f64) / peak as f32 } else { 1. }
+        },
+        Normalization::Loudness(target) => {
+            let measured = match decoded {
+                Some((samples, channels, sample_rate)) => {
+                    let mut meter = LoudnessMeter::new(channels, sample_rate);
+                    meter.process(samples);
+                    meter.integrated()
+                },
+                None => measure_file(file)
+            };
+            match measured {
+                Some(loudness) => db_to_gain((target - loudness) as f64),
+                None => 1.
+            }
+        }
+    })
+}
+
+/**
+ * Get the AL_MAX_GAIN letting a source reach its base gain.
+ *
+ * OpenAL clamps the gain of a source to AL_MAX_GAIN, 1. by default, which
+ * would cancel a base gain above 1. The max volume of the sounds is scaled by
+ * this value.
+ */
+#[doc(hidden)]
+pub fn gain_headroom(base_gain: f32) -> f32 {
+    if base_gain > 1. { base_gain } else { 1. }
+}
+
+/// Measure the loudness of a whole file, then seek back to its start.
+fn measure_file(file: &mut SndFile) -> Option<f32> {
+    let infos = file.get_sndinfo();
+    if infos.channels < 1 {
+        return None;
+    }
+    let mut meter = LoudnessMeter::new(infos.channels, infos.samplerate);
+    let mut samples = vec![0f32; MEASURE_CHUNK_FRAMES * infos.channels as usize];
+
+    file.seek(0, SeekSet);
+    loop {
+        let len = samples.len() as i64;
+        let read = file.read_f32(&mut samples[..], len);
+        if read <= 0 {
+            break;
+        }
+        meter.process(&samples[..read as usize]);
+        if read < len {
+            break;
+        }
+    }
+    file.seek(0, SeekSet);
+    meter.integrated()
+}
+
+#[cfg(test)]
+mod test {
+    use normalization::{LoudnessMeter, ReplayGain, replay_gain, gain_headroom};
+
+    fn sine(frequency: f32, amplitude: f32, seconds: usize, rate: usize) -> Vec<f32> {
+        (0..seconds * rate).map(|i| {
+            amplitude * (i as f32 * frequency * 2. * ::std::f32::consts::PI / rate as f32).sin()
+        }).collect()
+    }
+
+    #[test]
+    fn normalization_loudness_sine() -> () {
+        // A full scale 1 kHz sine on one channel measures -3.01 LUFS
+        let mut meter = LoudnessMeter::new(1, 48000);
+        meter.process(&sine(1000., 1., 5, 48000));
+        let loudness = meter.integrated().unwrap();
+        assert!((loudness + 3.01).abs() < 0.1, "{}", loudness);
+
+        // 20 dB lower
+        let mut meter = LoudnessMeter::new(1, 44100);
+        meter.process(&sine(1000., 0.1, 5, 44100));
+        let loudness = meter.integrated().unwrap();
+        assert!((loudness + 23.01).abs() < 0.1, "{}", loudness);
+    }
+
+    #[test]
+    fn normalization_loudness_silence() -> () {
+        let mut meter = LoudnessMeter::new(2, 44100);
+        meter.process(&vec![0.; 44100 * 2]);
+        assert_eq!(meter.integrated(), None);
+    }
+
+    #[test]
+    fn normalization_replay_gain() -> () {
+        let comments = vec![("REPLAYGAIN_TRACK_GAIN".to_string(), "-6.02 dB".to_string()),
+                            ("REPLAYGAIN_ALBUM_GAIN".to_string(), "+6.02 dB".to_string()),
+                            ("REPLAYGAIN_ALBUM_PEAK".to_string(), "0.8".to_string())];
+
+        let track = replay_gain(&comments, ReplayGain::Track).unwrap();
+        assert!((track - 0.5).abs() < 0.001);
+        // The album gain is limited by the peak
+        assert_eq!(replay_gain(&comments, ReplayGain::Album), Some(1.25));
+        assert_eq!(replay_gain(&[], ReplayGain::Track), None);
+    }
+
+    #[test]
+    fn normalization_gain_headroom() -> () {
+        assert_eq!(gain_headroom(0.5), 1.);
+        assert_eq!(gain_headroom(2.), 2.);
+    }
+}
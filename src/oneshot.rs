@@ -0,0 +1,211 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Play sounds without keeping them alive.
+
+use std::sync::Mutex;
+
+use internal::OpenAlData;
+use sound_data;
+use sound_data::SoundData;
+use normalization::gain_headroom;
+use openal::{ffi, al};
+
+lazy_static! {
+    static ref ONESHOTS: Mutex<OneShots> = Mutex::new(OneShots {
+        playing: Vec::new(),
+        free: Vec::new()
+    });
+}
+
+/// The sources owned by the library to play one-shot sounds.
+struct OneShots {
+    /// The sources playing, with the buffer they play
+    playing: Vec<(u32, u32)>,
+    /// The sources ready to be reused
+    free: Vec<u32>
+}
+
+impl OneShots {
+    /// Recycle the sources which finished playing.
+    fn reap(&mut self) -> () {
+        let free = &mut self.free;
+        self.playing.retain(|&(source, _)| {
+            if al::alGetState(source) == ffi::AL_STOPPED {
+                al::alSourcei(source, ffi::AL_BUFFER, 0);
+                free.push(source);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+/**
+ * The parameters of a one-shot sound.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OneShotParams {
+    /// The position of the sound, or None to play it on the listener
+    /// (default None)
+    pub position: Option<[f32; 3]>,
+    /// The volume of the sound (default 1.)
+    pub volume: f32,
+    /// The pitch of the sound (default 1.)
+    pub pitch: f32
+}
+
+impl Default for OneShotParams {
+    fn default() -> OneShotParams {
+        OneShotParams {
+            position: None,
+            volume: 1.,
+            pitch: 1.
+        }
+    }
+}
+
+/**
+ * Play a SoundData once, without keeping a Sound alive.
+ *
+ * The library owns the source playing the sound and recycles it when the
+ * playback is finished. If the SoundData is dropped before the end of the
+ * playback, the sound is stopped.
+ *
+ * # Arguments
+ * * `data` - The SoundData to play
+ * * `params` - The position, volume and pitch of the sound
+ *
+ * # Return
+ * `Ok(())` if the sound is playing, `Err(String)` if no source is available.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{SoundData, OneShotParams};
+ *
+ * let shot = SoundData::new("path/to/shot.ogg").unwrap();
+ *
+ * let mut params = OneShotParams::default();
+ * params.position = Some([10., 0., 5.]);
+ * params.pitch = 1.1;
+ * ears::play_oneshot(&shot, params).unwrap();
+ * ```
+ */
+pub fn play_oneshot(data: &SoundData, params: OneShotParams) -> Result<(), String> {
+    check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+    let mut oneshots = match ONESHOTS.lock() {
+        Ok(oneshots) => oneshots,
+        Err(poison_error) => {
+            return Err(format!("Can't lock one-shot sources mutex: {}", poison_error));
+        }
+    };
+    oneshots.reap();
+
+    let source = match oneshots.free.pop() {
+        Some(source) => source,
+        None => {
+            let mut source_id = 0;
+            al::alGenSources(1, &mut source_id);
+            if let Some(err) = al::openal_has_error() {
+                return Err(format!("Internal OpenAL error: {}", err));
+            }
+            source_id
+        }
+    };
+
+    let buffer = sound_data::get_buffer(data);
+    al::alSourcei(source, ffi::AL_BUFFER, buffer as i32);
+    al::alSourcef(source, ffi::AL_GAIN, params.volume * data.get_base_gain());
+    al::alSourcef(source, ffi::AL_MAX_GAIN, gain_headroom(data.get_base_gain()));
+    al::alSourcef(source, ffi::AL_PITCH, params.pitch);
+    match params.position {
+        Some(position) => {
+            al::alSourcei(source, ffi::AL_SOURCE_RELATIVE, ffi::ALC_FALSE as i32);
+            al::alSourcefv(source, ffi::AL_POSITION, &position[0]);
+        },
+        None => {
+            al::alSourcei(source, ffi::AL_SOURCE_RELATIVE, ffi::ALC_TRUE as i32);
+            al::alSourcefv(source, ffi::AL_POSITION, &[0f32; 3][0]);
+        }
+    }
+    al::alSourcePlay(source);
+
+    if let Some(err) = al::openal_has_error() {
+        al::alSourcei(source, ffi::AL_BUFFER, 0);
+        oneshots.free.push(source);
+        return Err(format!("Internal OpenAL error: {}", err));
+    }
+    oneshots.playing.push((source, buffer));
+    Ok(())
+}
+
+/**
+ * Stop the one-shot sounds playing a buffer.
+ *
+ * Called before a buffer is deleted, OpenAL cannot delete a buffer still
+ * attached to a source.
+ */
+#[doc(hidden)]
+pub fn release_buffer(buffer: u32) -> () {
+    if let Ok(mut oneshots) = ONESHOTS.lock() {
+        let OneShots { ref mut playing, ref mut free } = *oneshots;
+        playing.retain(|&(source, source_buffer)| {
+            if source_buffer == buffer {
+                al::alSourceStop(source);
+                al::alSourcei(source, ffi::AL_BUFFER, 0);
+                free.push(source);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use oneshot::{play_oneshot, OneShotParams};
+    use sound_data::SoundData;
+
+    #[test]
+    #[ignore]
+    fn oneshot_play_OK() -> () {
+        let data = SoundData::new("res/shot.wav").unwrap();
+
+        assert!(play_oneshot(&data, OneShotParams::default()).is_ok());
+    }
+
+    #[test]
+    #[ignore]
+    fn oneshot_drop_data_OK() -> () {
+        let data = SoundData::new("res/shot.wav").unwrap();
+        let mut params = OneShotParams::default();
+        params.position = Some([1., 2., 3.]);
+        play_oneshot(&data, params).unwrap();
+
+        // The sound is stopped and the buffer freed
+        drop(data);
+    }
+}
@@ -53,14 +53,40 @@ pub mod ffi {
     pub const AL_MAX_DISTANCE:        i32         = 0x1023;
     pub const AL_REFERENCE_DISTANCE:  i32         = 0x1020;
     pub const AL_ROLLOFF_FACTOR:      i32         = 0x1021;
+    pub const AL_SEC_OFFSET:          i32         = 0x1024;
+    pub const AL_SAMPLE_OFFSET:       i32         = 0x1025;
 
     /// Sound format
+    pub const AL_FORMAT_MONO8:        i32         = 0x1100;
+    pub const AL_FORMAT_STEREO8:      i32         = 0x1102;
+    pub const AL_FORMAT_51CHN8:       i32         = 0x120A;
+    pub const AL_FORMAT_61CHN8:       i32         = 0x120D;
+    pub const AL_FORMAT_71CHN8:       i32         = 0x1210;
+    pub const AL_FORMAT_QUAD8:        i32         = 0x1204;
     pub const AL_FORMAT_MONO16:       i32         = 0x1101;
     pub const AL_FORMAT_STEREO16:     i32         = 0x1103;
     pub const AL_FORMAT_51CHN16:      i32         = 0x120B;
     pub const AL_FORMAT_61CHN16:      i32         = 0x120E;
     pub const AL_FORMAT_71CHN16:      i32         = 0x1211;
     pub const AL_FORMAT_QUAD16:       i32         = 0x1205;
+    pub const AL_FORMAT_MONO_FLOAT32: i32         = 0x10010;
+    pub const AL_FORMAT_STEREO_FLOAT32: i32       = 0x10011;
+    pub const AL_FORMAT_51CHN32:      i32         = 0x120C;
+    pub const AL_FORMAT_61CHN32:      i32         = 0x120F;
+    pub const AL_FORMAT_71CHN32:      i32         = 0x1212;
+    pub const AL_FORMAT_QUAD32:       i32         = 0x1206;
+
+    /// Buffer params
+    pub const AL_SIZE:                i32         = 0x2004;
+    pub const AL_LOOP_POINTS_SOFT:    i32         = 0x2015;
+
+    /// AL_SOFT_source_resampler
+    pub const AL_NUM_RESAMPLERS_SOFT:     i32     = 0x1210;
+    pub const AL_DEFAULT_RESAMPLER_SOFT:  i32     = 0x1211;
+    pub const AL_SOURCE_RESAMPLER_SOFT:   i32     = 0x1212;
+    pub const AL_RESAMPLER_NAME_SOFT:     i32     = 0x1213;
+
+    pub const ALC_FREQUENCY:          i32         = 0x1007;
 
     /// Source params
     pub const AL_BUFFER:              i32         = 0x1009;
@@ -113,6 +139,9 @@ pub mod ffi {
         pub fn alSourcePlay(source: u32) -> ();
         pub fn alSourcePause(source: u32) -> ();
         pub fn alSourceStop(source: u32) -> ();
+        pub fn alSourcePlayv(n: i32, sources: *const u32) -> ();
+        pub fn alSourcePausev(n: i32, sources: *const u32) -> ();
+        pub fn alSourceStopv(n: i32, sources: *const u32) -> ();
         pub fn alGetSourcei(source: u32, param: i32, value: *mut i32) -> ();
         pub fn alGetSourcef(source: u32, param: i32, value: *mut f32) -> ();
         pub fn alSourcefv(source: u32, param: i32, value: *const f32) -> ();
@@ -130,11 +159,17 @@ pub mod ffi {
 
         /// extension check
         pub fn alcIsExtensionPresent(device: ALCdevicePtr, extension: *const c_char) -> ALCboolean;
+        pub fn alIsExtensionPresent(extension: *const c_char) -> ALCboolean;
 
         /// Buffers functions
         pub fn alGenBuffers(n: i32, buffers: *mut u32) -> ();
         pub fn alDeleteBuffers(n: i32, buffers: *mut u32);
         pub fn alBufferData(buffer: u32, format: i32, data: *mut c_void, size: i32, freq: i32) -> ();
+        pub fn alGetBufferi(buffer: u32, param: i32, value: *mut i32) -> ();
+        pub fn alBufferiv(buffer: u32, param: i32, values: *const i32) -> ();
+        pub fn alGetInteger(param: i32) -> i32;
+        pub fn alGetProcAddress(fname: *const c_char) -> *mut c_void;
+        pub fn alGetBufferiv(buffer: u32, param: i32, values: *mut i32) -> ();
 
         /// Error
         pub fn alGetError() -> i32;
@@ -150,11 +185,24 @@ pub mod al {
 
     use super::ffi;
     use libc::c_void;
+    use std::ffi::CString;
 
     pub fn alBufferData(buffer: u32, format: i32, data: *mut c_void, size: i32, freq: i32) -> () {
         unsafe { ffi::alBufferData(buffer, format, data, size, freq); }
     }
 
+    pub fn alGetBufferi(buffer: u32, param: i32, value: *mut i32) -> () {
+        unsafe { ffi::alGetBufferi(buffer, param, value); }
+    }
+
+    pub fn alBufferiv(buffer: u32, param: i32, values: *const i32) -> () {
+        unsafe { ffi::alBufferiv(buffer, param, values); }
+    }
+
+    pub fn alGetBufferiv(buffer: u32, param: i32, values: *mut i32) -> () {
+        unsafe { ffi::alGetBufferiv(buffer, param, values); }
+    }
+
     pub fn alSourceQueueBuffers(source: u32, nb: i32, buffers: *const u32) -> () {
         unsafe { ffi::alSourceQueueBuffers(source, nb, buffers); }
     }
@@ -193,6 +241,19 @@ pub mod al {
         unsafe { ffi::alSourceStop(source); }
     }
 
+    /// Play several sources at the same sample.
+    pub fn alSourcePlayv(sources: &[u32]) -> () {
+        unsafe { ffi::alSourcePlayv(sources.len() as i32, sources.as_ptr()); }
+    }
+
+    pub fn alSourcePausev(sources: &[u32]) -> () {
+        unsafe { ffi::alSourcePausev(sources.len() as i32, sources.as_ptr()); }
+    }
+
+    pub fn alSourceStopv(sources: &[u32]) -> () {
+        unsafe { ffi::alSourceStopv(sources.len() as i32, sources.as_ptr()); }
+    }
+
     pub fn alSourceUnqueueBuffers(source: u32, nb: i32, buffers: *mut u32) -> () {
         unsafe { ffi::alSourceUnqueueBuffers(source, nb, buffers); }
     }
@@ -254,10 +315,39 @@ pub mod al {
             1 => Some(ffi::AL_FORMAT_MONO16),
             2 => Some(ffi::AL_FORMAT_STEREO16),
             4 => Some(ffi::AL_FORMAT_QUAD16),
-            5 => Some(ffi::AL_FORMAT_51CHN16),
-            6 => Some(ffi::AL_FORMAT_61CHN16),
-            7 => Some(ffi::AL_FORMAT_71CHN16),
+            6 => Some(ffi::AL_FORMAT_51CHN16),
+            7 => Some(ffi::AL_FORMAT_61CHN16),
+            8 => Some(ffi::AL_FORMAT_71CHN16),
             _ => return None
         }
     }
+
+    pub fn get_channels_format8(channels : i32) -> Option<i32> {
+        match channels {
+            1 => Some(ffi::AL_FORMAT_MONO8),
+            2 => Some(ffi::AL_FORMAT_STEREO8),
+            4 => Some(ffi::AL_FORMAT_QUAD8),
+            6 => Some(ffi::AL_FORMAT_51CHN8),
+            7 => Some(ffi::AL_FORMAT_61CHN8),
+            8 => Some(ffi::AL_FORMAT_71CHN8),
+            _ => return None
+        }
+    }
+
+    pub fn get_channels_format_float32(channels : i32) -> Option<i32> {
+        match channels {
+            1 => Some(ffi::AL_FORMAT_MONO_FLOAT32),
+            2 => Some(ffi::AL_FORMAT_STEREO_FLOAT32),
+            4 => Some(ffi::AL_FORMAT_QUAD32),
+            6 => Some(ffi::AL_FORMAT_51CHN32),
+            7 => Some(ffi::AL_FORMAT_61CHN32),
+            8 => Some(ffi::AL_FORMAT_71CHN32),
+            _ => return None
+        }
+    }
+
+    pub fn is_extension_present(extension: &str) -> bool {
+        let c_str = CString::new(extension).unwrap();
+        unsafe { ffi::alIsExtensionPresent(c_str.as_ptr()) != ffi::ALC_FALSE }
+    }
 }
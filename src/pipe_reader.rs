@@ -0,0 +1,191 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Give a seekable interface to a reader which can only be read forward.
+
+use std::cmp;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+
+/// The bytes kept to seek back, enough for the headers of the formats.
+pub const HISTORY_SIZE: usize = 256 * 1024;
+
+/**
+ * A reader of a pipe, a socket or any stream which can't seek.
+ *
+ * libsndfile seeks while it parses the header of a file, so the last bytes
+ * read are kept to seek back among them, and seeking forward reads and
+ * discards the bytes skipped. The end of the stream is unknown, seeking from
+ * the end fails.
+ */
+#[doc(hidden)]
+pub struct PipeReader<R> {
+    inner: R,
+    /// The last bytes read from the stream
+    history: Vec<u8>,
+    /// The position of the first byte of the history in the stream
+    history_start: u64,
+    /// The position the next read starts from
+    position: u64
+}
+
+impl<R: Read> PipeReader<R> {
+    /// Wrap a reader.
+    pub fn new(inner: R) -> PipeReader<R> {
+        PipeReader {
+            inner: inner,
+            history: Vec::new(),
+            history_start: 0,
+            position: 0
+        }
+    }
+
+    /// The position following the last byte read from the stream.
+    fn history_end(&self) -> u64 {
+        self.history_start + self.history.len() as u64
+    }
+
+    /// Read new bytes from the stream into the history.
+    fn fill(&mut self, len: usize) -> io::Result<usize> {
+        let mut buf = vec![0u8; len];
+        let read = loop {
+            match self.inner.read(&mut buf) {
+                Ok(read) => break read,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err)
+            }
+        };
+        self.history.extend_from_slice(&buf[..read]);
+
+        // Forget the oldest bytes
+        if self.history.len() > HISTORY_SIZE {
+            let excess = self.history.len() - HISTORY_SIZE;
+            self.history.drain(..excess);
+            self.history_start += excess as u64;
+        }
+        Ok(read)
+    }
+}
+
+impl<R: Read> Read for PipeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.position == self.history_end() {
+            let len = cmp::min(buf.len(), HISTORY_SIZE);
+            if self.fill(len)? == 0 {
+                return Ok(0);
+            }
+        }
+        let start = (self.position - self.history_start) as usize;
+        let len = cmp::min(buf.len(), self.history.len() - start);
+        buf[..len].copy_from_slice(&self.history[start..start + len]);
+        self.position += len as u64;
+        Ok(len)
+    }
+}
+
+impl<R: Read> Seek for PipeReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset)   => offset as i64,
+            SeekFrom::Current(offset) => self.position as i64 + offset,
+            SeekFrom::End(_)          => return Err(io::Error::new(io::ErrorKind::Other,
+                                                    "The length of a pipe is unknown."))
+        };
+        if target < self.history_start as i64 {
+            return Err(io::Error::new(io::ErrorKind::Other,
+                                      "Can't seek back this far in a pipe."));
+        }
+
+        let target = target as u64;
+        while self.history_end() < target {
+            let len = cmp::min((target - self.history_end()) as usize, HISTORY_SIZE);
+            if self.fill(len)? == 0 {
+                break;
+            }
+        }
+        // Seeking past the end stops at the end
+        self.position = cmp::min(target, self.history_end());
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use std::io::{Read, Seek, SeekFrom};
+    use pipe_reader::{PipeReader, HISTORY_SIZE};
+
+    /// A reader which can't seek, returning few bytes at a time.
+    struct Pipe {
+        data: Vec<u8>,
+        position: usize
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> ::std::io::Result<usize> {
+            let len = buf.len().min(7).min(self.data.len() - self.position);
+            buf[..len].copy_from_slice(&self.data[self.position..self.position + len]);
+            self.position += len;
+            Ok(len)
+        }
+    }
+
+    fn pipe(len: usize) -> PipeReader<Pipe> {
+        PipeReader::new(Pipe { data: (0..len).map(|i| i as u8).collect(), position: 0 })
+    }
+
+    #[test]
+    fn pipereader_seek_back_OK() -> () {
+        let mut reader = pipe(100);
+        let mut buf = [0u8; 20];
+        reader.read_exact(&mut buf).unwrap();
+
+        assert_eq!(reader.seek(SeekFrom::Start(2)).unwrap(), 2);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf[0], 2);
+        assert_eq!(buf[19], 21);
+    }
+
+    #[test]
+    fn pipereader_seek_forward_OK() -> () {
+        let mut reader = pipe(100);
+
+        assert_eq!(reader.seek(SeekFrom::Current(50)).unwrap(), 50);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest.len(), 50);
+        assert_eq!(rest[0], 50);
+    }
+
+    #[test]
+    fn pipereader_seek_FAIL() -> () {
+        let mut reader = pipe(HISTORY_SIZE * 2);
+        assert!(reader.seek(SeekFrom::End(0)).is_err());
+
+        reader.seek(SeekFrom::Start(HISTORY_SIZE as u64 + 10)).unwrap();
+        assert!(reader.seek(SeekFrom::Start(0)).is_err());
+    }
+}
@@ -0,0 +1,1431 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Play a list of files one after the other through a single source.
+
+use std::cmp;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Sender, Receiver};
+use std::time::Duration;
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+use sndfile::SndFile;
+use sndfile::OpenMode::Read;
+use states::State;
+use states::State::{Initial, Playing, Paused, Stopped};
+use audio_controller::AudioController;
+use sample::{SampleType, Samples};
+use load_options::{LoadOptions, validate_header, has_known_length};
+use channel_mix::{ChannelMix, Downmix};
+use normalization::{Normalization, compute_base_gain, gain_headroom};
+use resampler::Resampler;
+use stream_config::StreamConfig;
+use streamer;
+use streamer::{Stream, StreamId};
+use rng::Rng;
+
+/**
+ * The repeat modes of a Playlist.
+ *
+ * * Off - Stop after the last track (default)
+ * * One - Repeat the current track
+ * * All - Start again from the first track after the last one
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Repeat {
+    Off,
+    One,
+    All
+}
+
+impl Default for Repeat {
+    fn default() -> Repeat {
+        Repeat::Off
+    }
+}
+
+/// An event of a Playlist, polled with `Playlist::poll_events`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlaylistEvent {
+    /// A track started, with its index in the Playlist
+    TrackChanged(usize),
+    /// A track couldn't be opened and was skipped
+    TrackError(usize, String),
+    /// The last track ended
+    Ended
+}
+
+/**
+ * Play a list of files one after the other through a single source.
+ *
+ * The next track is opened and decoded while the current one ends, so the
+ * tracks follow each other without a gap. Every track is converted to the
+ * channels and sample rate of the first one played.
+ *
+ * The normalization of the load options isn't applied, because it needs to
+ * decode the whole file, but the ReplayGain tags are used if asked for. The
+ * gain of a track is set on the source when the track is heard.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{Playlist, PlaylistEvent, Repeat, AudioController};
+ *
+ * let mut playlist = Playlist::new().unwrap();
+ * playlist.add("path/to/first.ogg");
+ * playlist.add("path/to/second.ogg");
+ * playlist.set_repeat(Repeat::All);
+ * playlist.play();
+ *
+ * loop {
+ *     for event in playlist.poll_events() {
+ *         if let PlaylistEvent::TrackChanged(index) = event {
+ *             println!("Now playing {}", playlist.get_track(index).unwrap());
+ *         }
+ *     }
+ * }
+ * ```
+ */
+pub struct Playlist {
+    /// The internal OpenAL source identifier
+    al_source: u32,
+    /// The internal OpenAL buffers
+    al_buffers: Vec<u32>,
+    /// The options used to decode the tracks
+    options: LoadOptions,
+    /// The tracks, shared with the stream
+    queue: Arc<Mutex<Queue>>,
+    /// The volumes and the gain of the track heard, shared with the stream
+    level: Arc<Mutex<Level>>,
+    /// True if the tracks are played in a random order
+    shuffle: bool,
+    rng: Rng,
+    /// Channel the stream reports the events to
+    event_sender: Sender<PlaylistEvent>,
+    /// The events not yet polled
+    event_receiver: Receiver<PlaylistEvent>,
+    /// The stream registered with the streaming thread
+    stream_id: Option<StreamId>
+}
+
+impl Playlist {
+    /**
+     * Create a new empty Playlist.
+     *
+     * # Return
+     * A `Result` containing Ok(Playlist) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new() -> Result<Playlist, String> {
+        Playlist::new_with_options(&LoadOptions::default())
+    }
+
+    /**
+     * Create a new empty Playlist with custom load options
+     *
+     * # Argument
+     * * `options` - The options used to decode the tracks
+     *
+     * # Return
+     * A `Result` containing Ok(Playlist) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new_with_options(options: &LoadOptions) -> Result<Playlist, String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        if let Err(err) = options.stream_config.validate() {
+            return Err(err);
+        }
+        if let Err(err) = options.channel_mix.get_channels(1) {
+            return Err(err);
+        }
+
+        let mut source_id = 0;
+        let mut buffer_ids = vec![0; options.stream_config.buffer_count];
+        al::alGenSources(1, &mut source_id);
+        al::alGenBuffers(buffer_ids.len() as i32, &mut buffer_ids[0]);
+
+        if let Some(err) = al::openal_has_error() {
+            return Err(format!("Internal OpenAL error: {}", err));
+        };
+
+        let (event_sender, event_receiver) = channel();
+
+        Ok(Playlist {
+            al_source: source_id,
+            al_buffers: buffer_ids,
+            options: options.clone(),
+            queue: Arc::new(Mutex::new(Queue::default())),
+            level: Arc::new(Mutex::new(Level {
+                volume: 1.,
+                max_volume: 1.,
+                track_gain: 1.
+            })),
+            shuffle: false,
+            rng: Rng::new(),
+            event_sender: event_sender,
+            event_receiver: event_receiver,
+            stream_id: None
+        })
+    }
+
+    /**
+     * Add a track at the end of the Playlist.
+     *
+     * The file is opened only when its turn comes, a file which can't be
+     * played is skipped with a `PlaylistEvent::TrackError`.
+     *
+     * # Argument
+     * * `path` - The path of the file
+     *
+     * # Return
+     * The index of the track in the Playlist.
+     */
+    pub fn add(&mut self, path: &str) -> usize {
+        let mut queue = self.queue.lock().unwrap();
+        let index = queue.tracks.len();
+        queue.tracks.push(path.to_string());
+        queue.order.push(index);
+        index
+    }
+
+    /// Stop the Playlist and remove all its tracks.
+    pub fn clear(&mut self) -> () {
+        self.stop();
+        let mut queue = self.queue.lock().unwrap();
+        queue.tracks.clear();
+        queue.order.clear();
+        queue.current = None;
+    }
+
+    /// Get the number of tracks of the Playlist.
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().tracks.len()
+    }
+
+    /// Check if the Playlist has no track.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * Get the path of a track.
+     *
+     * # Argument
+     * * `index` - The index of the track in the Playlist
+     */
+    pub fn get_track(&self, index: usize) -> Option<String> {
+        self.queue.lock().unwrap().tracks.get(index).cloned()
+    }
+
+    /**
+     * Get the track being played.
+     *
+     * # Return
+     * The index of the track heard, or of the one the Playlist starts from
+     * when stopped. None if no track was played yet or the last one ended.
+     */
+    pub fn get_current(&self) -> Option<usize> {
+        self.queue.lock().unwrap().current
+    }
+
+    /**
+     * Jump to a track.
+     *
+     * The track starts right away if the Playlist is playing, otherwise it's
+     * the one played next time.
+     *
+     * # Argument
+     * * `index` - The index of the track in the Playlist
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if there is no such track.
+     */
+    pub fn jump(&mut self, index: usize) -> Result<(), String> {
+        if index >= self.len() {
+            return Err(format!("The Playlist has no track {}.", index));
+        }
+        let was_active = self.stream_id.is_some() && self.get_state() != Stopped;
+        self.stop();
+        self.queue.lock().unwrap().current = Some(index);
+        if was_active {
+            self.start();
+        }
+        Ok(())
+    }
+
+    /**
+     * Skip to the next track.
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the current track is the last one
+     * and the Playlist doesn't repeat all the tracks.
+     */
+    pub fn next(&mut self) -> Result<(), String> {
+        let next = {
+            let queue = self.queue.lock().unwrap();
+            match queue.current {
+                Some(current) => queue.next_after(current, true),
+                None          => queue.first()
+            }
+        };
+        match next {
+            Some(index) => self.jump(index),
+            None        => Err("There is no next track.".into())
+        }
+    }
+
+    /**
+     * Go back to the previous track.
+     *
+     * The first track restarts if the Playlist doesn't repeat all the
+     * tracks.
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the Playlist is empty.
+     */
+    pub fn previous(&mut self) -> Result<(), String> {
+        let previous = {
+            let queue = self.queue.lock().unwrap();
+            match queue.current {
+                Some(current) => queue.previous_before(current),
+                None          => queue.first()
+            }
+        };
+        match previous {
+            Some(index) => self.jump(index),
+            None        => Err("The Playlist is empty.".into())
+        }
+    }
+
+    /**
+     * Play the tracks in a random order or in the order they were added.
+     *
+     * The current track stays the same, the order of the following ones
+     * changes.
+     *
+     * # Argument
+     * * `shuffle` - True to shuffle the tracks
+     */
+    pub fn set_shuffle(&mut self, shuffle: bool) -> () {
+        let mut queue = self.queue.lock().unwrap();
+        let current = queue.current;
+        if shuffle {
+            shuffle_order(&mut queue.order, current, &mut self.rng);
+        } else {
+            let len = queue.tracks.len();
+            queue.order = (0..len).collect();
+        }
+        self.shuffle = shuffle;
+    }
+
+    /// Check if the tracks are played in a random order.
+    pub fn is_shuffle(&self) -> bool {
+        self.shuffle
+    }
+
+    /**
+     * Set the repeat mode of the Playlist.
+     *
+     * # Argument
+     * * `repeat` - The new repeat mode
+     */
+    pub fn set_repeat(&mut self, repeat: Repeat) -> () {
+        self.queue.lock().unwrap().repeat = repeat;
+    }
+
+    /// Get the repeat mode of the Playlist.
+    pub fn get_repeat(&self) -> Repeat {
+        self.queue.lock().unwrap().repeat
+    }
+
+    /**
+     * Get the events which happened since the last call.
+     *
+     * # Return
+     * The events in the order they happened.
+     */
+    pub fn poll_events(&self) -> Vec<PlaylistEvent> {
+        self.event_receiver.try_iter().collect()
+    }
+
+    /// Start streaming from the current track.
+    fn start(&mut self) -> () {
+        let first = {
+            let mut queue = self.queue.lock().unwrap();
+            let first = match queue.current {
+                Some(current) => Some(current),
+                None          => queue.first()
+            };
+            queue.current = first;
+            first
+        };
+        let first = match first {
+            Some(first) => first,
+            None        => return
+        };
+
+        // The normalization would decode each track entirely
+        let mut options = self.options.clone();
+        options.normalization = Normalization::Off;
+
+        let mut stream = PlaylistStream {
+            al_source: self.al_source,
+            queue: self.queue.clone(),
+            level: self.level.clone(),
+            options: options,
+            out_channels: 0,
+            out_rate: 0,
+            sample_type: self.options.sample_type.resolve(),
+            stream_config: self.options.stream_config,
+            chunk_frames: 0,
+            current: None,
+            next: None,
+            pending: Some(first),
+            queued: VecDeque::new(),
+            free: self.al_buffers.clone(),
+            playing: None,
+            event_sender: self.event_sender.clone()
+        };
+
+        stream.refill();
+        if stream.queued.is_empty() {
+            self.event_sender.send(PlaylistEvent::Ended);
+            self.queue.lock().unwrap().current = None;
+            return;
+        }
+        stream.report_track();
+        al::alSourcePlay(self.al_source);
+        self.stream_id = Some(streamer::register(Box::new(stream)));
+    }
+}
+
+impl AudioController for Playlist {
+    /**
+     * Play or resume the Playlist.
+     *
+     * A stopped Playlist starts from its current track, or from the first
+     * one.
+     */
+    fn play(&mut self) -> () {
+        check_openal_context!(());
+
+        match self.get_state() {
+            Paused => { al::alSourcePlay(self.al_source); return; },
+            _      => {
+                self.stop();
+                self.start();
+            }
+        }
+    }
+
+    /**
+     * Pause the Playlist.
+     */
+    fn pause(&mut self) -> () {
+        check_openal_context!(());
+
+        al::alSourcePause(self.al_source)
+    }
+
+    /**
+     * Stop the Playlist.
+     *
+     * The current track stays the same, it starts again from its beginning
+     * when the Playlist is played.
+     */
+    fn stop(&mut self) -> () {
+        check_openal_context!(());
+
+        if let Some(id) = self.stream_id.take() {
+            streamer::unregister(id);
+        }
+        al::alSourceStop(self.al_source);
+    }
+
+    /**
+     * Check if the Playlist is playing or not.
+     *
+     * # Return
+     * True if the Playlist is playing, false otherwise.
+     */
+    fn is_playing(&self) -> bool {
+        match self.get_state() {
+            Playing     => true,
+            _           => false
+        }
+    }
+
+    /**
+     * Get the current state of the Playlist
+     *
+     * # Return
+     * The state of the playlist as a variant of the enum State
+     */
+    fn get_state(&self) -> State {
+        check_openal_context!(Initial);
+
+        let state  = al::alGetState(self.al_source);
+
+        match state {
+            ffi::AL_INITIAL => Initial,
+            ffi::AL_PLAYING => Playing,
+            ffi::AL_PAUSED  => Paused,
+            ffi::AL_STOPPED => Stopped,
+            _               => unreachable!()
+        }
+    }
+
+    /**
+     * Set the volume of the Playlist.
+     *
+     * A value of 1.0 means unattenuated. Each division by 2 equals an attenuation
+     * of about -6dB. Each multiplicaton by 2 equals an amplification of about
+     * +6dB. The ReplayGain of the track heard is applied on top of it.
+     *
+     * # Argument
+     * * `volume` - The volume of the Playlist, should be between 0. and 1.
+     */
+    fn set_volume(&mut self, volume: f32) -> () {
+        check_openal_context!(());
+
+        let mut level = self.level.lock().unwrap();
+        level.volume = volume;
+        level.apply(self.al_source);
+    }
+
+    /**
+     * Get the volume of the Playlist.
+     *
+     * # Return
+     * The volume of the Playlist between 0. and 1.
+     */
+    fn get_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        self.level.lock().unwrap().volume
+    }
+
+    /**
+     * Set the minimal volume for a Playlist.
+     *
+     * The minimum volume allowed for a playlist, after distance and cone
+     * attenation is applied (if applicable).
+     *
+     * # Argument
+     * * `min_volume` - The new minimal volume of the Playlist should be
+     * between 0. and 1.
+     */
+    fn set_min_volume(&mut self, min_volume: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_MIN_GAIN, min_volume);
+    }
+
+    /**
+     * Get the minimal volume of the Playlist.
+     *
+     * # Return
+     * The minimal volume of the Playlist between 0. and 1.
+     */
+    fn get_min_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut volume : f32 = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_MIN_GAIN, &mut volume);
+        volume
+    }
+
+    /**
+     * Set the maximal volume for a Playlist.
+     *
+     * The maximum volume allowed for a Playlist, after distance and cone
+     * attenation is applied (if applicable). It's raised by a ReplayGain
+     * above 1.
+     *
+     * # Argument
+     * * `max_volume` - The new maximal volume of the Playlist should be
+     * between 0. and 1.
+     */
+    fn set_max_volume(&mut self, max_volume: f32) -> () {
+        check_openal_context!(());
+
+        let mut level = self.level.lock().unwrap();
+        level.max_volume = max_volume;
+        level.apply(self.al_source);
+    }
+
+    /**
+     * Get the maximal volume of the Playlist.
+     *
+     * # Return
+     * The maximal volume of the Playlist between 0. and 1.
+     */
+    fn get_max_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        self.level.lock().unwrap().max_volume
+    }
+
+    /**
+     * Set the Playlist looping or not
+     *
+     * Looping repeats all the tracks, see `set_repeat` for the other modes.
+     * The default looping is false.
+     *
+     * # Arguments
+     * `looping` - The new looping state.
+     */
+    fn set_looping(&mut self, looping: bool) -> () {
+        self.set_repeat(if looping { Repeat::All } else { Repeat::Off });
+    }
+
+    /**
+     * Check if the Playlist is looping or not
+     *
+     * # Return
+     * True if the Playlist repeats one or all the tracks, false otherwise.
+     */
+    fn is_looping(&self) -> bool {
+        self.get_repeat() != Repeat::Off
+    }
+
+    /**
+     * Set the pitch of the Playlist.
+     *
+     * A multiplier for the frequency (sample rate) of the Playlist's buffer.
+     *
+     * Default pitch is 1.0.
+     *
+     * # Argument
+     * * `new_pitch` - The new pitch of the Playlist in the range [0.5 - 2.0]
+     */
+    fn set_pitch(&mut self, pitch: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_PITCH, pitch)
+    }
+
+    /**
+     * Set the pitch of the Playlist.
+     *
+     * # Return
+     * The pitch of the Playlist in the range [0.5 - 2.0]
+     */
+    fn get_pitch(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut pitch = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_PITCH, &mut pitch);
+        pitch
+    }
+
+    /**
+     * Set the position of the Playlist relative to the listener or absolute.
+     *
+     * Default position is absolute.
+     *
+     * # Argument
+     * `relative` - True to set Playlist relative to the listener false to set the
+     * Playlist position absolute.
+     */
+    fn set_relative(&mut self, relative: bool) -> () {
+        check_openal_context!(());
+
+        match relative {
+            true    => al::alSourcei(self.al_source,
+                                     ffi::AL_SOURCE_RELATIVE,
+                                     ffi::ALC_TRUE as i32),
+            false   => al::alSourcei(self.al_source,
+                                     ffi::AL_SOURCE_RELATIVE,
+                                     ffi::ALC_FALSE as i32)
+        };
+    }
+
+    /**
+     * Is the Playlist relative to the listener or not?
+     *
+     * # Return
+     * True if the Playlist is relative to the listener false otherwise
+     */
+    fn is_relative(&mut self) -> bool {
+        check_openal_context!(false);
+
+        let mut boolean = 0;
+        al::alGetSourcei(self.al_source, ffi::AL_SOURCE_RELATIVE, &mut boolean);
+        match boolean as i8 {
+            ffi::ALC_TRUE  => true,
+            ffi::ALC_FALSE => false,
+            _              => unreachable!()
+        }
+    }
+
+    /**
+     * Set the Playlist location in three dimensional space.
+     *
+     * OpenAL, like OpenGL, uses a right handed coordinate system, where in a
+     * frontal default view X (thumb) points right, Y points up (index finger),
+     * and Z points towards the viewer/camera (middle finger).
+     * To switch from a left handed coordinate system, flip the sign on the Z
+     * coordinate.
+     *
+     * Default position is [0., 0., 0.].
+     *
+     * # Argument
+     * * `position` - A three dimensional vector of f32 containing the position
+     * of the listener [x, y, z].
+     */
+    fn set_position(&mut self, position: [f32; 3]) -> () {
+        check_openal_context!(());
+
+        al::alSourcefv(self.al_source, ffi::AL_POSITION, &position[0]);
+    }
+
+    /**
+     * Get the position of the Playlist in three dimensional space.
+     *
+     * # Return
+     * A three dimensional vector of f32 containing the position of the
+     * listener [x, y, z].
+     */
+    fn get_position(&self) -> [f32; 3] {
+        check_openal_context!([0.; 3]);
+
+        let mut position : [f32; 3] = [0.; 3];
+        al::alGetSourcefv(self.al_source, ffi::AL_POSITION, &mut position[0]);
+        position
+    }
+
+    /**
+     * Set the direction of the Playlist.
+     *
+     * Specifies the current direction in local space.
+     *
+     * The default direction is: [0., 0., 0.]
+     *
+     * # Argument
+     * `direction` - The new direction of the Playlist.
+     */
+    fn set_direction(&mut self, direction: [f32; 3]) -> () {
+        check_openal_context!(());
+
+        al::alSourcefv(self.al_source, ffi::AL_DIRECTION, &direction[0]);
+    }
+
+    /**
+     * Get the direction of the Playlist.
+     *
+     * # Return
+     * The current direction of the Playlist.
+     */
+    fn get_direction(&self)  -> [f32; 3] {
+        check_openal_context!([0.; 3]);
+
+        let mut direction : [f32; 3] = [0.; 3];
+        al::alGetSourcefv(self.al_source, ffi::AL_DIRECTION, &mut direction[0]);
+        direction
+    }
+
+    /**
+     * Set the maximum distance of the Playlist.
+     *
+     * The distance above which the source is not attenuated any further with a
+     * clamped distance model, or where attenuation reaches 0.0 gain for linear
+     * distance models with a default rolloff factor.
+     *
+     * The default maximum distance is +inf.
+     *
+     * # Argument
+     * `max_distance` - The new maximum distance in the range [0., +inf]
+     */
+    fn set_max_distance(&mut self, max_distance: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_MAX_DISTANCE, max_distance);
+    }
+
+    /**
+     * Get the maximum distance of the Playlist.
+     *
+     * # Return
+     * The maximum distance of the Playlist in the range [0., +inf]
+     */
+    fn get_max_distance(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut max_distance = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_MAX_DISTANCE, &mut max_distance);
+        max_distance
+    }
+
+    /**
+     * Set the reference distance of the Playlist.
+     *
+     * The distance in units that no attenuation occurs.
+     * At 0.0, no distance attenuation ever occurs on non-linear
+     * attenuation models.
+     *
+     * The default distance reference is 1.
+     *
+     * # Argument
+     * * `ref_distance` - The new reference distance of the Playlist.
+     */
+    fn set_reference_distance(&mut self, ref_distance: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_REFERENCE_DISTANCE, ref_distance);
+    }
+
+    /**
+     * Get the reference distance of the Playlist.
+     *
+     * # Return
+     * The current reference distance of the Playlist.
+     */
+    fn get_reference_distance(&self) -> f32 {
+        check_openal_context!(1.);
+
+        let mut ref_distance = 0.;
+        al::alGetSourcef(self.al_source,
+                         ffi::AL_REFERENCE_DISTANCE,
+                         &mut ref_distance);
+        ref_distance
+    }
+
+    /**
+     * Set the attenuation of a Playlist.
+     *
+     * Multiplier to exaggerate or diminish distance attenuation.
+     * At 0.0, no distance attenuation ever occurs.
+     *
+     * The default attenuation is 1.
+     *
+     * # Arguments
+     * `attenuation` - The new attenuation for the Playlist in the range [0., 1.].
+     */
+    fn set_attenuation(&mut self, attenuation: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_ROLLOFF_FACTOR, attenuation);
+    }
+
+    /**
+     * Get the attenuation of a Playlist.
+     *
+     * # Return
+     * The current attenuation for the Playlist in the range [0., 1.].
+     */
+    fn get_attenuation(&self) -> f32 {
+        check_openal_context!(1.);
+
+        let mut attenuation = 0.;
+        al::alGetSourcef(self.al_source,
+                         ffi::AL_ROLLOFF_FACTOR,
+                         &mut attenuation);
+        attenuation
+    }
+}
+
+impl Drop for Playlist {
+    /// Destroy all the resources of the Playlist.
+    fn drop(&mut self) -> () {
+        if let Some(id) = self.stream_id.take() {
+            streamer::unregister(id);
+        }
+        unsafe {
+            al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+            ffi::alDeleteBuffers(self.al_buffers.len() as i32, &mut self.al_buffers[0]);
+            ffi::alDeleteSources(1, &mut self.al_source);
+        }
+    }
+}
+
+/// The tracks of a Playlist and the order they are played in.
+#[derive(Default)]
+struct Queue {
+    tracks: Vec<String>,
+    /// The indices of the tracks, in the order they are played
+    order: Vec<usize>,
+    repeat: Repeat,
+    /// The track heard, or the one the Playlist starts from
+    current: Option<usize>
+}
+
+impl Queue {
+    fn first(&self) -> Option<usize> {
+        self.order.first().cloned()
+    }
+
+    /**
+     * Get the track played after another one.
+     *
+     * `skip` leaves a track repeated by `Repeat::One`.
+     */
+    fn next_after(&self, track: usize, skip: bool) -> Option<usize> {
+        if self.repeat == Repeat::One && !skip && track < self.tracks.len() {
+            return Some(track);
+        }
+        match self.order.iter().position(|&t| t == track) {
+            Some(pos) if pos + 1 < self.order.len() => Some(self.order[pos + 1]),
+            Some(_) if self.repeat == Repeat::All   => self.first(),
+            Some(_)                                 => None,
+            None                                    => self.first()
+        }
+    }
+
+    /// Get the track played before another one.
+    fn previous_before(&self, track: usize) -> Option<usize> {
+        match self.order.iter().position(|&t| t == track) {
+            Some(pos) if pos > 0                  => Some(self.order[pos - 1]),
+            Some(_) if self.repeat == Repeat::All => self.order.last().cloned(),
+            Some(_)                               => Some(track),
+            None                                  => self.first()
+        }
+    }
+}
+
+/// The volumes of a Playlist and the ReplayGain of the track heard.
+struct Level {
+    volume: f32,
+    max_volume: f32,
+    track_gain: f32
+}
+
+impl Level {
+    /// Set the gains of the source, the ReplayGain under the volumes.
+    fn apply(&self, source: u32) -> () {
+        al::alSourcef(source, ffi::AL_GAIN, self.volume * self.track_gain);
+        al::alSourcef(source, ffi::AL_MAX_GAIN, self.max_volume * gain_headroom(self.track_gain));
+    }
+}
+
+/**
+ * Shuffle the order of the tracks.
+ *
+ * `first` is moved at the start of the order, to keep playing it.
+ */
+fn shuffle_order(order: &mut Vec<usize>, first: Option<usize>, rng: &mut Rng) -> () {
+    // Fisher-Yates
+    for i in (1..order.len()).rev() {
+        let j = rng.index(i + 1);
+        order.swap(i, j);
+    }
+    if let Some(first) = first {
+        if let Some(pos) = order.iter().position(|&t| t == first) {
+            order.remove(pos);
+            order.insert(0, first);
+        }
+    }
+}
+
+/**
+ * Convert interleaved samples to another channel count.
+ *
+ * Fewer channels are mixed down, the surround layouts to stereo with the
+ * usual matrix, and more channels duplicate the existing ones.
+ */
+fn conform_channels(samples: &[f32], channels: i32, out_channels: i32) -> Vec<f32> {
+    if channels == out_channels {
+        samples.to_vec()
+    } else if out_channels == 1 {
+        ChannelMix::Mono(Downmix::Average).apply(samples, channels)
+    } else if out_channels > channels {
+        ChannelMix::Duplicate(out_channels).apply(samples, channels)
+    } else {
+        let weights = match out_channels {
+            2 => stereo_weights(channels),
+            _ => None
+        }.unwrap_or_else(|| {
+            // Average the channels falling on each output channel
+            (0..channels).map(|channel| {
+                (0..out_channels).map(|out| if channel % out_channels == out { 1. } else { 0. })
+                                 .collect()
+            }).collect()
+        });
+        downmix(samples, channels, out_channels, &weights)
+    }
+}
+
+/**
+ * Get the weight of each channel of a surround layout in the left and right
+ * channels, or None for an unknown layout.
+ *
+ * The centre and the surrounds are mixed 3 dB lower, the LFE is dropped.
+ */
+fn stereo_weights(channels: i32) -> Option<Vec<Vec<f32>>> {
+    let h = ::std::f32::consts::FRAC_1_SQRT_2;
+    let weights: &[[f32; 2]] = match channels {
+        // Left, right, centre
+        3 => &[[1., 0.], [0., 1.], [h, h]],
+        // Quad
+        4 => &[[1., 0.], [0., 1.], [h, 0.], [0., h]],
+        // 5.1
+        6 => &[[1., 0.], [0., 1.], [h, h], [0., 0.], [h, 0.], [0., h]],
+        // 6.1, with a back centre
+        7 => &[[1., 0.], [0., 1.], [h, h], [0., 0.], [0.5, 0.5], [h, 0.], [0., h]],
+        // 7.1
+        8 => &[[1., 0.], [0., 1.], [h, h], [0., 0.], [h, 0.], [0., h], [h, 0.], [0., h]],
+        _ => return None
+    };
+    Some(weights.iter().map(|weight| weight.to_vec()).collect())
+}
+
+/**
+ * Mix interleaved samples with a matrix giving the weight of each input
+ * channel in each output channel.
+ *
+ * The weights of an output channel are normalized so the mix never clips.
+ */
+fn downmix(samples: &[f32], channels: i32, out_channels: i32, weights: &[Vec<f32>]) -> Vec<f32> {
+    let out_channels = out_channels as usize;
+    let totals: Vec<f32> = (0..out_channels).map(|out| {
+        weights.iter().map(|weight| weight[out]).sum()
+    }).collect();
+
+    let mut mixed = Vec::with_capacity(samples.len() / channels as usize * out_channels);
+    for frame in samples.chunks(channels as usize) {
+        for out in 0..out_channels {
+            let sum: f32 = frame.iter().zip(weights)
+                                .map(|(sample, weight)| sample * weight[out])
+                                .sum();
+            mixed.push(if totals[out] > 0. { sum / totals[out] } else { 0. });
+        }
+    }
+    mixed
+}
+
+/// A track being decoded.
+struct Track {
+    /// The index of the track in the Playlist
+    index: usize,
+    file: SndFile,
+    /// The channel count of the file
+    channels: i32,
+    /// The conversion of the channels of the file
+    channel_mix: ChannelMix,
+    /// The frames left to read, if the length of the file is known
+    frames_left: Option<u64>,
+    /// The ReplayGain of the track
+    gain: f32,
+    resampler: Option<Resampler>,
+    at_end: bool
+}
+
+impl Track {
+    /**
+     * Open a track.
+     *
+     * `out_channels` and `out_rate` are taken from the track if they are 0.
+     */
+    fn open(index: usize,
+            path: &str,
+            options: &LoadOptions,
+            out_channels: &mut i32,
+            out_rate: &mut i32) -> Result<Track, String> {
+        let mut file = match SndFile::new(path, Read) {
+            Ok(file) => file,
+            Err(err) => return Err(format!("Error while loading track {}: {}", path, err))
+        };
+        let infos = file.get_sndinfo();
+        if let Err(err) = validate_header(&infos) {
+            return Err(err);
+        }
+        let channel_mix = match options.channel_mix.get_channels(infos.channels) {
+            Ok(_)  => options.channel_mix,
+            Err(_) => ChannelMix::Keep
+        };
+        if *out_channels == 0 {
+            *out_channels = channel_mix.get_channels(infos.channels).unwrap_or(infos.channels);
+            *out_rate = options.get_sample_rate(infos.samplerate);
+        }
+
+        let gain = match compute_base_gain(&mut file, options, None) {
+            Ok(gain) => gain,
+            Err(err) => return Err(format!("Error while loading track {}: {}", path, err))
+        };
+        let frames_left = if has_known_length(&infos) { Some(infos.frames as u64) } else { None };
+        Ok(Track {
+            index: index,
+            gain: gain,
+            channels: infos.channels,
+            channel_mix: channel_mix,
+            frames_left: frames_left,
+            resampler: if infos.samplerate != *out_rate {
+                Some(Resampler::new(infos.samplerate, *out_rate, *out_channels))
+            } else {
+                None
+            },
+            file: file,
+            at_end: false
+        })
+    }
+
+    /// Check if the track ends within `frames` frames.
+    fn is_ending(&self, frames: u64) -> bool {
+        self.at_end || self.frames_left.map_or(false, |left| left <= frames)
+    }
+
+    /**
+     * Decode the next frames of the track, converted to the output format.
+     *
+     * Fewer samples are returned at the end of the track.
+     */
+    fn decode(&mut self, frames: usize, out_channels: i32) -> Vec<f32> {
+        let len = frames * self.channels as usize;
+        let mut samples = vec![0f32; len];
+        let read = self.file.read_f32(&mut samples[..], len as i64);
+        let read = if read < 0 { 0 } else { read as usize };
+        let read = read - read % self.channels as usize;
+        if read < len {
+            self.at_end = true;
+        }
+        if let Some(ref mut left) = self.frames_left {
+            *left = left.saturating_sub((read / self.channels as usize) as u64);
+        }
+
+        let mixed = self.channel_mix.apply(&samples[..read], self.channels);
+        let mixed_channels = self.channel_mix.get_channels(self.channels).unwrap_or(self.channels);
+        let mut converted = conform_channels(&mixed, mixed_channels, out_channels);
+        if let Some(ref mut resampler) = self.resampler {
+            let mut resampled = resampler.process(&converted);
+            if self.at_end {
+                resampled.extend(resampler.flush());
+            }
+            converted = resampled;
+        }
+        converted
+    }
+}
+
+/// A track starting in a chunk, as (frame, track index, ReplayGain).
+type TrackStart = (usize, usize, f32);
+
+/// The state of a playing Playlist, updated by the streaming thread.
+struct PlaylistStream {
+    al_source: u32,
+    queue: Arc<Mutex<Queue>>,
+    level: Arc<Mutex<Level>>,
+    options: LoadOptions,
+    /// The channel count sent to OpenAL, taken from the first track
+    out_channels: i32,
+    /// The sample rate sent to OpenAL, taken from the first track
+    out_rate: i32,
+    /// The type of the samples sent to OpenAL
+    sample_type: SampleType,
+    stream_config: StreamConfig,
+    /// The number of frames of a chunk, once the output format is known
+    chunk_frames: usize,
+    /// The track being decoded
+    current: Option<Track>,
+    /// The track following the current one, opened before it ends
+    next: Option<Track>,
+    /// The track to open first
+    pending: Option<usize>,
+    /// The buffers queued on the source, with the tracks starting in them
+    queued: VecDeque<(u32, Vec<TrackStart>)>,
+    /// The buffers ready to be filled
+    free: Vec<u32>,
+    /// The track heard
+    playing: Option<usize>,
+    event_sender: Sender<PlaylistEvent>
+}
+
+impl PlaylistStream {
+    /**
+     * Open the first playable track from `index`, reporting the tracks
+     * skipped.
+     */
+    fn open_from(&mut self, index: Option<usize>) -> Option<Track> {
+        let mut index = index;
+        let mut attempts = 0;
+        while let Some(track) = index {
+            let path = match self.queue.lock().unwrap().tracks.get(track) {
+                Some(path) => path.clone(),
+                None       => return None
+            };
+            match Track::open(track, &path, &self.options, &mut self.out_channels,
+                              &mut self.out_rate) {
+                Ok(opened) => {
+                    if self.chunk_frames == 0 {
+                        self.chunk_frames = self.stream_config.get_chunk_samples(self.out_rate, 1);
+                    }
+                    return Some(opened);
+                },
+                Err(err) => { self.event_sender.send(PlaylistEvent::TrackError(track, err)); }
+            }
+            // Don't loop forever on a Playlist of broken files
+            attempts += 1;
+            let queue = self.queue.lock().unwrap();
+            if attempts >= queue.tracks.len() {
+                return None;
+            }
+            index = queue.next_after(track, true);
+        }
+        None
+    }
+
+    /// Get the track following one, opened.
+    fn open_next(&mut self, track: usize) -> Option<Track> {
+        let next = self.queue.lock().unwrap().next_after(track, false);
+        self.open_from(next)
+    }
+
+    /**
+     * Decode the next chunk, going on with the next tracks when the current
+     * one ends.
+     *
+     * # Return
+     * The samples and the tracks starting in them, no sample once the last
+     * track ended.
+     */
+    fn decode_chunk(&mut self) -> (Vec<f32>, Vec<TrackStart>) {
+        let mut chunk = Vec::new();
+        let mut starts = Vec::new();
+
+        if let Some(pending) = self.pending.take() {
+            self.current = self.open_from(Some(pending));
+            if let Some(ref track) = self.current {
+                starts.push((0, track.index, track.gain));
+            }
+        }
+
+        loop {
+            let out_channels = self.out_channels as usize;
+            let filled = chunk.len() / cmp::max(out_channels, 1);
+            if self.current.is_none() || filled >= self.chunk_frames {
+                break;
+            }
+
+            let needed = self.chunk_frames - filled;
+            let mut track = self.current.take().unwrap();
+            // Ask for the frames of the file giving the frames needed
+            let in_frames = match track.resampler {
+                Some(ref resampler) => (needed as u64 * resampler.get_from_rate() as u64
+                                        / resampler.get_to_rate() as u64) as usize + 1,
+                None                => needed
+            };
+            chunk.extend(track.decode(in_frames, self.out_channels));
+
+            // Open the next track before the current one ends
+            if self.next.is_none() && track.is_ending(2 * in_frames as u64) {
+                self.next = self.open_next(track.index);
+            }
+
+            if track.at_end {
+                self.current = self.next.take();
+                if let Some(ref next) = self.current {
+                    starts.push((chunk.len() / out_channels, next.index, next.gain));
+                }
+            } else {
+                self.current = Some(track);
+            }
+        }
+        (chunk, starts)
+    }
+
+    /// Fill and queue the free buffers.
+    fn refill(&mut self) -> () {
+        while let Some(buffer) = self.free.pop() {
+            let (chunk, starts) = self.decode_chunk();
+            if chunk.is_empty() {
+                self.free.push(buffer);
+                break;
+            }
+            let samples = Samples::from_f32(self.sample_type, &chunk);
+            if let Err(err) = samples.buffer_data(buffer, chunk.len(), self.out_channels,
+                                                   self.out_rate) {
+                let track = starts.last().map(|&(_, track, _)| track)
+                                  .or(self.playing).unwrap_or(0);
+                self.event_sender.send(PlaylistEvent::TrackError(track, err));
+                self.free.push(buffer);
+                break;
+            }
+            al::alSourceQueueBuffers(self.al_source, 1, &buffer);
+            self.queued.push_back((buffer, starts));
+        }
+    }
+
+    /// Report the track heard if it changed.
+    fn report_track(&mut self) -> () {
+        let mut offset = 0;
+        al::alGetSourcei(self.al_source, ffi::AL_SAMPLE_OFFSET, &mut offset);
+        let heard = match self.queued.front() {
+            Some(&(_, ref starts)) => starts.iter()
+                                            .filter(|&&(frame, _, _)| frame <= offset as usize)
+                                            .last()
+                                            .map(|&(_, track, gain)| (track, gain)),
+            None => None
+        };
+        if let Some((track, gain)) = heard {
+            self.set_playing(track, gain);
+        }
+    }
+
+    /// Apply the ReplayGain of the track heard and report it, if it changed.
+    fn set_playing(&mut self, track: usize, gain: f32) -> () {
+        if Some(track) == self.playing {
+            return;
+        }
+        self.playing = Some(track);
+        self.queue.lock().unwrap().current = Some(track);
+        {
+            let mut level = self.level.lock().unwrap();
+            level.track_gain = gain;
+            level.apply(self.al_source);
+        }
+        self.event_sender.send(PlaylistEvent::TrackChanged(track));
+    }
+}
+
+impl Stream for PlaylistStream {
+    fn update(&mut self) -> bool {
+        let status = al::alGetState(self.al_source);
+        if status == ffi::AL_PAUSED {
+            return true;
+        }
+
+        let mut processed = 0;
+        let mut buffer = 0;
+        al::alGetSourcei(self.al_source, ffi::AL_BUFFERS_PROCESSED, &mut processed);
+        while processed > 0 {
+            al::alSourceUnqueueBuffers(self.al_source, 1, &mut buffer);
+            if let Some((_, starts)) = self.queued.pop_front() {
+                // A track starting at the very end of a buffer
+                if let Some(&(_, track, gain)) = starts.last() {
+                    self.set_playing(track, gain);
+                }
+            }
+            self.free.push(buffer);
+            processed -= 1;
+        }
+        self.refill();
+        self.report_track();
+
+        if status == ffi::AL_STOPPED {
+            if self.queued.is_empty() {
+                self.queue.lock().unwrap().current = None;
+                self.event_sender.send(PlaylistEvent::Ended);
+                return false;
+            }
+            // The buffers ran out before the end: restart
+            al::alSourcePlay(self.al_source);
+        }
+        true
+    }
+
+    fn get_poll_interval(&self) -> Duration {
+        self.stream_config.poll_interval
+    }
+}
+
+impl Drop for PlaylistStream {
+    /// Release the buffers of the source.
+    fn drop(&mut self) -> () {
+        al::alSourceStop(self.al_source);
+        al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use playlist::{Playlist, PlaylistEvent, Repeat, Queue, shuffle_order, conform_channels};
+    use audio_controller::AudioController;
+    use rng::Rng;
+
+    fn queue(len: usize, repeat: Repeat) -> Queue {
+        Queue {
+            tracks: (0..len).map(|i| format!("track{}.ogg", i)).collect(),
+            order: (0..len).collect(),
+            repeat: repeat,
+            current: None
+        }
+    }
+
+    #[test]
+    fn playlist_next_after_OK() -> () {
+        assert_eq!(queue(3, Repeat::Off).next_after(0, false), Some(1));
+        assert_eq!(queue(3, Repeat::Off).next_after(2, false), None);
+        assert_eq!(queue(3, Repeat::All).next_after(2, false), Some(0));
+        assert_eq!(queue(3, Repeat::One).next_after(1, false), Some(1));
+        assert_eq!(queue(3, Repeat::One).next_after(1, true), Some(2));
+    }
+
+    #[test]
+    fn playlist_previous_before_OK() -> () {
+        assert_eq!(queue(3, Repeat::Off).previous_before(2), Some(1));
+        assert_eq!(queue(3, Repeat::Off).previous_before(0), Some(0));
+        assert_eq!(queue(3, Repeat::All).previous_before(0), Some(2));
+    }
+
+    #[test]
+    fn playlist_shuffle_order_OK() -> () {
+        let mut order: Vec<usize> = (0..10).collect();
+        shuffle_order(&mut order, Some(7), &mut Rng::with_seed(42));
+
+        assert_eq!(order[0], 7);
+        let mut sorted = order.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..10).collect::<Vec<usize>>());
+    }
+
+    #[test]
+    fn playlist_conform_channels_OK() -> () {
+        assert_eq!(conform_channels(&[0.2, 0.4], 2, 1), vec![0.3]);
+        assert_eq!(conform_channels(&[0.2], 1, 2), vec![0.2, 0.2]);
+
+        // 5.1 to stereo keeps the centre and the surrounds, not the LFE
+        let mixed = conform_channels(&[0., 0., 1., 1., 0., 0.], 6, 2);
+        assert!(mixed[0] > 0. && (mixed[0] - mixed[1]).abs() < 1e-6);
+        let mixed = conform_channels(&[1., 0., 0., 0., 1., 0.], 6, 2);
+        assert!(mixed[0] > 0. && mixed[1] == 0.);
+        assert_eq!(conform_channels(&[0., 0., 0., 1., 0., 0.], 6, 2), vec![0., 0.]);
+
+        // An unknown layout averages the channels on each output channel
+        assert_eq!(conform_channels(&[0.2, 0.4, 0.6, 0.8, 0.1], 5, 4),
+                   vec![0.15, 0.4, 0.6, 0.8]);
+    }
+
+    #[test]
+    #[ignore]
+    fn playlist_play_OK() -> () {
+        let mut playlist = Playlist::new().unwrap();
+        playlist.add("res/shot.wav");
+        playlist.add("res/shot.wav");
+        playlist.play();
+
+        assert_eq!(playlist.get_current(), Some(0));
+        assert_eq!(playlist.poll_events(), vec![PlaylistEvent::TrackChanged(0)]);
+        playlist.stop();
+    }
+
+    #[test]
+    #[ignore]
+    fn playlist_jump_FAIL() -> () {
+        let mut playlist = Playlist::new().unwrap();
+        playlist.add("res/shot.wav");
+
+        assert!(playlist.jump(1).is_err());
+    }
+}
@@ -0,0 +1,722 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Stream samples generated at runtime through an OpenAL source.
+
+use std::sync::{Arc, Mutex};
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+use states::State;
+use states::State::{Initial, Playing, Paused, Stopped};
+use audio_controller::AudioController;
+use sample::{SampleType, Samples};
+use load_options::LoadOptions;
+use stream_config::StreamConfig;
+use streamer;
+use streamer::{QueueStream, Producer, StreamId};
+
+/// The highest channel count supported by the OpenAL formats.
+const MAX_CHANNELS: i32 = 8;
+
+/**
+ * A source of samples generated at runtime, e.g. a synthesizer or an
+ * emulator.
+ *
+ * Implemented for the closures taking the buffer to fill.
+ */
+pub trait Generator: Send {
+    /**
+     * Fill a buffer with the next interleaved samples, in [-1.0, 1.0].
+     *
+     * # Argument
+     * * `out` - The buffer to fill, a whole number of frames long
+     *
+     * # Return
+     * The number of samples written. Writing fewer samples than the buffer
+     * length ends the ProceduralSound once they are played.
+     */
+    fn fill(&mut self, out: &mut [f32]) -> usize;
+}
+
+impl<F: FnMut(&mut [f32]) -> usize + Send> Generator for F {
+    fn fill(&mut self, out: &mut [f32]) -> usize {
+        self(out)
+    }
+}
+
+/**
+ * Play samples generated at runtime.
+ *
+ * The samples are requested from the Generator chunk after chunk, as a
+ * Music decodes its file, so they are heard after the latency of the queued
+ * chunks, set by the StreamConfig of the load options. A mono
+ * ProceduralSound can be positioned in 3D.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{ProceduralSound, AudioController};
+ *
+ * let mut phase = 0f32;
+ * let mut snd = ProceduralSound::new(move |out: &mut [f32]| {
+ *     for sample in out.iter_mut() {
+ *         *sample = (phase * 2. * std::f32::consts::PI).sin() * 0.25;
+ *         phase = (phase + 440. / 44100.) % 1.;
+ *     }
+ *     out.len()
+ * }, 1, 44100).unwrap();
+ * snd.set_position([2., 0., 0.]);
+ * snd.play();
+ * ```
+ */
+pub struct ProceduralSound {
+    /// The internal OpenAL source identifier
+    al_source: u32,
+    /// The internal OpenAL buffers
+    al_buffers: Vec<u32>,
+    /// The generator, shared with the stream while playing
+    generator: Arc<Mutex<Box<dyn Generator>>>,
+    /// The channel count of the samples generated
+    channels: i32,
+    /// The sample rate of the samples generated
+    sample_rate: i32,
+    /// Type of the samples sent to OpenAL
+    sample_type: SampleType,
+    /// The buffering of the stream
+    stream_config: StreamConfig,
+    /// The stream registered with the streaming thread
+    stream_id: Option<StreamId>
+}
+
+impl ProceduralSound {
+    /**
+     * Create a new ProceduralSound
+     *
+     * # Arguments
+     * * `generator` - The generator of the samples
+     * * `channels` - The channel count of the samples generated
+     * * `sample_rate` - The sample rate of the samples generated
+     *
+     * # Return
+     * A `Result` containing Ok(ProceduralSound) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new<G>(generator: G, channels: i32, sample_rate: i32) -> Result<ProceduralSound, String>
+        where G: Generator + 'static {
+        ProceduralSound::new_with_options(generator, channels, sample_rate, &LoadOptions::default())
+    }
+
+    /**
+     * Create a new ProceduralSound with custom options
+     *
+     * Only the sample type and the StreamConfig of the options are used.
+     *
+     * # Arguments
+     * * `generator` - The generator of the samples
+     * * `channels` - The channel count of the samples generated
+     * * `sample_rate` - The sample rate of the samples generated
+     * * `options` - The options of the stream
+     *
+     * # Return
+     * A `Result` containing Ok(ProceduralSound) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new_with_options<G>(generator: G,
+                               channels: i32,
+                               sample_rate: i32,
+                               options: &LoadOptions) -> Result<ProceduralSound, String>
+        where G: Generator + 'static {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        if channels < 1 || channels > MAX_CHANNELS {
+            return Err(format!("Invalid channel count: {}.", channels));
+        }
+        if sample_rate <= 0 {
+            return Err(format!("Invalid sample rate: {}.", sample_rate));
+        }
+        if let Err(err) = options.stream_config.validate() {
+            return Err(err);
+        }
+        let sample_type = options.sample_type.resolve();
+        if sample_type.get_format(channels).is_none() {
+            return Err("Unrecognized sound format.".into());
+        }
+
+        let mut source_id = 0;
+        let mut buffer_ids = vec![0; options.stream_config.buffer_count];
+        al::alGenSources(1, &mut source_id);
+        al::alGenBuffers(buffer_ids.len() as i32, &mut buffer_ids[0]);
+
+        if let Some(err) = al::openal_has_error() {
+            return Err(format!("Internal OpenAL error: {}", err));
+        };
+
+        Ok(ProceduralSound {
+            al_source: source_id,
+            al_buffers: buffer_ids,
+            generator: Arc::new(Mutex::new(Box::new(generator))),
+            channels: channels,
+            sample_rate: sample_rate,
+            sample_type: sample_type,
+            stream_config: options.stream_config,
+            stream_id: None
+        })
+    }
+
+    /// Get the channel count of the samples generated.
+    pub fn get_channels(&self) -> i32 {
+        self.channels
+    }
+
+    /// Get the sample rate of the samples generated.
+    pub fn get_sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+}
+
+/// Request the chunks of a playing ProceduralSound, called by the streaming
+/// thread.
+struct GeneratorProducer {
+    generator: Arc<Mutex<Box<dyn Generator>>>,
+    /// The buffer the samples are generated in
+    samples: Vec<f32>,
+    channels: i32,
+    sample_rate: i32,
+    sample_type: SampleType,
+    /// True once the generator wrote fewer samples than asked
+    at_end: bool
+}
+
+impl Producer for GeneratorProducer {
+    fn produce(&mut self, buffer: u32) -> bool {
+        if self.at_end {
+            return false;
+        }
+
+        let len = self.samples.len();
+        let written = self.generator.lock().unwrap().fill(&mut self.samples);
+        let written = if written > len { len } else { written };
+        // Upload whole frames only
+        let written = written - written % self.channels as usize;
+        self.at_end = written < len;
+        if written == 0 {
+            return false;
+        }
+
+        Samples::from_f32(self.sample_type, &self.samples[..written]).buffer_data(buffer,
+                                                                                written,
+                                                                                self.channels,
+                                                                                self.sample_rate)
+                                                                   .is_ok()
+    }
+}
+
+impl AudioController for ProceduralSound {
+    /**
+     * Play or resume the ProceduralSound.
+     *
+     * A stopped ProceduralSound goes on with the next samples of its
+     * Generator.
+     */
+    fn play(&mut self) -> () {
+        check_openal_context!(());
+
+        match self.get_state() {
+            Paused => { al::alSourcePlay(self.al_source); return; },
+            _      => {
+                self.stop();
+                let producer = GeneratorProducer {
+                    generator: self.generator.clone(),
+                    samples: vec![0.; self.stream_config.get_chunk_samples(self.sample_rate,
+                                                                           self.channels)],
+                    channels: self.channels,
+                    sample_rate: self.sample_rate,
+                    sample_type: self.sample_type,
+                    at_end: false
+                };
+                if let Some(stream) = QueueStream::start(self.al_source,
+                                                         &self.al_buffers,
+                                                         producer,
+                                                         self.stream_config.poll_interval) {
+                    self.stream_id = Some(streamer::register(Box::new(stream)));
+                }
+            }
+        }
+    }
+
+    /**
+     * Pause the ProceduralSound.
+     */
+    fn pause(&mut self) -> () {
+        check_openal_context!(());
+
+        al::alSourcePause(self.al_source)
+    }
+
+    /**
+     * Stop the ProceduralSound.
+     *
+     * The samples queued and not yet played are dropped.
+     */
+    fn stop(&mut self) -> () {
+        check_openal_context!(());
+
+        if let Some(id) = self.stream_id.take() {
+            streamer::unregister(id);
+        }
+        al::alSourceStop(self.al_source);
+    }
+
+    /**
+     * Check if the ProceduralSound is playing or not.
+     *
+     * # Return
+     * True if the ProceduralSound is playing, false otherwise.
+     */
+    fn is_playing(&self) -> bool {
+        match self.get_state() {
+            Playing     => true,
+            _           => false
+        }
+    }
+
+    /**
+     * Get the current state of the ProceduralSound
+     *
+     * # Return
+     * The state of the procedural sound as a variant of the enum State
+     */
+    fn get_state(&self) -> State {
+        check_openal_context!(Initial);
+
+        let state  = al::alGetState(self.al_source);
+
+        match state {
+            ffi::AL_INITIAL => Initial,
+            ffi::AL_PLAYING => Playing,
+            ffi::AL_PAUSED  => Paused,
+            ffi::AL_STOPPED => Stopped,
+            _               => unreachable!()
+        }
+    }
+
+    /**
+     * Set the volume of the ProceduralSound.
+     *
+     * A value of 1.0 means unattenuated. Each division by 2 equals an attenuation
+     * of about -6dB. Each multiplicaton by 2 equals an amplification of about
+     * +6dB.
+     *
+     * # Argument
+     * * `volume` - The volume of the ProceduralSound, should be between 0. and 1.
+     */
+    fn set_volume(&mut self, volume: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_GAIN, volume);
+    }
+
+    /**
+     * Get the volume of the ProceduralSound.
+     *
+     * # Return
+     * The volume of the ProceduralSound between 0. and 1.
+     */
+    fn get_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut volume : f32 = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_GAIN, &mut volume);
+        volume
+    }
+
+    /**
+     * Set the minimal volume for a ProceduralSound.
+     *
+     * The minimum volume allowed for a procedural sound, after distance and cone
+     * attenation is applied (if applicable).
+     *
+     * # Argument
+     * * `min_volume` - The new minimal volume of the ProceduralSound should be
+     * between 0. and 1.
+     */
+    fn set_min_volume(&mut self, min_volume: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_MIN_GAIN, min_volume);
+    }
+
+    /**
+     * Get the minimal volume of the ProceduralSound.
+     *
+     * # Return
+     * The minimal volume of the ProceduralSound between 0. and 1.
+     */
+    fn get_min_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut volume : f32 = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_MIN_GAIN, &mut volume);
+        volume
+    }
+
+    /**
+     * Set the maximal volume for a ProceduralSound.
+     *
+     * The maximum volume allowed for a ProceduralSound, after distance and cone
+     * attenation is applied (if applicable).
+     *
+     * # Argument
+     * * `max_volume` - The new maximal volume of the ProceduralSound should be
+     * between 0. and 1.
+     */
+    fn set_max_volume(&mut self, max_volume: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_MAX_GAIN, max_volume);
+    }
+
+    /**
+     * Get the maximal volume of the ProceduralSound.
+     *
+     * # Return
+     * The maximal volume of the ProceduralSound between 0. and 1.
+     */
+    fn get_max_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut volume : f32 = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_MAX_GAIN, &mut volume);
+        volume
+    }
+
+    /**
+     * Set the ProceduralSound looping or not
+     *
+     * A ProceduralSound plays as long as its Generator writes samples, it
+     * never loops.
+     *
+     * # Arguments
+     * `looping` - Ignored.
+     */
+    fn set_looping(&mut self, _looping: bool) -> () {}
+
+    /**
+     * Check if the ProceduralSound is looping or not
+     *
+     * # Return
+     * Always false.
+     */
+    fn is_looping(&self) -> bool {
+        false
+    }
+
+    /**
+     * Set the pitch of the ProceduralSound.
+     *
+     * A multiplier for the frequency (sample rate) of the ProceduralSound's buffer.
+     *
+     * Default pitch is 1.0.
+     *
+     * # Argument
+     * * `new_pitch` - The new pitch of the ProceduralSound in the range [0.5 - 2.0]
+     */
+    fn set_pitch(&mut self, pitch: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_PITCH, pitch)
+    }
+
+    /**
+     * Set the pitch of the ProceduralSound.
+     *
+     * # Return
+     * The pitch of the ProceduralSound in the range [0.5 - 2.0]
+     */
+    fn get_pitch(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut pitch = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_PITCH, &mut pitch);
+        pitch
+    }
+
+    /**
+     * Set the position of the ProceduralSound relative to the listener or absolute.
+     *
+     * Default position is absolute.
+     *
+     * # Argument
+     * `relative` - True to set ProceduralSound relative to the listener false to set the
+     * ProceduralSound position absolute.
+     */
+    fn set_relative(&mut self, relative: bool) -> () {
+        check_openal_context!(());
+
+        match relative {
+            true    => al::alSourcei(self.al_source,
+                                     ffi::AL_SOURCE_RELATIVE,
+                                     ffi::ALC_TRUE as i32),
+            false   => al::alSourcei(self.al_source,
+                                     ffi::AL_SOURCE_RELATIVE,
+                                     ffi::ALC_FALSE as i32)
+        };
+    }
+
+    /**
+     * Is the ProceduralSound relative to the listener or not?
+     *
+     * # Return
+     * True if the ProceduralSound is relative to the listener false otherwise
+     */
+    fn is_relative(&mut self) -> bool {
+        check_openal_context!(false);
+
+        let mut boolean = 0;
+        al::alGetSourcei(self.al_source, ffi::AL_SOURCE_RELATIVE, &mut boolean);
+        match boolean as i8 {
+            ffi::ALC_TRUE  => true,
+            ffi::ALC_FALSE => false,
+            _              => unreachable!()
+        }
+    }
+
+    /**
+     * Set the ProceduralSound location in three dimensional space.
+     *
+     * OpenAL, like OpenGL, uses a right handed coordinate system, where in a
+     * frontal default view X (thumb) points right, Y points up (index finger),
+     * and Z points towards the viewer/camera (middle finger).
+     * To switch from a left handed coordinate system, flip the sign on the Z
+     * coordinate.
+     *
+     * Default position is [0., 0., 0.].
+     *
+     * # Argument
+     * * `position` - A three dimensional vector of f32 containing the position
+     * of the listener [x, y, z].
+     */
+    fn set_position(&mut self, position: [f32; 3]) -> () {
+        check_openal_context!(());
+
+        al::alSourcefv(self.al_source, ffi::AL_POSITION, &position[0]);
+    }
+
+    /**
+     * Get the position of the ProceduralSound in three dimensional space.
+     *
+     * # Return
+     * A three dimensional vector of f32 containing the position of the
+     * listener [x, y, z].
+     */
+    fn get_position(&self) -> [f32; 3] {
+        check_openal_context!([0.; 3]);
+
+        let mut position : [f32; 3] = [0.; 3];
+        al::alGetSourcefv(self.al_source, ffi::AL_POSITION, &mut position[0]);
+        position
+    }
+
+    /**
+     * Set the direction of the ProceduralSound.
+     *
+     * Specifies the current direction in local space.
+     *
+     * The default direction is: [0., 0., 0.]
+     *
+     * # Argument
+     * `direction` - The new direction of the ProceduralSound.
+     */
+    fn set_direction(&mut self, direction: [f32; 3]) -> () {
+        check_openal_context!(());
+
+        al::alSourcefv(self.al_source, ffi::AL_DIRECTION, &direction[0]);
+    }
+
+    /**
+     * Get the direction of the ProceduralSound.
+     *
+     * # Return
+     * The current direction of the ProceduralSound.
+     */
+    fn get_direction(&self)  -> [f32; 3] {
+        check_openal_context!([0.; 3]);
+
+        let mut direction : [f32; 3] = [0.; 3];
+        al::alGetSourcefv(self.al_source, ffi::AL_DIRECTION, &mut direction[0]);
+        direction
+    }
+
+    /**
+     * Set the maximum distance of the ProceduralSound.
+     *
+     * The distance above which the source is not attenuated any further with a
+     * clamped distance model, or where attenuation reaches 0.0 gain for linear
+     * distance models with a default rolloff factor.
+     *
+     * The default maximum distance is +inf.
+     *
+     * # Argument
+     * `max_distance` - The new maximum distance in the range [0., +inf]
+     */
+    fn set_max_distance(&mut self, max_distance: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_MAX_DISTANCE, max_distance);
+    }
+
+    /**
+     * Get the maximum distance of the ProceduralSound.
+     *
+     * # Return
+     * The maximum distance of the ProceduralSound in the range [0., +inf]
+     */
+    fn get_max_distance(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut max_distance = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_MAX_DISTANCE, &mut max_distance);
+        max_distance
+    }
+
+    /**
+     * Set the reference distance of the ProceduralSound.
+     *
+     * The distance in units that no attenuation occurs.
+     * At 0.0, no distance attenuation ever occurs on non-linear
+     * attenuation models.
+     *
+     * The default distance reference is 1.
+     *
+     * # Argument
+     * * `ref_distance` - The new reference distance of the ProceduralSound.
+     */
+    fn set_reference_distance(&mut self, ref_distance: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_REFERENCE_DISTANCE, ref_distance);
+    }
+
+    /**
+     * Get the reference distance of the ProceduralSound.
+     *
+     * # Return
+     * The current reference distance of the ProceduralSound.
+     */
+    fn get_reference_distance(&self) -> f32 {
+        check_openal_context!(1.);
+
+        let mut ref_distance = 0.;
+        al::alGetSourcef(self.al_source,
+                         ffi::AL_REFERENCE_DISTANCE,
+                         &mut ref_distance);
+        ref_distance
+    }
+
+    /**
+     * Set the attenuation of a ProceduralSound.
+     *
+     * Multiplier to exaggerate or diminish distance attenuation.
+     * At 0.0, no distance attenuation ever occurs.
+     *
+     * The default attenuation is 1.
+     *
+     * # Arguments
+     * `attenuation` - The new attenuation for the ProceduralSound in the range [0., 1.].
+     */
+    fn set_attenuation(&mut self, attenuation: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_ROLLOFF_FACTOR, attenuation);
+    }
+
+    /**
+     * Get the attenuation of a ProceduralSound.
+     *
+     * # Return
+     * The current attenuation for the ProceduralSound in the range [0., 1.].
+     */
+    fn get_attenuation(&self) -> f32 {
+        check_openal_context!(1.);
+
+        let mut attenuation = 0.;
+        al::alGetSourcef(self.al_source,
+                         ffi::AL_ROLLOFF_FACTOR,
+                         &mut attenuation);
+        attenuation
+    }
+}
+
+impl Drop for ProceduralSound {
+    /// Destroy all the resources of the ProceduralSound.
+    fn drop(&mut self) -> () {
+        if let Some(id) = self.stream_id.take() {
+            streamer::unregister(id);
+        }
+        unsafe {
+            al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+            ffi::alDeleteBuffers(self.al_buffers.len() as i32, &mut self.al_buffers[0]);
+            ffi::alDeleteSources(1, &mut self.al_source);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use procedural::{ProceduralSound, Generator};
+    use states::State::{Playing, Stopped};
+    use audio_controller::AudioController;
+
+    #[test]
+    fn procedural_generator_closure_OK() -> () {
+        let mut generator = |out: &mut [f32]| {
+            for sample in out.iter_mut() {
+                *sample = 0.5;
+            }
+            out.len()
+        };
+        let mut out = [0f32; 4];
+
+        assert_eq!(Generator::fill(&mut generator, &mut out), 4);
+        assert_eq!(out, [0.5; 4]);
+    }
+
+    #[test]
+    #[ignore]
+    fn procedural_play_OK() -> () {
+        let mut snd = ProceduralSound::new(|out: &mut [f32]| out.len(), 1, 44100).unwrap();
+
+        snd.play();
+        assert_eq!(snd.get_state(), Playing);
+        snd.stop();
+        assert_eq!(snd.get_state(), Stopped);
+    }
+
+    #[test]
+    #[ignore]
+    fn procedural_create_FAIL() -> () {
+        assert!(ProceduralSound::new(|out: &mut [f32]| out.len(), 0, 44100).is_err());
+        assert!(ProceduralSound::new(|out: &mut [f32]| out.len(), 2, 0).is_err());
+    }
+}
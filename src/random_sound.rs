@@ -0,0 +1,460 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Play a random variation among several SoundDatas.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use sound::Sound;
+use sound_data::SoundData;
+use states::State;
+use audio_controller::AudioController;
+use rng::Rng;
+
+/**
+ * Play a random SoundData among several ones, with random pitch and volume.
+ *
+ * Each call to play picks one of the SoundDatas according to their weights,
+ * never the one played just before unless there is only one, then applies a
+ * random pitch and volume factor on top of the pitch and volume set on the
+ * RandomSound.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{RandomSound, SoundData, AudioController};
+ * use std::rc::Rc;
+ * use std::cell::RefCell;
+ *
+ * let mut steps = Vec::new();
+ * for path in &["step1.wav", "step2.wav", "step3.wav"] {
+ *     steps.push(Rc::new(RefCell::new(SoundData::new(path).unwrap())));
+ * }
+ * let mut footstep = RandomSound::new(steps).unwrap();
+ * footstep.set_pitch_range(0.9, 1.1);
+ * footstep.set_volume_range(0.8, 1.);
+ * footstep.play();
+ * ```
+ */
+pub struct RandomSound {
+    /// The source playing the variations
+    sound: Sound,
+    /// The variations with their weight
+    datas: Vec<(Rc<RefCell<SoundData>>, f32)>,
+    /// The index of the variation played last
+    last: Option<usize>,
+    /// Can the same variation be played twice in a row
+    allow_repeats: bool,
+    /// The range of the random pitch factor
+    pitch_range: (f32, f32),
+    /// The range of the random volume factor
+    volume_range: (f32, f32),
+    /// The pitch set by the user
+    pitch: f32,
+    /// The volume set by the user
+    volume: f32,
+    /// The generator picking the variations
+    rng: Rng
+}
+
+impl RandomSound {
+    /**
+     * Create a new RandomSound with variations of equal weight.
+     *
+     * # Argument
+     * * `datas` - The SoundDatas to pick from, at least one
+     *
+     * # Return
+     * A `Result` containing Ok(RandomSound) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new(datas: Vec<Rc<RefCell<SoundData>>>) -> Result<RandomSound, String> {
+        RandomSound::new_with_weights(datas.into_iter().map(|data| (data, 1.)).collect())
+    }
+
+    /**
+     * Create a new RandomSound with weighted variations.
+     *
+     * A variation with a weight of 2 is picked twice as often as one with
+     * a weight of 1.
+     *
+     * # Argument
+     * * `datas` - The SoundDatas to pick from with their weight, at least one
+     *
+     * # Return
+     * A `Result` containing Ok(RandomSound) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new_with_weights(datas: Vec<(Rc<RefCell<SoundData>>, f32)>)
+                            -> Result<RandomSound, String> {
+        if datas.is_empty() {
+            return Err("A RandomSound needs at least one SoundData.".into());
+        }
+        if datas.iter().any(|&(_, weight)| !(weight >= 0.)) {
+            return Err("The weights must be positive.".into());
+        }
+
+        let sound = match Sound::new_with_data(datas[0].0.clone()) {
+            Ok(sound) => sound,
+            Err(err) => return Err(err)
+        };
+
+        Ok(RandomSound {
+            sound: sound,
+            datas: datas,
+            last: None,
+            allow_repeats: false,
+            pitch_range: (1., 1.),
+            volume_range: (1., 1.),
+            pitch: 1.,
+            volume: 1.,
+            rng: Rng::new()
+        })
+    }
+
+    /**
+     * Add a variation.
+     *
+     * # Arguments
+     * * `data` - The SoundData to add
+     * * `weight` - The weight of the variation, ignored if negative
+     */
+    pub fn add(&mut self, data: Rc<RefCell<SoundData>>, weight: f32) -> () {
+        if weight >= 0. {
+            self.datas.push((data, weight));
+        }
+    }
+
+    /// Get the number of variations.
+    pub fn len(&self) -> usize {
+        self.datas.len()
+    }
+
+    /// Check if the RandomSound has no variation.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * Set the range of the random pitch factor.
+     *
+     * The factor multiplies the pitch set with set_pitch. The default range
+     * is (1., 1.), without any variation.
+     */
+    pub fn set_pitch_range(&mut self, min: f32, max: f32) -> () {
+        self.pitch_range = (min, max);
+    }
+
+    /// Get the range of the random pitch factor.
+    pub fn get_pitch_range(&self) -> (f32, f32) {
+        self.pitch_range
+    }
+
+    /**
+     * Set the range of the random volume factor.
+     *
+     * The factor multiplies the volume set with set_volume. The default range
+     * is (1., 1.), without any variation.
+     */
+    pub fn set_volume_range(&mut self, min: f32, max: f32) -> () {
+        self.volume_range = (min, max);
+    }
+
+    /// Get the range of the random volume factor.
+    pub fn get_volume_range(&self) -> (f32, f32) {
+        self.volume_range
+    }
+
+    /**
+     * Allow or not the same variation to be played twice in a row.
+     *
+     * Repeats are forbidden by default.
+     */
+    pub fn set_allow_repeats(&mut self, allow_repeats: bool) -> () {
+        self.allow_repeats = allow_repeats;
+    }
+
+    /**
+     * Get the index of the variation played last.
+     *
+     * # Return
+     * The index of the variation in creation order, or None if nothing has
+     * been played yet.
+     */
+    pub fn get_last_index(&self) -> Option<usize> {
+        self.last
+    }
+
+    /// Pick the variation to play next.
+    fn pick(&mut self) -> usize {
+        let exclude = if self.allow_repeats { None } else { self.last };
+        let weights: Vec<f32> = self.datas.iter().map(|&(_, weight)| weight).collect();
+        let choice = self.rng.next_f32();
+        pick_index(&weights, exclude, choice)
+    }
+}
+
+/**
+ * Pick an index according to the weights.
+ *
+ * `choice` is in [0, 1). The excluded index is skipped unless it's the only
+ * variation with a weight, and all the variations get the same chance if no
+ * weight is positive.
+ */
+fn pick_index(weights: &[f32], exclude: Option<usize>, choice: f32) -> usize {
+    let candidates: Vec<usize> = (0..weights.len()).filter(|&i| Some(i) != exclude).collect();
+    let candidates = if candidates.is_empty() { vec![0] } else { candidates };
+
+    let total: f32 = candidates.iter().map(|&i| weights[i]).sum();
+    if total <= 0. {
+        // Only the excluded variation can play: repeat it rather than
+        // picking a variation with no weight
+        if let Some(excluded) = exclude {
+            if weights.get(excluded).map_or(false, |&weight| weight > 0.) {
+                return excluded;
+            }
+        }
+        let index = (choice * candidates.len() as f32) as usize;
+        return candidates[index.min(candidates.len() - 1)];
+    }
+
+    let mut target = choice * total;
+    for &i in candidates.iter() {
+        if target < weights[i] {
+            return i;
+        }
+        target -= weights[i];
+    }
+    // Rounding errors: fall back on the last variation with a weight
+    *candidates.iter().rev().find(|&&i| weights[i] > 0.).unwrap()
+}
+
+impl AudioController for RandomSound {
+    /**
+     * Play a random variation.
+     *
+     * The variation playing is stopped first.
+     */
+    fn play(&mut self) -> () {
+        let index = self.pick();
+        self.sound.stop();
+        self.sound.set_datas(self.datas[index].0.clone());
+        let pitch = self.pitch * self.rng.range(self.pitch_range.0, self.pitch_range.1);
+        let volume = self.volume * self.rng.range(self.volume_range.0, self.volume_range.1);
+        self.sound.set_pitch(pitch);
+        self.sound.set_volume(volume);
+        self.sound.play();
+        self.last = Some(index);
+    }
+
+    /// Pause the variation playing.
+    fn pause(&mut self) -> () {
+        self.sound.pause()
+    }
+
+    /// Stop the variation playing.
+    fn stop(&mut self) -> () {
+        self.sound.stop()
+    }
+
+    /// Check if a variation is playing.
+    fn is_playing(&self) -> bool {
+        self.sound.is_playing()
+    }
+
+    /// Get the state of the RandomSound.
+    fn get_state(&self) -> State {
+        self.sound.get_state()
+    }
+
+    /**
+     * Set the volume of the RandomSound.
+     *
+     * The random volume factor is applied on top of it at each play.
+     */
+    fn set_volume(&mut self, volume: f32) -> () {
+        self.volume = volume;
+    }
+
+    /// Get the volume of the RandomSound, without the random factor.
+    fn get_volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Set the minimal volume of the RandomSound.
+    fn set_min_volume(&mut self, min_volume: f32) -> () {
+        self.sound.set_min_volume(min_volume)
+    }
+
+    /// Get the minimal volume of the RandomSound.
+    fn get_min_volume(&self) -> f32 {
+        self.sound.get_min_volume()
+    }
+
+    /// Set the maximal volume of the RandomSound.
+    fn set_max_volume(&mut self, max_volume: f32) -> () {
+        self.sound.set_max_volume(max_volume)
+    }
+
+    /// Get the maximal volume of the RandomSound.
+    fn get_max_volume(&self) -> f32 {
+        self.sound.get_max_volume()
+    }
+
+    /// Set the RandomSound looping or not, the same variation is repeated.
+    fn set_looping(&mut self, looping: bool) -> () {
+        self.sound.set_looping(looping)
+    }
+
+    /// Check if the RandomSound is looping or not.
+    fn is_looping(&self) -> bool {
+        self.sound.is_looping()
+    }
+
+    /**
+     * Set the pitch of the RandomSound.
+     *
+     * The random pitch factor is applied on top of it at each play.
+     */
+    fn set_pitch(&mut self, pitch: f32) -> () {
+        self.pitch = pitch;
+    }
+
+    /// Get the pitch of the RandomSound, without the random factor.
+    fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Set the position of the RandomSound relative to the listener or absolute.
+    fn set_relative(&mut self, relative: bool) -> () {
+        self.sound.set_relative(relative)
+    }
+
+    /// Is the RandomSound relative to the listener or not?
+    fn is_relative(&mut self) -> bool {
+        self.sound.is_relative()
+    }
+
+    /// Set the RandomSound location in three dimensional space.
+    fn set_position(&mut self, position: [f32; 3]) -> () {
+        self.sound.set_position(position)
+    }
+
+    /// Get the position of the RandomSound in three dimensional space.
+    fn get_position(&self) -> [f32; 3] {
+        self.sound.get_position()
+    }
+
+    /// Set the direction of the RandomSound.
+    fn set_direction(&mut self, direction: [f32; 3]) -> () {
+        self.sound.set_direction(direction)
+    }
+
+    /// Get the direction of the RandomSound.
+    fn get_direction(&self) -> [f32; 3] {
+        self.sound.get_direction()
+    }
+
+    /// Set the maximum distance of the RandomSound.
+    fn set_max_distance(&mut self, max_distance: f32) -> () {
+        self.sound.set_max_distance(max_distance)
+    }
+
+    /// Get the maximum distance of the RandomSound.
+    fn get_max_distance(&self) -> f32 {
+        self.sound.get_max_distance()
+    }
+
+    /// Set the reference distance of the RandomSound.
+    fn set_reference_distance(&mut self, ref_distance: f32) -> () {
+        self.sound.set_reference_distance(ref_distance)
+    }
+
+    /// Get the reference distance of the RandomSound.
+    fn get_reference_distance(&self) -> f32 {
+        self.sound.get_reference_distance()
+    }
+
+    /// Set the attenuation of the RandomSound.
+    fn set_attenuation(&mut self, attenuation: f32) -> () {
+        self.sound.set_attenuation(attenuation)
+    }
+
+    /// Get the attenuation of the RandomSound.
+    fn get_attenuation(&self) -> f32 {
+        self.sound.get_attenuation()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use random_sound::{RandomSound, pick_index};
+    use sound_data::SoundData;
+    use audio_controller::AudioController;
+
+    #[test]
+    fn randomsound_pick_WEIGHTS() -> () {
+        let weights = [1., 0., 3.];
+
+        assert_eq!(pick_index(&weights, None, 0.), 0);
+        assert_eq!(pick_index(&weights, None, 0.24), 0);
+        assert_eq!(pick_index(&weights, None, 0.26), 2);
+        assert_eq!(pick_index(&weights, None, 0.99), 2);
+    }
+
+    #[test]
+    fn randomsound_pick_NO_REPEAT() -> () {
+        let weights = [1., 1., 1.];
+
+        for i in 0..100 {
+            assert!(pick_index(&weights, Some(1), i as f32 / 100.) != 1);
+        }
+        assert_eq!(pick_index(&[1.], Some(0), 0.5), 0);
+        assert_eq!(pick_index(&[1., 0.], Some(0), 0.5), 0);
+        assert_eq!(pick_index(&[0., 0.], Some(0), 0.9), 1);
+    }
+
+    #[test]
+    #[ignore]
+    fn randomsound_play_OK() -> () {
+        let data = Rc::new(RefCell::new(SoundData::new("res/shot.wav").unwrap()));
+        let mut snd = RandomSound::new(vec![data.clone(), data]).unwrap();
+        snd.set_pitch_range(0.9, 1.1);
+
+        snd.play();
+        let first = snd.get_last_index();
+        snd.play();
+        assert!(snd.get_last_index() != first);
+    }
+
+    #[test]
+    #[ignore]
+    #[should_panic]
+    fn randomsound_create_FAIL() -> () {
+        #![allow(unused_variables)]
+        let snd = RandomSound::new(Vec::new()).unwrap();
+    }
+}
@@ -0,0 +1,376 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Convert the sample rate of the samples, and choose the OpenAL resampler.
+
+use std::cmp;
+use std::f64::consts::PI;
+use std::ffi::CStr;
+use std::mem;
+use libc::c_char;
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+
+/// Number of zero crossings of the sinc on each side of the kernel.
+const ZERO_CROSSINGS: usize = 16;
+/// Number of kernel values stored between two input samples.
+const TABLE_RESOLUTION: usize = 256;
+
+/**
+ * A windowed sinc resampler converting interleaved float samples.
+ *
+ * The Resampler keeps the end of the previous samples between calls to
+ * process, so a stream can be converted chunk by chunk without clicks.
+ */
+#[derive(Clone, Debug)]
+pub struct Resampler {
+    channels: usize,
+    /// Input frames consumed for one output frame
+    step: f64,
+    /// Half width of the kernel, in input frames
+    half_width: usize,
+    /// The kernel sampled every 1 / TABLE_RESOLUTION input frame
+    table: Vec<f32>,
+    /// The input frames still needed by the kernel
+    buffer: Vec<f32>,
+    /// The position of the next output frame in the buffer, in frames
+    position: f64,
+    /// The number of input frames received since the start
+    frames_in: u64,
+    /// The number of output frames produced since the start
+    frames_out: u64,
+    from_rate: i32,
+    to_rate: i32
+}
+
+impl Resampler {
+    /**
+     * Create a new Resampler.
+     *
+     * # Arguments
+     * * `from_rate` - The sample rate of the input samples
+     * * `to_rate` - The sample rate of the output samples
+     * * `channels` - The number of interleaved channels
+     */
+    pub fn new(from_rate: i32, to_rate: i32, channels: i32) -> Resampler {
+        let from_rate = if from_rate < 1 { 1 } else { from_rate };
+        let to_rate = if to_rate < 1 { 1 } else { to_rate };
+        let step = from_rate as f64 / to_rate as f64;
+        // Lower the cutoff frequency below the output Nyquist when downsampling
+        let cutoff = if step > 1. { 1. / step } else { 1. };
+        let half_width = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+
+        let table = (0..half_width * TABLE_RESOLUTION + 2).map(|i| {
+            let distance = i as f64 / TABLE_RESOLUTION as f64;
+            (cutoff * sinc(distance * cutoff) * blackman(distance / half_width as f64)) as f32
+        }).collect();
+
+        let mut resampler = Resampler {
+            channels: if channels < 1 { 1 } else { channels as usize },
+            step: step,
+            half_width: half_width,
+            table: table,
+            buffer: Vec::new(),
+            position: 0.,
+            frames_in: 0,
+            frames_out: 0,
+            from_rate: from_rate,
+            to_rate: to_rate
+        };
+        resampler.reset();
+        resampler
+    }
+
+    /// Forget the previous samples, to start converting another stream.
+    pub fn reset(&mut self) -> () {
+        // The frames before the start are silent
+        self.buffer = vec![0.; self.half_width * self.channels];
+        self.position = self.half_width as f64;
+        self.frames_in = 0;
+        self.frames_out = 0;
+    }
+
+    /// Get the sample rate of the input samples.
+    pub fn get_from_rate(&self) -> i32 {
+        self.from_rate
+    }
+
+    /// Get the sample rate of the output samples.
+    pub fn get_to_rate(&self) -> i32 {
+        self.to_rate
+    }
+
+    /**
+     * Convert the next samples of the stream.
+     *
+     * The output is delayed by the kernel width: call flush at the end of
+     * the stream to get the last samples.
+     *
+     * # Argument
+     * * `samples` - Interleaved samples, made of whole frames
+     *
+     * # Return
+     * The converted interleaved samples available so far.
+     */
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.frames_in += (samples.len() / self.channels) as u64;
+        self.buffer.extend_from_slice(samples);
+        self.produce(None)
+    }
+
+    /**
+     * Convert the samples still held by the Resampler at the end of the
+     * stream, then reset it.
+     *
+     * # Return
+     * The last converted interleaved samples.
+     */
+    pub fn flush(&mut self) -> Vec<f32> {
+        let expected = (self.frames_in as f64 / self.step).ceil() as u64;
+        let padding = vec![0.; (self.half_width + 1) * self.channels];
+        self.buffer.extend_from_slice(&padding);
+        let out = self.produce(Some(expected));
+        self.reset();
+        out
+    }
+
+    /// Produce the output frames whose kernel is covered by the buffer.
+    fn produce(&mut self, limit: Option<u64>) -> Vec<f32> {
+        let channels = self.channels;
+        let frames = self.buffer.len() / channels;
+        let mut out = Vec::new();
+
+        while (self.position.floor() as usize) + self.half_width < frames {
+            if let Some(limit) = limit {
+                if self.frames_out >= limit {
+                    break;
+                }
+            }
+            let center = self.position.floor() as usize;
+            let first = center + 1 - self.half_width;
+            let last = center + self.half_width;
+
+            let start = out.len();
+            out.resize(start + channels, 0.);
+            for k in first..last + 1 {
+                let distance = (self.position - k as f64).abs();
+                let weight = self.kernel(distance);
+                let frame = &self.buffer[k * channels..(k + 1) * channels];
+                for (o, s) in out[start..].iter_mut().zip(frame.iter()) {
+                    *o += weight * s;
+                }
+            }
+
+            self.position += self.step;
+            self.frames_out += 1;
+        }
+
+        // Drop the frames the kernel won't reach anymore
+        let center = self.position.floor() as usize;
+        let keep_from = cmp::min(center + 1 - self.half_width, frames);
+        if keep_from > 0 {
+            self.buffer.drain(..keep_from * channels);
+            self.position -= keep_from as f64;
+        }
+        out
+    }
+
+    /// Interpolate the kernel at a distance in input frames.
+    fn kernel(&self, distance: f64) -> f32 {
+        let index = distance * TABLE_RESOLUTION as f64;
+        let i = index as usize;
+        if i + 1 >= self.table.len() {
+            return 0.;
+        }
+        let t = (index - i as f64) as f32;
+        self.table[i] * (1. - t) + self.table[i + 1] * t
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0. { 1. } else { (PI * x).sin() / (PI * x) }
+}
+
+/// Blackman window over [-1, 1].
+fn blackman(x: f64) -> f64 {
+    if x.abs() >= 1. {
+        return 0.;
+    }
+    let phase = PI * (x + 1.);
+    0.42 - 0.5 * phase.cos() + 0.08 * (2. * phase).cos()
+}
+
+/**
+ * Convert the sample rate of a whole sound.
+ *
+ * # Arguments
+ * * `samples` - The interleaved samples
+ * * `channels` - The number of channels
+ * * `from_rate` - The sample rate of the samples
+ * * `to_rate` - The sample rate to convert to
+ *
+ * # Return
+ * The converted interleaved samples.
+ */
+pub fn resample(samples: &[f32], channels: i32, from_rate: i32, to_rate: i32) -> Vec<f32> {
+    if from_rate == to_rate {
+        return samples.to_vec();
+    }
+    let mut resampler = Resampler::new(from_rate, to_rate, channels);
+    let mut out = resampler.process(samples);
+    out.extend(resampler.flush());
+    out
+}
+
+/**
+ * Get the resamplers OpenAL can use on a source.
+ *
+ * Requires the AL_SOFT_source_resampler extension, the index of a name in
+ * the list is the value given to `set_resampler`.
+ *
+ * # Return
+ * The names of the resamplers, empty if the extension is missing.
+ */
+pub fn get_resamplers() -> Vec<String> {
+    check_openal_context!(Vec::new());
+
+    if !al::is_extension_present("AL_SOFT_source_resampler") {
+        return Vec::new();
+    }
+    let proc_name = b"alGetStringiSOFT\0";
+    let address = unsafe { ffi::alGetProcAddress(proc_name.as_ptr() as *const c_char) };
+    if address.is_null() {
+        return Vec::new();
+    }
+    let get_stringi: extern "C" fn(i32, i32) -> *const c_char = unsafe {
+        mem::transmute(address)
+    };
+
+    let count = unsafe { ffi::alGetInteger(ffi::AL_NUM_RESAMPLERS_SOFT) };
+    (0..count).map(|i| {
+        let name = get_stringi(ffi::AL_RESAMPLER_NAME_SOFT, i);
+        if name.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(name).to_string_lossy().into_owned() }
+        }
+    }).collect()
+}
+
+/**
+ * Get the index of the resampler used by default on the sources.
+ *
+ * # Return
+ * The index of the default resampler, or None if the AL_SOFT_source_resampler
+ * extension is missing.
+ */
+pub fn get_default_resampler() -> Option<usize> {
+    check_openal_context!(None);
+
+    if !al::is_extension_present("AL_SOFT_source_resampler") {
+        return None;
+    }
+    Some(unsafe { ffi::alGetInteger(ffi::AL_DEFAULT_RESAMPLER_SOFT) } as usize)
+}
+
+/// Select the resampler of an OpenAL source.
+#[doc(hidden)]
+pub fn set_source_resampler(source: u32, resampler: usize) -> Result<(), String> {
+    if !al::is_extension_present("AL_SOFT_source_resampler") {
+        return Err("Resamplers are not supported by the OpenAL implementation.".into());
+    }
+    let count = unsafe { ffi::alGetInteger(ffi::AL_NUM_RESAMPLERS_SOFT) };
+    if resampler >= count as usize {
+        return Err(format!("Unknown resampler: {}", resampler));
+    }
+
+    al::alSourcei(source, ffi::AL_SOURCE_RESAMPLER_SOFT, resampler as i32);
+    match al::openal_has_error() {
+        Some(err) => Err(format!("Internal OpenAL error: {}", err)),
+        None => Ok(())
+    }
+}
+
+/// Get the resampler of an OpenAL source.
+#[doc(hidden)]
+pub fn get_source_resampler(source: u32) -> Option<usize> {
+    if !al::is_extension_present("AL_SOFT_source_resampler") {
+        return None;
+    }
+    let mut resampler = 0;
+    al::alGetSourcei(source, ffi::AL_SOURCE_RESAMPLER_SOFT, &mut resampler);
+    Some(resampler as usize)
+}
+
+#[cfg(test)]
+mod test {
+    use resampler::{Resampler, resample};
+
+    #[test]
+    fn resampler_length() -> () {
+        let samples = vec![0.; 44100 * 2];
+
+        assert_eq!(resample(&samples, 2, 44100, 48000).len(), 48000 * 2);
+        assert_eq!(resample(&samples, 1, 44100, 22050).len(), 44100);
+        assert_eq!(resample(&samples, 2, 44100, 44100).len(), 44100 * 2);
+    }
+
+    #[test]
+    fn resampler_dc() -> () {
+        // A constant signal keeps its level once the kernel is full
+        let out = resample(&vec![0.5; 4800], 1, 48000, 44100);
+        for s in &out[100..out.len() - 100] {
+            assert!((s - 0.5).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn resampler_sine() -> () {
+        let sine: Vec<f32> = (0..22050).map(|i| {
+            (i as f32 * 440. * 2. * ::std::f32::consts::PI / 22050.).sin()
+        }).collect();
+        let out = resample(&sine, 1, 22050, 44100);
+        for (i, s) in out.iter().enumerate().skip(200).take(40000) {
+            let expected = (i as f32 * 440. * 2. * ::std::f32::consts::PI / 44100.).sin();
+            assert!((s - expected).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn resampler_chunks() -> () {
+        let sine: Vec<f32> = (0..10000).map(|i| (i as f32 * 0.01).sin()).collect();
+        let whole = resample(&sine, 1, 32000, 44100);
+
+        let mut resampler = Resampler::new(32000, 44100, 1);
+        let mut chunked = Vec::new();
+        for chunk in sine.chunks(777) {
+            chunked.extend(resampler.process(chunk));
+        }
+        chunked.extend(resampler.flush());
+
+        assert_eq!(whole.len(), chunked.len());
+        for (a, b) in whole.iter().zip(chunked.iter()) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! A small pseudo random number generator for the sound variations.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Make the generators created at the same instant differ.
+static SEED_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A xorshift64* generator, good enough to vary sounds, not for cryptography.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    /// Create a generator seeded from the clock.
+    pub fn new() -> Rng {
+        let nanos = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() ^ ((duration.subsec_nanos() as u64) << 32),
+            Err(_) => 0
+        };
+        let counter = SEED_COUNTER.fetch_add(1, Ordering::Relaxed) as u64;
+        Rng::with_seed(nanos ^ counter.wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+
+    /// Create a generator with a fixed seed, to reproduce a sequence.
+    pub fn with_seed(seed: u64) -> Rng {
+        // The state of a xorshift must never be zero
+        Rng { state: if seed == 0 { 0x2545_F491_4F6C_DD1D } else { seed } }
+    }
+
+    /// Get the next random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Get a random number in [0, 1).
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Get a random number in [min, max], or min if the range is empty.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        if max <= min {
+            min
+        } else {
+            min + (max - min) * self.next_f32()
+        }
+    }
+
+    /// Get a random index in [0, len).
+    pub fn index(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rng::Rng;
+
+    #[test]
+    fn rng_range() -> () {
+        let mut rng = Rng::with_seed(42);
+        for _ in 0..1000 {
+            let value = rng.range(0.5, 2.);
+            assert!(value >= 0.5 && value <= 2.);
+        }
+        assert_eq!(rng.range(1., 1.), 1.);
+    }
+
+    #[test]
+    fn rng_seed() -> () {
+        let mut a = Rng::with_seed(7);
+        let mut b = Rng::with_seed(7);
+        for _ in 0..10 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+}
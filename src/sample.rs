@@ -0,0 +1,351 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! The sample types which can be uploaded in an OpenAL buffer.
+
+use std::mem;
+use libc::c_void;
+
+use openal::al;
+use sndfile::SndFile;
+
+/**
+ * The type of the samples stored in the OpenAL buffers.
+ *
+ * * UInt8 - Unsigned 8 bit samples, half the memory of Int16
+ * * Int16 - Signed 16 bit samples, supported everywhere (default)
+ * * Float32 - 32 bit float samples, keep the full precision of float and
+ * 24 bit sources and don't clip hot masters. Needs the AL_EXT_FLOAT32
+ * extension, Int16 is used instead if it's missing.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SampleType {
+    UInt8,
+    Int16,
+    Float32
+}
+
+impl Default for SampleType {
+    fn default() -> SampleType {
+        SampleType::Int16
+    }
+}
+
+impl SampleType {
+    /// Get the sample type supported by the current OpenAL implementation.
+    #[doc(hidden)]
+    pub fn resolve(self) -> SampleType {
+        match self {
+            SampleType::Float32 if !has_float32() => SampleType::Int16,
+            sample_type                           => sample_type
+        }
+    }
+
+    /// Get the size in bytes of one sample of this type.
+    pub fn get_size(self) -> usize {
+        match self {
+            SampleType::UInt8   => 1,
+            SampleType::Int16   => 2,
+            SampleType::Float32 => 4
+        }
+    }
+
+    /// Get the OpenAL buffer format for this sample type.
+    #[doc(hidden)]
+    pub fn get_format(self, channels: i32) -> Option<i32> {
+        match self {
+            SampleType::UInt8   => al::get_channels_format8(channels),
+            SampleType::Int16   => al::get_channels_format(channels),
+            SampleType::Float32 => al::get_channels_format_float32(channels)
+        }
+    }
+}
+
+fn has_float32() -> bool {
+    al::is_extension_present("AL_EXT_FLOAT32")
+}
+
+/**
+ * A type of sample which can be stored in a SoundData.
+ *
+ * Implemented for signed 16 bit integers (`i16`), 32 bit floats in the
+ * range [-1.0, 1.0] (`f32`) and unsigned 8 bit integers centered on 128
+ * (`u8`).
+ */
+pub trait Sample: Copy {
+    /// Upload the interleaved samples in an OpenAL buffer.
+    #[doc(hidden)]
+    fn buffer_data(buffer: u32,
+                   samples: &[Self],
+                   channels: i32,
+                   sample_rate: i32) -> Result<(), String>;
+}
+
+impl Sample for i16 {
+    fn buffer_data(buffer: u32,
+                   samples: &[i16],
+                   channels: i32,
+                   sample_rate: i32) -> Result<(), String> {
+        buffer_data(buffer,
+                    al::get_channels_format(channels),
+                    samples,
+                    sample_rate)
+    }
+}
+
+impl Sample for u8 {
+    fn buffer_data(buffer: u32,
+                   samples: &[u8],
+                   channels: i32,
+                   sample_rate: i32) -> Result<(), String> {
+        buffer_data(buffer,
+                    al::get_channels_format8(channels),
+                    samples,
+                    sample_rate)
+    }
+}
+
+impl Sample for f32 {
+    fn buffer_data(buffer: u32,
+                   samples: &[f32],
+                   channels: i32,
+                   sample_rate: i32) -> Result<(), String> {
+        if has_float32() {
+            buffer_data(buffer,
+                        al::get_channels_format_float32(channels),
+                        samples,
+                        sample_rate)
+        } else {
+            let samples: Vec<i16> = samples.iter().map(|s| f32_to_i16(*s)).collect();
+            i16::buffer_data(buffer, &samples, channels, sample_rate)
+        }
+    }
+}
+
+/// Samples decoded from a file, stored with the type of the OpenAL buffer.
+#[doc(hidden)]
+pub enum Samples {
+    UInt8(Vec<u8>),
+    Int16(Vec<i16>),
+    Float32(Vec<f32>)
+}
+
+impl Samples {
+    /// Allocate `len` samples of the given type.
+    pub fn new(sample_type: SampleType, len: usize) -> Samples {
+        match sample_type {
+            SampleType::UInt8   => Samples::UInt8(vec![0u8; len]),
+            SampleType::Int16   => Samples::Int16(vec![0i16; len]),
+            SampleType::Float32 => Samples::Float32(vec![0f32; len])
+        }
+    }
+
+    /// Get the number of samples which fits in the buffer.
+    pub fn len(&self) -> usize {
+        match *self {
+            Samples::UInt8(ref samples)   => samples.len(),
+            Samples::Int16(ref samples)   => samples.len(),
+            Samples::Float32(ref samples) => samples.len()
+        }
+    }
+
+    /// Convert float samples in [-1.0, 1.0] to the given type.
+    pub fn from_f32(sample_type: SampleType, samples: &[f32]) -> Samples {
+        match sample_type {
+            SampleType::UInt8   =>
+                Samples::UInt8(samples.iter().map(|s| i16_to_u8(f32_to_i16(*s))).collect()),
+            SampleType::Int16   => Samples::Int16(samples.iter().map(|s| f32_to_i16(*s)).collect()),
+            SampleType::Float32 => Samples::Float32(samples.to_vec())
+        }
+    }
+
+    /// Grow or shorten the buffer to `len` samples.
+    pub fn resize(&mut self, len: usize) -> () {
+        match *self {
+            Samples::UInt8(ref mut samples)   => samples.resize(len, 128),
+            Samples::Int16(ref mut samples)   => samples.resize(len, 0),
+            Samples::Float32(ref mut samples) => samples.resize(len, 0.)
+        }
+    }
+
+    /// Shorten the buffer to `len` samples.
+    pub fn truncate(&mut self, len: usize) -> () {
+        match *self {
+            Samples::UInt8(ref mut samples)   => samples.truncate(len),
+            Samples::Int16(ref mut samples)   => samples.truncate(len),
+            Samples::Float32(ref mut samples) => samples.truncate(len)
+        }
+    }
+
+    /// Convert the samples to floats in [-1.0, 1.0].
+    pub fn to_f32(&self) -> Vec<f32> {
+        match *self {
+            Samples::UInt8(ref samples)   => samples.iter().map(|s| u8_to_f32(*s)).collect(),
+            Samples::Int16(ref samples)   => samples.iter().map(|s| i16_to_f32(*s)).collect(),
+            Samples::Float32(ref samples) => samples.clone()
+        }
+    }
+
+    /**
+     * Fill the buffer with samples read from the file.
+     *
+     * Return the number of samples read, less than the buffer length at the
+     * end of the file.
+     */
+    pub fn read(&mut self, file: &mut SndFile) -> usize {
+        let len = self.len();
+        self.read_part(file, 0, len)
+    }
+
+    /**
+     * Fill the samples between `start` and `end` with samples read from the
+     * file.
+     *
+     * Return the number of samples read, less than `end - start` at the end
+     * of the file.
+     */
+    pub fn read_part(&mut self, file: &mut SndFile, start: usize, end: usize) -> usize {
+        let len = (end - start) as i64;
+        let read = match *self {
+            Samples::UInt8(ref mut samples) => {
+                let mut tmp = vec![0i16; end - start];
+                let read = file.read_i16(&mut tmp[..], len);
+                for (dst, src) in samples[start..end].iter_mut().zip(tmp.iter()) {
+                    *dst = i16_to_u8(*src);
+                }
+                read
+            },
+            Samples::Int16(ref mut samples)   => file.read_i16(&mut samples[start..end], len),
+            Samples::Float32(ref mut samples) => file.read_f32(&mut samples[start..end], len)
+        };
+        if read < 0 { 0 } else { read as usize }
+    }
+
+    /// Upload the `count` first samples in an OpenAL buffer.
+    pub fn buffer_data(&self,
+                       buffer: u32,
+                       count: usize,
+                       channels: i32,
+                       sample_rate: i32) -> Result<(), String> {
+        self.buffer_data_range(buffer, 0, count, channels, sample_rate)
+    }
+
+    /// Upload the samples between `start` and `end` in an OpenAL buffer.
+    pub fn buffer_data_range(&self,
+                             buffer: u32,
+                             start: usize,
+                             end: usize,
+                             channels: i32,
+                             sample_rate: i32) -> Result<(), String> {
+        match *self {
+            Samples::UInt8(ref samples) =>
+                u8::buffer_data(buffer, &samples[start..end], channels, sample_rate),
+            Samples::Int16(ref samples) =>
+                i16::buffer_data(buffer, &samples[start..end], channels, sample_rate),
+            Samples::Float32(ref samples) =>
+                f32::buffer_data(buffer, &samples[start..end], channels, sample_rate)
+        }
+    }
+}
+
+/// Convert a 16 bit sample to an unsigned 8 bit one.
+pub fn i16_to_u8(sample: i16) -> u8 {
+    ((sample >> 8) + 128) as u8
+}
+
+/// Convert an unsigned 8 bit sample to a float one.
+pub fn u8_to_f32(sample: u8) -> f32 {
+    (sample as f32 - 128.) / 128.
+}
+
+/// Convert a 16 bit sample to a float one.
+pub fn i16_to_f32(sample: i16) -> f32 {
+    sample as f32 / 32768.
+}
+
+/// Convert a float sample to a 16 bit one, clipping it to [-1.0, 1.0].
+pub fn f32_to_i16(sample: f32) -> i16 {
+    let sample = if sample > 1. { 1. } else if sample < -1. { -1. } else { sample };
+    (sample * 32767.) as i16
+}
+
+fn buffer_data<T>(buffer: u32,
+                  format: Option<i32>,
+                  samples: &[T],
+                  sample_rate: i32) -> Result<(), String> {
+    let format = match format {
+        Some(fmt) => fmt,
+        None => {
+            return Err("Unrecognized sound format.".into());
+        }
+    };
+
+    // alBufferData takes the size as an i32
+    let len = mem::size_of::<T>() * samples.len();
+    if len > i32::MAX as usize {
+        return Err("The samples are too big for an OpenAL buffer.".into());
+    }
+    al::alBufferData(buffer,
+                     format,
+                     samples.as_ptr() as *mut c_void,
+                     len as i32,
+                     sample_rate);
+
+    match al::openal_has_error() {
+        Some(err) => Err(format!("Internal OpenAL error: {}", err)),
+        None      => Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use sample::{f32_to_i16, i16_to_u8, u8_to_f32, i16_to_f32};
+
+    #[test]
+    fn sample_f32_to_i16_OK() -> () {
+        assert_eq!(f32_to_i16(0.), 0);
+        assert_eq!(f32_to_i16(1.), 32767);
+        assert_eq!(f32_to_i16(-1.), -32767);
+    }
+
+    #[test]
+    fn sample_f32_to_i16_CLIP() -> () {
+        assert_eq!(f32_to_i16(2.), 32767);
+        assert_eq!(f32_to_i16(-3.5), -32767);
+    }
+
+    #[test]
+    fn sample_i16_to_u8_OK() -> () {
+        assert_eq!(i16_to_u8(0), 128);
+        assert_eq!(i16_to_u8(32767), 255);
+        assert_eq!(i16_to_u8(-32768), 0);
+    }
+
+    #[test]
+    fn sample_to_f32_OK() -> () {
+        assert_eq!(u8_to_f32(128), 0.);
+        assert_eq!(u8_to_f32(0), -1.);
+        assert_eq!(i16_to_f32(-32768), -1.);
+        assert_eq!(i16_to_f32(16384), 0.5);
+    }
+}
@@ -0,0 +1,228 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Decoded samples and the analysis helpers used to check or draw them.
+
+/// Samples with an absolute value above this are considered clipped.
+const CLIP_THRESHOLD: f32 = 0.999;
+
+/**
+ * Interleaved samples of a sound, converted to floats in [-1.0, 1.0].
+ *
+ * # Example
+ * ```no_run
+ * use ears::{SoundData, LoadOptions};
+ *
+ * let mut options = LoadOptions::default();
+ * options.keep_samples = true;
+ * let snd_data = SoundData::new_with_options("path/to/my/sound.wav", &options).unwrap();
+ *
+ * let samples = snd_data.get_sample_buffer().unwrap();
+ * println!("peak: {}, rms: {}", samples.peak(), samples.rms());
+ * for (min, max) in samples.overview(800) {
+ *     // draw a vertical line from min to max
+ * }
+ * ```
+ */
+#[derive(Clone, Debug, PartialEq)]
+pub struct SampleBuffer {
+    samples: Vec<f32>,
+    channels: i32,
+    sample_rate: i32
+}
+
+impl SampleBuffer {
+    /**
+     * Create a SampleBuffer from interleaved samples.
+     *
+     * # Arguments
+     * * `samples` - The interleaved samples
+     * * `channels` - The number of channels, at least one
+     * * `sample_rate` - The sample rate of the samples
+     */
+    pub fn new(samples: Vec<f32>, channels: i32, sample_rate: i32) -> SampleBuffer {
+        SampleBuffer {
+            samples: samples,
+            channels: if channels < 1 { 1 } else { channels },
+            sample_rate: sample_rate
+        }
+    }
+
+    /// Get the interleaved samples.
+    pub fn get_samples(&self) -> &[f32] {
+        &self.samples
+    }
+
+    /// Get the number of channels.
+    pub fn get_channels(&self) -> i32 {
+        self.channels
+    }
+
+    /// Get the sample rate.
+    pub fn get_sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    /// Get the number of frames, a frame holding one sample per channel.
+    pub fn get_frames(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /**
+     * Get the samples of one channel.
+     *
+     * # Argument
+     * * `channel` - The index of the channel
+     *
+     * # Return
+     * The samples of the channel, or an empty vector if there is no such
+     * channel.
+     */
+    pub fn get_channel(&self, channel: i32) -> Vec<f32> {
+        if channel < 0 || channel >= self.channels {
+            return Vec::new();
+        }
+        self.samples.iter()
+            .skip(channel as usize)
+            .step_by(self.channels as usize)
+            .cloned()
+            .collect()
+    }
+
+    /// Get the highest absolute sample value of all the channels.
+    pub fn peak(&self) -> f32 {
+        peak(self.samples.iter())
+    }
+
+    /// Get the highest absolute sample value of a channel.
+    pub fn channel_peak(&self, channel: i32) -> f32 {
+        peak(self.get_channel(channel).iter())
+    }
+
+    /// Get the root mean square level of all the channels.
+    pub fn rms(&self) -> f32 {
+        rms(self.samples.iter())
+    }
+
+    /// Get the root mean square level of a channel.
+    pub fn channel_rms(&self, channel: i32) -> f32 {
+        rms(self.get_channel(channel).iter())
+    }
+
+    /**
+     * Count the clipped samples, whose absolute value reaches full scale.
+     */
+    pub fn clipped_samples(&self) -> usize {
+        self.samples.iter().filter(|s| s.abs() >= CLIP_THRESHOLD).count()
+    }
+
+    /**
+     * Get a min/max overview of all the channels, to draw a waveform.
+     *
+     * # Argument
+     * * `buckets` - The number of points of the overview, e.g. its width in
+     * pixels
+     *
+     * # Return
+     * The minimum and maximum sample of each bucket of frames, in order.
+     */
+    pub fn overview(&self, buckets: usize) -> Vec<(f32, f32)> {
+        overview(&self.samples, self.channels as usize, buckets)
+    }
+
+    /**
+     * Get a min/max overview of a channel, to draw a waveform.
+     *
+     * # Arguments
+     * * `channel` - The index of the channel
+     * * `buckets` - The number of points of the overview
+     */
+    pub fn channel_overview(&self, channel: i32, buckets: usize) -> Vec<(f32, f32)> {
+        overview(&self.get_channel(channel), 1, buckets)
+    }
+}
+
+fn peak<'r, I: Iterator<Item = &'r f32>>(samples: I) -> f32 {
+    samples.fold(0., |peak: f32, s| peak.max(s.abs()))
+}
+
+fn rms<'r, I: Iterator<Item = &'r f32>>(samples: I) -> f32 {
+    let (sum, count) = samples.fold((0f64, 0usize), |(sum, count), s| {
+        (sum + (*s as f64) * (*s as f64), count + 1)
+    });
+    if count == 0 { 0. } else { (sum / count as f64).sqrt() as f32 }
+}
+
+/// Split the frames in `buckets` ranges and get the min and max of each one.
+fn overview(samples: &[f32], channels: usize, buckets: usize) -> Vec<(f32, f32)> {
+    let frames = samples.len() / channels;
+    if buckets == 0 || frames == 0 {
+        return Vec::new();
+    }
+
+    (0..buckets).map(|bucket| {
+        let start = bucket * frames / buckets;
+        let end = ((bucket + 1) * frames / buckets).max(start + 1).min(frames);
+        samples[start * channels..end * channels].iter()
+            .fold(None, |bounds: Option<(f32, f32)>, &s| match bounds {
+                Some((min, max)) => Some((min.min(s), max.max(s))),
+                None => Some((s, s))
+            })
+            .unwrap_or((0., 0.))
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use sample_buffer::SampleBuffer;
+
+    #[test]
+    fn samplebuffer_channels() -> () {
+        let buffer = SampleBuffer::new(vec![0.1, -0.2, 0.3, -0.4], 2, 44100);
+
+        assert_eq!(buffer.get_frames(), 2);
+        assert_eq!(buffer.get_channel(0), vec![0.1, 0.3]);
+        assert_eq!(buffer.get_channel(1), vec![-0.2, -0.4]);
+        assert!(buffer.get_channel(2).is_empty());
+    }
+
+    #[test]
+    fn samplebuffer_levels() -> () {
+        let buffer = SampleBuffer::new(vec![0.5, -1., 0.5, 1.], 2, 44100);
+
+        assert_eq!(buffer.peak(), 1.);
+        assert_eq!(buffer.channel_peak(0), 0.5);
+        assert_eq!(buffer.channel_rms(1), 1.);
+        assert_eq!(buffer.clipped_samples(), 2);
+    }
+
+    #[test]
+    fn samplebuffer_overview() -> () {
+        let buffer = SampleBuffer::new(vec![0., 0.5, -0.5, 1., -1., 0.25], 1, 44100);
+
+        assert_eq!(buffer.overview(3), vec![(0., 0.5), (-0.5, 1.), (-1., 0.25)]);
+        assert_eq!(buffer.overview(1), vec![(-1., 1.)]);
+        // More buckets than frames repeat the frames
+        assert_eq!(buffer.overview(12).len(), 12);
+        assert!(buffer.overview(0).is_empty());
+    }
+}
@@ -40,6 +40,16 @@ use std::ffi::CStr;
 use std::ops::BitOr;
 use std::i32::*;
 use std::intrinsics::transmute;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::{Arc, Mutex};
+use std::slice;
+use std::mem;
+use std::cmp;
+use std::fs::File;
+use libc::c_void;
+use comments::{read_comments, read_comments_within, get_comment};
+use pipe_reader::{PipeReader, HISTORY_SIZE};
+use beat_clock::Tempo;
 
 #[doc(hidden)]
 mod libsndfile {
@@ -64,6 +74,17 @@ pub struct SndInfo {
     pub seekable : i32
 }
 
+/// A cue point marking a position in the file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CuePoint {
+    /// The identifier of the cue point
+    pub index : i32,
+    /// The position of the cue point in frames
+    pub position : u64,
+    /// The name of the cue point, empty if it has none
+    pub name : String
+}
+
 /// Modes availables for the open function.
 ///
 /// * Read - Read only mode
@@ -236,21 +257,120 @@ impl BitOr for FormatType {
     //fn bitor(self, rhs: RHS) -> Self::Output;
 }
 
-/// SndFile object, used to load/store sound from a file path or an fd.
+/// Any seekable reader which can be handed to libsndfile's virtual I/O.
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// The reader behind a SndFile opened with from_reader.
+type VirtualReader = Mutex<Box<dyn ReadSeek>>;
+
+/// SndFile object, used to load/store sound from a file path, an fd or a reader.
 pub struct SndFile {
     handle : ffi::SNDFILEhandle, //*const ffi::SNDFILE,
-    info : Box<SndInfo>
+    info : Box<SndInfo>,
+    /// The reader used by the virtual I/O callbacks, kept alive with the handle
+    reader : Option<Arc<VirtualReader>>,
+    /// If the reader is a pipe, which can only seek back among its last bytes
+    pipe : bool,
+    /// The path of the file, if opened from a path
+    path : Option<String>
 }
 
 impl Clone for SndFile {
     fn clone(&self) -> SndFile {
         SndFile {
             handle : self.handle,
-            info : self.info.clone()
+            info : self.info.clone(),
+            reader : self.reader.clone(),
+            pipe : self.pipe,
+            path : self.path.clone()
         }
     }
 }
 
+fn virtual_reader<'r>(user_data : *mut c_void) -> &'r VirtualReader {
+    unsafe { &*(user_data as *const VirtualReader) }
+}
+
+extern "C" fn vio_get_filelen(user_data : *mut c_void) -> ffi::sf_count_t {
+    let mut reader = match virtual_reader(user_data).lock() {
+        Ok(reader) => reader,
+        Err(_)     => return -1
+    };
+    let current = match reader.seek(SeekFrom::Current(0)) {
+        Ok(pos) => pos,
+        Err(_)  => return -1
+    };
+    let len = match reader.seek(SeekFrom::End(0)) {
+        Ok(pos) => pos,
+        Err(_)  => return -1
+    };
+    match reader.seek(SeekFrom::Start(current)) {
+        Ok(_)  => len as ffi::sf_count_t,
+        Err(_) => -1
+    }
+}
+
+extern "C" fn vio_get_pipe_len(_user_data : *mut c_void) -> ffi::sf_count_t {
+    // The length of a pipe is unknown, as libsndfile does for its own pipes
+    ffi::sf_count_t::max_value()
+}
+
+extern "C" fn vio_seek(offset : ffi::sf_count_t,
+                       whence : i32,
+                       user_data : *mut c_void) -> ffi::sf_count_t {
+    let pos = match whence {
+        ffi::SEEK_SET => SeekFrom::Start(offset as u64),
+        ffi::SEEK_CUR => SeekFrom::Current(offset),
+        ffi::SEEK_END => SeekFrom::End(offset),
+        _             => return -1
+    };
+    match virtual_reader(user_data).lock() {
+        Ok(mut reader) => match reader.seek(pos) {
+            Ok(pos) => pos as ffi::sf_count_t,
+            Err(_)  => -1
+        },
+        Err(_) => -1
+    }
+}
+
+extern "C" fn vio_read(ptr : *mut c_void,
+                       count : ffi::sf_count_t,
+                       user_data : *mut c_void) -> ffi::sf_count_t {
+    let mut reader = match virtual_reader(user_data).lock() {
+        Ok(reader) => reader,
+        Err(_)     => return 0
+    };
+    let buf = unsafe { slice::from_raw_parts_mut(ptr as *mut u8, count as usize) };
+    let mut total = 0;
+    // A reader may return less than asked, libsndfile treats that as EOF
+    while total < buf.len() {
+        match reader.read(&mut buf[total..]) {
+            Ok(0)  => break,
+            Ok(n)  => total += n,
+            Err(_) => break
+        }
+    }
+    total as ffi::sf_count_t
+}
+
+extern "C" fn vio_write(_ptr : *const c_void,
+                        _count : ffi::sf_count_t,
+                        _user_data : *mut c_void) -> ffi::sf_count_t {
+    0
+}
+
+extern "C" fn vio_tell(user_data : *mut c_void) -> ffi::sf_count_t {
+    match virtual_reader(user_data).lock() {
+        Ok(mut reader) => match reader.seek(SeekFrom::Current(0)) {
+            Ok(pos) => pos as ffi::sf_count_t,
+            Err(_)  => -1
+        },
+        Err(_) => -1
+    }
+}
+
 impl SndFile {
     /**
      * Construct SndFile object with the path to the music and a mode to open it.
@@ -282,7 +402,10 @@ impl SndFile {
         } else {
             Ok(SndFile {
                 handle :    tmp_sndfile,
-                info :      info
+                info :      info,
+                reader :    None,
+                pipe :      false,
+                path :      Some(path.to_string())
             })
         }
     }
@@ -310,7 +433,10 @@ impl SndFile {
         } else {
             Ok(SndFile {
                 handle :    tmp_sndfile,
-                info :      info
+                info :      info,
+                reader :    None,
+                pipe :      false,
+                path :      Some(path.to_string())
             })
         }
     }
@@ -354,7 +480,82 @@ impl SndFile {
         } else {
             Ok(SndFile {
                 handle :    tmp_sndfile,
-                info :      info
+                info :      info,
+                reader :    None,
+                pipe :      false,
+                path :      None
+            })
+        }
+    }
+
+    /**
+     * Construct SndFile object reading the music from any seekable reader.
+     *
+     * The file is opened in read only mode through libsndfile's virtual I/O,
+     * the reader is kept alive as long as the SndFile or one of its clones.
+     *
+     * # Argument
+     * * reader - The reader containing the encoded music
+     *
+     * Return Ok() containing the SndFile on success, a string representation
+     * of the error otherwise.
+     */
+    pub fn from_reader<R: Read + Seek + Send + 'static>(reader : R)
+                                                       -> Result<SndFile, String> {
+        SndFile::open_virtual(Box::new(reader), false)
+    }
+
+    /**
+     * Construct SndFile object reading the music from a reader which can't
+     * seek, like a pipe or a socket.
+     *
+     * The length of the music is unknown, and the SndFile can only be read
+     * forward once the header is parsed.
+     *
+     * # Argument
+     * * reader - The reader containing the encoded music
+     *
+     * Return Ok() containing the SndFile on success, a string representation
+     * of the error otherwise.
+     */
+    pub fn from_pipe<R: Read + Send + 'static>(reader : R) -> Result<SndFile, String> {
+        SndFile::open_virtual(Box::new(PipeReader::new(reader)), true)
+    }
+
+    fn open_virtual(reader : Box<dyn ReadSeek>, pipe : bool) -> Result<SndFile, String> {
+        let mut info = Box::new(SndInfo {
+            frames : 0,
+            samplerate : 0,
+            channels : 0,
+            format : 0,
+            sections : 0,
+            seekable : 0
+        });
+        let reader : Arc<VirtualReader> = Arc::new(Mutex::new(reader));
+        let mut vio = ffi::SF_VIRTUAL_IO {
+            get_filelen : if pipe { vio_get_pipe_len } else { vio_get_filelen },
+            seek :        vio_seek,
+            read :        vio_read,
+            write :       vio_write,
+            tell :        vio_tell
+        };
+        let tmp_sndfile = unsafe {
+            ffi::sf_open_virtual(&mut vio,
+                                 ffi::SFM_READ,
+                                 &mut *info,
+                                 &*reader as *const VirtualReader as *mut c_void)
+        };
+        if tmp_sndfile == 0 {
+            Err(unsafe {
+                from_utf8(CStr::from_ptr(ffi::sf_strerror(0) as *const i8).to_bytes()).unwrap().to_owned()
+            })
+        } else {
+            Ok(SndFile {
+                handle :    tmp_sndfile,
+                info :      info,
+                reader :    Some(reader),
+                pipe :      pipe,
+                path :      None
             })
         }
     }
@@ -386,6 +587,185 @@ impl SndFile {
         }
     }
 
+    /**
+     * Retrieve the cue points of the file, sorted by position.
+     *
+     * Return an empty vector if the format has no cue point.
+     */
+    pub fn get_cues(&self) -> Vec<CuePoint> {
+        let mut count : u32 = 0;
+        let has_cues = unsafe {
+            ffi::sf_command(self.handle,
+                            ffi::SFC_GET_CUE_COUNT,
+                            &mut count as *mut u32 as *mut c_void,
+                            mem::size_of::<u32>() as i32)
+        };
+        if has_cues == ffi::SF_FALSE || count == 0 {
+            return Vec::new();
+        }
+
+        let mut cues : Box<ffi::SF_CUES> = Box::new(unsafe { mem::zeroed() });
+        let res = unsafe {
+            ffi::sf_command(self.handle,
+                            ffi::SFC_GET_CUE,
+                            &mut *cues as *mut ffi::SF_CUES as *mut c_void,
+                            mem::size_of::<ffi::SF_CUES>() as i32)
+        };
+        if res == ffi::SF_FALSE {
+            return Vec::new();
+        }
+
+        let count = cmp::min(cues.cue_count as usize, cues.cue_points.len());
+        let mut points : Vec<CuePoint> = cues.cue_points[..count].iter().map(|cue| {
+            CuePoint {
+                index : cue.indx,
+                position : cue.sample_offset as u64,
+                name : unsafe {
+                    CStr::from_ptr(cue.name.as_ptr()).to_string_lossy().into_owned()
+                }
+            }
+        }).collect();
+        points.sort_by_key(|cue| cue.position);
+        points
+    }
+
+    /**
+     * Compute the highest absolute sample value of the file, normalized to
+     * [0.0, 1.0].
+     *
+     * The whole file is read, then the read position is restored.
+     *
+     * Return the peak, or None if libsndfile can't compute it.
+     */
+    pub fn calc_norm_signal_max(&self) -> Option<f64> {
+        let mut peak : f64 = 0.;
+        let res = unsafe {
+            ffi::sf_command(self.handle,
+                            ffi::SFC_CALC_NORM_SIGNAL_MAX,
+                            &mut peak as *mut f64 as *mut c_void,
+                            mem::size_of::<f64>() as i32)
+        };
+        if res == 0 { Some(peak) } else { None }
+    }
+
+    /**
+     * Read all the Vorbis comments of an Ogg or FLAC file.
+     *
+     * The comments of a pipe are only found among the bytes it can seek
+     * back to.
+     *
+     * Return the comments as (key, value) pairs with uppercase keys, or an
+     * empty vector if the file has no Vorbis comment or was opened from a fd.
+     * Err(String) if the reader can't get back to where libsndfile left it.
+     */
+    pub fn get_comments(&self) -> Result<Vec<(String, String)>, String> {
+        if let Some(ref reader) = self.reader {
+            let mut reader = match reader.lock() {
+                Ok(reader) => reader,
+                Err(_)     => return Ok(Vec::new())
+            };
+            // libsndfile expects the reader where it left it
+            let current = match reader.seek(SeekFrom::Current(0)) {
+                Ok(pos) => pos,
+                Err(_)  => return Ok(Vec::new())
+            };
+            let limit = if self.pipe { current + HISTORY_SIZE as u64 } else { u64::max_value() };
+            let comments = read_comments_within(&mut **reader, limit);
+            match reader.seek(SeekFrom::Start(current)) {
+                Ok(_)    => Ok(comments),
+                Err(err) => Err(format!("Cannot seek back after reading the comments: {}", err))
+            }
+        } else if let Some(ref path) = self.path {
+            match File::open(path) {
+                Ok(mut file) => Ok(read_comments(&mut file)),
+                Err(_)       => Ok(Vec::new())
+            }
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    /**
+     * Retrieve the loop points of the file.
+     *
+     * The loop points are read from the instrument chunk (e.g. the `smpl`
+     * chunk of a WAV file), or from the LOOPSTART and LOOPLENGTH (or LOOPEND)
+     * Vorbis comments.
+     *
+     * Return the start and end frames of the loop, or None if the file has
+     * no loop. Err(String) if the comments can't be read.
+     */
+    pub fn get_loop_points(&self) -> Result<Option<(u64, u64)>, String> {
+        let mut instrument : ffi::SF_INSTRUMENT = unsafe { mem::zeroed() };
+        let res = unsafe {
+            ffi::sf_command(self.handle,
+                            ffi::SFC_GET_INSTRUMENT,
+                            &mut instrument as *mut ffi::SF_INSTRUMENT as *mut c_void,
+                            mem::size_of::<ffi::SF_INSTRUMENT>() as i32)
+        };
+        if res != ffi::SF_FALSE && instrument.loop_count > 0 {
+            let first = instrument.loops[0];
+            if first.mode != ffi::SF_LOOP_NONE && first.start < first.end {
+                return Ok(Some((first.start as u64, first.end as u64)));
+            }
+        }
+
+        let comments = match self.get_comments() {
+            Ok(comments) => comments,
+            Err(err)     => return Err(err)
+        };
+        let start = match get_comment(&comments, "LOOPSTART").and_then(|v| v.trim().parse::<u64>().ok()) {
+            Some(start) => start,
+            None        => return Ok(None)
+        };
+        let end = match get_comment(&comments, "LOOPLENGTH").and_then(|v| v.trim().parse::<u64>().ok()) {
+            Some(length) => match start.checked_add(length) {
+                Some(end) => end,
+                None      => return Ok(None)
+            },
+            None         => match get_comment(&comments, "LOOPEND").and_then(|v| v.trim().parse::<u64>().ok()) {
+                Some(end) => end,
+                None      => self.info.frames as u64
+            }
+        };
+        if start < end {
+            Ok(Some((start, end)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /**
+     * Retrieve the tempo of the file.
+     *
+     * The tempo is read from the loop info (e.g. the `acid` chunk of a WAV
+     * file), or from the BPM Vorbis comment in 4/4.
+     *
+     * Return the tempo, or None if the file has no valid tempo. Err(String)
+     * if the comments can't be read.
+     */
+    pub fn get_tempo(&self) -> Result<Option<Tempo>, String> {
+        let mut loop_info : ffi::SF_LOOP_INFO = unsafe { mem::zeroed() };
+        let res = unsafe {
+            ffi::sf_command(self.handle,
+                            ffi::SFC_GET_LOOP_INFO,
+                            &mut loop_info as *mut ffi::SF_LOOP_INFO as *mut c_void,
+                            mem::size_of::<ffi::SF_LOOP_INFO>() as i32)
+        };
+        if res != ffi::SF_FALSE {
+            let mut tempo = Tempo::new(loop_info.bpm);
+            if loop_info.time_sig_num > 0 && loop_info.time_sig_den > 0 {
+                tempo.beats_per_bar = loop_info.time_sig_num as u32;
+                tempo.beat_unit = loop_info.time_sig_den as u32;
+            }
+            if tempo.validate().is_ok() {
+                return Ok(Some(tempo));
+            }
+        }
+
+        self.get_comments().map(|comments| Tempo::from_comments(&comments))
+    }
+
     /**
      * Set a tag on the music file.
      *
@@ -48,6 +48,15 @@ pub const SF_STR_LICENSE : SF_STR      = 0x08;
 pub const SF_STR_TRACKNUMBER : SF_STR  = 0x09;
 pub const SF_STR_GENRE : SF_STR        = 0x10;
 
+pub type SF_COMMAND = i32;
+pub const SFC_GET_CUE_COUNT : SF_COMMAND = 0x10CD;
+pub const SFC_GET_CUE : SF_COMMAND       = 0x10CE;
+pub const SFC_GET_INSTRUMENT : SF_COMMAND = 0x10D0;
+pub const SFC_GET_LOOP_INFO : SF_COMMAND = 0x10E0;
+pub const SFC_CALC_NORM_SIGNAL_MAX : SF_COMMAND = 0x1041;
+
+pub const SF_LOOP_NONE : i32 = 800;
+
 pub type SF_BOOL = i32;
 pub const SF_FALSE : SF_BOOL   = 0;
 pub const SF_TRUE : SF_BOOL    = 1;
@@ -122,6 +131,74 @@ pub const SF_FORMAT_ENDMASK : FORMAT_TYPE      = 0x30000000;
 pub type SNDFILE = c_void;
 pub type SNDFILEhandle = intptr_t;
 
+pub type sf_count_t = i64;
+
+pub type sf_vio_get_filelen = extern "C" fn(user_data : *mut c_void) -> sf_count_t;
+pub type sf_vio_seek = extern "C" fn(offset : sf_count_t, whence : i32, user_data : *mut c_void) -> sf_count_t;
+pub type sf_vio_read = extern "C" fn(ptr : *mut c_void, count : sf_count_t, user_data : *mut c_void) -> sf_count_t;
+pub type sf_vio_write = extern "C" fn(ptr : *const c_void, count : sf_count_t, user_data : *mut c_void) -> sf_count_t;
+pub type sf_vio_tell = extern "C" fn(user_data : *mut c_void) -> sf_count_t;
+
+#[repr(C)]
+pub struct SF_VIRTUAL_IO {
+    pub get_filelen : sf_vio_get_filelen,
+    pub seek : sf_vio_seek,
+    pub read : sf_vio_read,
+    pub write : sf_vio_write,
+    pub tell : sf_vio_tell
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SF_CUE_POINT {
+    pub indx : i32,
+    pub position : u32,
+    pub fcc_chunk : i32,
+    pub chunk_start : i32,
+    pub block_start : i32,
+    pub sample_offset : u32,
+    pub name : [c_char; 256]
+}
+
+#[repr(C)]
+pub struct SF_CUES {
+    pub cue_count : u32,
+    pub cue_points : [SF_CUE_POINT; 100]
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct SF_INSTRUMENT_LOOP {
+    pub mode : i32,
+    pub start : u32,
+    pub end : u32,
+    pub count : u32
+}
+
+#[repr(C)]
+pub struct SF_INSTRUMENT {
+    pub gain : i32,
+    pub basenote : c_char,
+    pub detune : c_char,
+    pub velocity_lo : c_char,
+    pub velocity_hi : c_char,
+    pub key_lo : c_char,
+    pub key_hi : c_char,
+    pub loop_count : i32,
+    pub loops : [SF_INSTRUMENT_LOOP; 16]
+}
+
+#[repr(C)]
+pub struct SF_LOOP_INFO {
+    pub time_sig_num : i16,
+    pub time_sig_den : i16,
+    pub loop_mode : i32,
+    pub num_beats : i32,
+    pub bpm : f32,
+    pub root_key : i32,
+    pub future : [i32; 6]
+}
+
 #[repr(C)]
 pub struct FormatInfo {
     pub format : i32,
@@ -132,10 +209,11 @@ pub struct FormatInfo {
 extern "C" {
     pub fn sf_open(path : *mut c_char, mode : SF_MODE, info : *mut SndInfo) -> SNDFILEhandle;
     pub fn sf_open_fd(fd : i32, mode : SF_MODE, info : *mut SndInfo, close_desc : SF_BOOL) -> SNDFILEhandle;
+    pub fn sf_open_virtual(sfvirtual : *mut SF_VIRTUAL_IO, mode : SF_MODE, info : *mut SndInfo, user_data : *mut c_void) -> SNDFILEhandle;
     pub fn sf_format_check(info : *mut SndInfo) -> SF_BOOL;
 
     pub fn sf_seek(sndfile : SNDFILEhandle, frames : i64, whence : i32) -> i64;
-    pub fn sf_command(sndfile : SNDFILEhandle, cmd : i32, data : *mut c_void, datasize : i32) -> Error;
+    pub fn sf_command(sndfile : SNDFILEhandle, cmd : i32, data : *mut c_void, datasize : i32) -> i32;
 
     pub fn sf_error(sndfile : SNDFILEhandle) -> Error;
     pub fn sf_strerror(sndfile : SNDFILEhandle) -> *mut c_char;
@@ -23,15 +23,22 @@
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::time::{Duration, Instant};
 
 use internal::OpenAlData;
 use sound_data;//::*;//{SoundData};
 use sound_data::{SoundData};
+use normalization::gain_headroom;
+use sound_cache::SoundCache;
+use sprite::Region;
+use resampler::{set_source_resampler, get_source_resampler};
 use openal::{ffi, al};
 use states::State;
 use states::State::{Initial, Playing, Paused, Stopped};
 use audio_controller::AudioController;
 use audio_tags::{AudioTags, Tags};
+use streamer;
+use streamer::StreamId;
 
 
 /**
@@ -62,7 +69,11 @@ pub struct Sound {
     /// The internal OpenAl source identifier
     al_source: u32,
     /// The SoundData associated to the Sound.
-    sound_data: Rc<RefCell<SoundData>>
+    sound_data: Rc<RefCell<SoundData>>,
+    /// The region of the SoundData played by the Sound, if any.
+    region: Option<String>,
+    /// The timer playing the Sound after a delay
+    timer: Option<StreamId>
 }
 
 impl Sound {
@@ -99,6 +110,41 @@ impl Sound {
         Sound::new_with_data(sound_data)
     }
 
+    /**
+     * Create a new struct sharing the SoundData of a cache.
+     *
+     * The file is loaded in the cache if it's not already there.
+     *
+     * # Arguments
+     * `path` - The path of the sound file.
+     * `cache` - The cache holding the SoundData.
+     *
+     * # Return
+     * A `Result` containing Ok(Sound) on success, Err(String)
+     * if there has been an error.
+     *
+     * # Example
+     * ```no_run
+     * use ears::{Sound, SoundCache};
+     *
+     * let mut cache = SoundCache::new();
+     * let snd = Sound::new_with_cache("path/to/the/sound.ogg", &mut cache)
+     *                  .expect("Cannot load the sound from a file!");
+     * ```
+     */
+    pub fn new_with_cache(path: &str, cache: &mut SoundCache) -> Result<Sound, String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        let sound_data = match cache.get(path) {
+            Ok(data) => data,
+            Err(err) => {
+                return Err(format!("Error creating sound data: {}", err));
+            }
+        };
+
+        Sound::new_with_data(sound_data)
+    }
+
     /**
      * Create a new struct with a SoundData to associate.
      *
@@ -130,6 +176,10 @@ impl Sound {
                       ffi::AL_BUFFER,
                       sound_data::get_buffer(&*sound_data
                                              .borrow_mut()) as i32);
+        // apply the normalization gain
+        let base_gain = sound_data.borrow().get_base_gain();
+        al::alSourcef(source_id, ffi::AL_GAIN, base_gain);
+        al::alSourcef(source_id, ffi::AL_MAX_GAIN, gain_headroom(base_gain));
 
         // Check if there is OpenAL internal error
         if let Some(err) = al::openal_has_error() {
@@ -138,10 +188,47 @@ impl Sound {
 
         Ok(Sound {
             al_source: source_id,
-            sound_data: sound_data
+            sound_data: sound_data,
+            region: None,
+            timer: None
         })
     }
 
+    /**
+     * Create a new struct playing a single region of a SoundData.
+     *
+     * # Arguments
+     * `sound_data` - The sound_data to associate to the Sound.
+     * `region` - The name of the region to play.
+     *
+     * # Return
+     * A `Result` containing Ok(Sound) on success, Err(String)
+     * if the region doesn't exist or there has been an error.
+     *
+     * # Example
+     * ```no_run
+     * use ears::{Sound, SoundData, LoadOptions, SpriteSheet};
+     * use std::rc::Rc;
+     * use std::cell::RefCell;
+     *
+     * let mut options = LoadOptions::default();
+     * options.sprites = SpriteSheet::from_file("path/to/ui.csv").unwrap();
+     * let data = SoundData::new_with_options("path/to/ui.ogg", &options).unwrap();
+     * let sound = Sound::new_with_region(Rc::new(RefCell::new(data)), "click").unwrap();
+     * ```
+     */
+    pub fn new_with_region(sound_data: Rc<RefCell<SoundData>>,
+                           region: &str) -> Result<Sound, String> {
+        let mut sound = match Sound::new_with_data(sound_data) {
+            Ok(sound) => sound,
+            Err(err) => return Err(err)
+        };
+        match sound.set_region(Some(region)) {
+            Ok(()) => Ok(sound),
+            Err(err) => Err(err)
+        }
+    }
+
     /**
      * Get the sound datas.
      *
@@ -181,13 +268,193 @@ impl Sound {
             return;
         }
 
+        // keep the volumes, the base gain may change
+        let volume = self.get_volume();
+        let max_volume = self.get_max_volume();
+
         // set the buffer
         al::alSourcei(self.al_source,
                       ffi::AL_BUFFER,
                         sound_data::get_buffer(&*sound_data
                                                .borrow()) as i32);
 
-        self.sound_data = sound_data
+        self.sound_data = sound_data;
+        self.region = None;
+        self.set_volume(volume);
+        self.set_max_volume(max_volume);
+    }
+
+    /**
+     * Select the region of the SoundData played by the Sound.
+     *
+     * Doesn't work if the sound is currently playing.
+     *
+     * # Argument
+     * `region` - The name of the region to play, or None to play the whole
+     * SoundData.
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the sound is playing or the
+     * SoundData has no region with this name.
+     */
+    pub fn set_region(&mut self, region: Option<&str>) -> Result<(), String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        if self.is_playing() {
+            return Err("Cannot change the region of a playing sound.".into());
+        }
+
+        let buffer = {
+            let data = self.sound_data.borrow();
+            match region {
+                Some(name) => match sound_data::get_region_buffer(&*data, name) {
+                    Some(buffer) => buffer,
+                    None => return Err(format!("Unknown region: {}", name))
+                },
+                None => sound_data::get_buffer(&*data)
+            }
+        };
+
+        al::alSourcei(self.al_source, ffi::AL_BUFFER, buffer as i32);
+        if let Some(err) = al::openal_has_error() {
+            return Err(format!("Internal OpenAL error: {}", err));
+        }
+
+        self.region = region.map(|name| name.to_string());
+        Ok(())
+    }
+
+    /**
+     * Set the part of the SoundData repeated when the Sound is looping.
+     *
+     * The loop points are shared by all the Sounds playing the SoundData and
+     * apply to the whole SoundData, not to its regions. Requires the
+     * AL_SOFT_loop_points extension.
+     *
+     * # Argument
+     * `loop_points` - The bounds of the loop, or None to loop the whole sound.
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the sound is playing, another Sound
+     * uses the SoundData or the loop points are invalid.
+     *
+     * # Example
+     * ```no_run
+     * use ears::{Sound, AudioController, Region};
+     *
+     * let mut snd = Sound::new("path/to/the/sound.ogg").unwrap();
+     * snd.set_loop_points(Some(Region::Seconds(1.5, 4.))).unwrap();
+     * snd.set_looping(true);
+     * snd.play();
+     * ```
+     */
+    pub fn set_loop_points(&mut self, loop_points: Option<Region>) -> Result<(), String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        if self.is_playing() {
+            return Err("Cannot change the loop points of a playing sound.".into());
+        }
+
+        // OpenAL refuses to change a buffer attached to a source
+        al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+        let res = self.sound_data.borrow_mut().set_loop_points(loop_points);
+        let buffer = {
+            let data = self.sound_data.borrow();
+            match self.region {
+                Some(ref name) => sound_data::get_region_buffer(&*data, name),
+                None => None
+            }.unwrap_or(sound_data::get_buffer(&*data))
+        };
+        al::alSourcei(self.al_source, ffi::AL_BUFFER, buffer as i32);
+
+        res
+    }
+
+    /**
+     * Get the loop points of the SoundData played by the Sound.
+     *
+     * # Return
+     * The start and end frames of the loop, or None if the whole sound is
+     * looped.
+     */
+    pub fn get_loop_points(&self) -> Option<(u64, u64)> {
+        self.sound_data.borrow().get_loop_points()
+    }
+
+    /**
+     * Select the resampler OpenAL uses to play the Sound.
+     *
+     * Requires the AL_SOFT_source_resampler extension.
+     *
+     * # Argument
+     * `resampler` - The index of the resampler in the list returned by
+     * `ears::get_resamplers`.
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the resampler doesn't exist.
+     *
+     * # Example
+     * ```no_run
+     * let mut snd = ears::Sound::new("path/to/the/sound.ogg").unwrap();
+     * let resamplers = ears::get_resamplers();
+     * if let Some(index) = resamplers.iter().position(|name| name.contains("Sinc")) {
+     *     snd.set_resampler(index).unwrap();
+     * }
+     * ```
+     */
+    pub fn set_resampler(&mut self, resampler: usize) -> Result<(), String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        set_source_resampler(self.al_source, resampler)
+    }
+
+    /**
+     * Get the resampler OpenAL uses to play the Sound.
+     *
+     * # Return
+     * The index of the resampler, or None if the AL_SOFT_source_resampler
+     * extension is missing.
+     */
+    pub fn get_resampler(&self) -> Option<usize> {
+        check_openal_context!(None);
+
+        get_source_resampler(self.al_source)
+    }
+
+    /**
+     * Get the region of the SoundData played by the Sound.
+     *
+     * # Return
+     * The name of the region, or None if the whole SoundData is played.
+     */
+    pub fn get_region(&self) -> Option<String> {
+        self.region.clone()
+    }
+
+    /**
+     * Play the Sound after a delay.
+     *
+     * The Sound is started by the streaming thread, to play a stinger on a
+     * beat of a Music. Playing or stopping the Sound before cancels it.
+     *
+     * # Argument
+     * * `delay` - The time before the Sound starts
+     */
+    pub fn play_after(&mut self, delay: Duration) -> () {
+        check_openal_context!(());
+
+        self.cancel_timer();
+        let al_source = self.al_source;
+        self.timer = Some(streamer::schedule(Instant::now() + delay, move || {
+            al::alSourcePlay(al_source);
+        }));
+    }
+
+    /// Cancel the delayed play of the Sound.
+    fn cancel_timer(&mut self) -> () {
+        if let Some(id) = self.timer.take() {
+            streamer::unregister(id);
+        }
     }
 }
 
@@ -218,6 +485,7 @@ impl AudioController for Sound {
     fn play(&mut self) -> () {
         check_openal_context!(());
 
+        self.cancel_timer();
         al::alSourcePlay(self.al_source);
 
         match al::openal_has_error() {
@@ -261,6 +529,7 @@ impl AudioController for Sound {
     fn stop(&mut self) -> () {
         check_openal_context!(());
 
+        self.cancel_timer();
         al::alSourceStop(self.al_source)
     }
 
@@ -331,7 +600,8 @@ impl AudioController for Sound {
      *
      * A value of 1.0 means unattenuated. Each division by 2 equals an
      * attenuation of about -6dB. Each multiplicaton by 2 equals an
-     * amplification of about +6dB.
+     * amplification of about +6dB. The normalization gain of the SoundData is
+     * applied on top of it.
      *
      * # Argument
      * * `volume` - The volume of the Sound, should be between 0. and 1.
@@ -339,7 +609,8 @@ impl AudioController for Sound {
     fn set_volume(&mut self, volume: f32) -> () {
         check_openal_context!(());
 
-        al::alSourcef(self.al_source, ffi::AL_GAIN, volume);
+        let base_gain = self.sound_data.borrow().get_base_gain();
+        al::alSourcef(self.al_source, ffi::AL_GAIN, volume * base_gain);
     }
 
     /**
@@ -353,7 +624,8 @@ impl AudioController for Sound {
 
         let mut volume : f32 = 0.;
         al::alGetSourcef(self.al_source, ffi::AL_GAIN, &mut volume);
-        volume
+        let base_gain = self.sound_data.borrow().get_base_gain();
+        if base_gain > 0. { volume / base_gain } else { volume }
     }
 
     /**
@@ -390,7 +662,8 @@ impl AudioController for Sound {
      * Set the maximal volume for a Sound.
      *
      * The maximum volume allowed for a sound, after distance and cone
-     * attenation is applied (if applicable).
+     * attenation is applied (if applicable). It's raised by a normalization
+     * gain above 1.
      *
      * # Argument
      * * `max_volume` - The new maximal volume of the Sound should be between
@@ -399,7 +672,8 @@ impl AudioController for Sound {
     fn set_max_volume(&mut self, max_volume: f32) -> () {
         check_openal_context!(());
 
-        al::alSourcef(self.al_source, ffi::AL_MAX_GAIN, max_volume);
+        let headroom = gain_headroom(self.sound_data.borrow().get_base_gain());
+        al::alSourcef(self.al_source, ffi::AL_MAX_GAIN, max_volume * headroom);
     }
 
     /**
@@ -413,7 +687,7 @@ impl AudioController for Sound {
 
         let mut volume : f32 = 0.;
         al::alGetSourcef(self.al_source, ffi::AL_MAX_GAIN, &mut volume);
-        volume
+        volume / gain_headroom(self.sound_data.borrow().get_base_gain())
     }
 
     /**
@@ -700,6 +974,7 @@ impl AudioController for Sound {
 impl Drop for Sound {
     ///Destroy all the resources attached to the Sound.
     fn drop(&mut self) -> () {
+        self.cancel_timer();
         unsafe {
             ffi::alDeleteSources(1, &mut self.al_source);
         }
@@ -710,7 +985,15 @@ impl Drop for Sound {
 mod test {
     #![allow(non_snake_case)]
 
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use std::thread;
+    use std::time::Duration;
     use sound::Sound;
+    use sound_data::SoundData;
+    use load_options::LoadOptions;
+    use normalization::Normalization;
+    use sprite::Region;
     use states::State::{Playing, Paused, Stopped};
     use audio_controller::AudioController;
 
@@ -722,6 +1005,21 @@ mod test {
         assert!(snd.is_ok());
     }
 
+    #[test]
+    #[ignore]
+    fn sound_create_with_region_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.sprites.add("start", Region::Frames(0, 100));
+        let data = Rc::new(RefCell::new(SoundData::new_with_options("res/shot.wav",
+                                                                   &options).unwrap()));
+        let mut snd = Sound::new_with_region(data, "start").unwrap();
+
+        assert_eq!(snd.get_region(), Some("start".to_string()));
+        assert!(snd.set_region(Some("unknown")).is_err());
+        assert!(snd.set_region(None).is_ok());
+        assert_eq!(snd.get_region(), None);
+    }
+
     #[test]
     #[ignore]
     fn sound_create_FAIL() -> () {
@@ -848,6 +1146,20 @@ mod test {
         assert_eq!(snd.get_max_volume(), 0.9);
     }
 
+    #[test]
+    #[ignore]
+    fn sound_max_volume_normalized_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.normalization = Normalization::Peak(0.);
+        let data = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+        let mut snd = Sound::new_with_data(Rc::new(RefCell::new(data))).unwrap();
+
+        // The max volume is relative to the normalized level
+        assert_eq!(snd.get_max_volume(), 1.);
+        snd.set_max_volume(0.5);
+        assert!((snd.get_max_volume() - 0.5).abs() < 1e-6);
+    }
+
     #[test]
     #[ignore]
     #[should_panic]
@@ -1012,4 +1324,15 @@ mod test {
         snd.set_attenuation(-1.);
         assert_eq!(snd.get_attenuation(), -1.);
     }
+
+    #[test]
+    #[ignore]
+    fn sound_play_after_OK() -> () {
+        let mut snd = Sound::new("res/shot.wav").expect("Cannot create sound");
+
+        snd.play_after(Duration::from_millis(50));
+        assert!(snd.get_state() != Playing);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(snd.get_state(), Playing);
+    }
 }
@@ -0,0 +1,286 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Share the SoundDatas loaded from the same file.
+
+use std::rc::{Rc, Weak};
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use sound_data::SoundData;
+use load_options::LoadOptions;
+
+/**
+ * How a SoundCache keeps the SoundDatas it loaded.
+ *
+ * * Strong - The SoundData stays loaded until it's unloaded from the cache
+ * * Weak - The SoundData is freed as soon as no Sound uses it anymore, and
+ * loaded again the next time it's requested
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CachePolicy {
+    Strong,
+    Weak
+}
+
+enum Entry {
+    Strong(Rc<RefCell<SoundData>>),
+    Weak(Weak<RefCell<SoundData>>)
+}
+
+impl Entry {
+    fn get(&self) -> Option<Rc<RefCell<SoundData>>> {
+        match *self {
+            Entry::Strong(ref data) => Some(data.clone()),
+            Entry::Weak(ref data)   => data.upgrade()
+        }
+    }
+}
+
+/**
+ * Cache of SoundDatas keyed by path.
+ *
+ * Each file is decoded and uploaded to OpenAL only once, every Sound created
+ * through the cache with the same path shares the same SoundData.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{Sound, SoundCache, AudioController};
+ *
+ * let mut cache = SoundCache::new();
+ * cache.preload(&["path/to/step.ogg", "path/to/jump.ogg"]).unwrap();
+ *
+ * // Both sounds share the same samples
+ * let mut step1 = Sound::new_with_cache("path/to/step.ogg", &mut cache).unwrap();
+ * let mut step2 = Sound::new_with_cache("path/to/step.ogg", &mut cache).unwrap();
+ * step1.play();
+ * step2.play();
+ *
+ * println!("Memory used: {} bytes", cache.get_memory_usage());
+ * ```
+ */
+pub struct SoundCache {
+    /// The loaded SoundDatas
+    entries: HashMap<String, Entry>,
+    /// How the SoundDatas are kept
+    policy: CachePolicy,
+    /// The options used to load the SoundDatas
+    options: LoadOptions
+}
+
+impl Default for SoundCache {
+    fn default() -> SoundCache {
+        SoundCache::new()
+    }
+}
+
+impl SoundCache {
+    /**
+     * Create a new empty SoundCache.
+     *
+     * The SoundDatas are kept until they are unloaded.
+     */
+    pub fn new() -> SoundCache {
+        SoundCache::new_with_policy(CachePolicy::Strong)
+    }
+
+    /**
+     * Create a new empty SoundCache with a custom policy.
+     *
+     * # Argument
+     * * `policy` - How the SoundDatas are kept by the cache
+     */
+    pub fn new_with_policy(policy: CachePolicy) -> SoundCache {
+        SoundCache {
+            entries: HashMap::new(),
+            policy: policy,
+            options: LoadOptions::default()
+        }
+    }
+
+    /**
+     * Set the options used to load the next SoundDatas.
+     *
+     * The SoundDatas already in the cache are not reloaded.
+     *
+     * # Argument
+     * * `options` - The options used to decode the files
+     */
+    pub fn set_load_options(&mut self, options: LoadOptions) -> () {
+        self.options = options;
+    }
+
+    /**
+     * Get the SoundData of a file, loading it if it's not in the cache.
+     *
+     * # Argument
+     * * `path` - The path of the sound file
+     *
+     * # Return
+     * A `Result` containing Ok(SoundData) on success, Err(String)
+     * if the file cannot be loaded.
+     */
+    pub fn get(&mut self, path: &str) -> Result<Rc<RefCell<SoundData>>, String> {
+        if let Some(data) = self.entries.get(path).and_then(|entry| entry.get()) {
+            return Ok(data);
+        }
+
+        let data = match SoundData::new_with_options(path, &self.options) {
+            Ok(data) => Rc::new(RefCell::new(data)),
+            Err(err) => return Err(err)
+        };
+        let entry = match self.policy {
+            CachePolicy::Strong => Entry::Strong(data.clone()),
+            CachePolicy::Weak   => Entry::Weak(Rc::downgrade(&data))
+        };
+        self.entries.insert(path.to_string(), entry);
+        Ok(data)
+    }
+
+    /**
+     * Load a list of files in the cache.
+     *
+     * With the Weak policy the SoundDatas are freed right away if they
+     * are not used, so preloading is only useful with the Strong policy.
+     *
+     * # Argument
+     * * `paths` - The paths of the sound files
+     *
+     * # Return
+     * `Ok(())` if all the files are loaded, otherwise the error of the first
+     * file which cannot be loaded.
+     */
+    pub fn preload(&mut self, paths: &[&str]) -> Result<(), String> {
+        for path in paths {
+            if let Err(err) = self.get(path) {
+                return Err(format!("Error while preloading {}: {}", path, err));
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * Remove a file from the cache.
+     *
+     * The Sounds already using the SoundData keep it alive, it's freed when
+     * the last of them is dropped.
+     *
+     * # Argument
+     * * `path` - The path of the sound file
+     *
+     * # Return
+     * True if the file was in the cache, false otherwise.
+     */
+    pub fn unload(&mut self, path: &str) -> bool {
+        self.entries.remove(path).is_some()
+    }
+
+    /// Remove all the files from the cache.
+    pub fn clear(&mut self) -> () {
+        self.entries.clear();
+    }
+
+    /**
+     * Check if a file is loaded in the cache.
+     *
+     * # Argument
+     * * `path` - The path of the sound file
+     */
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.get(path).and_then(|entry| entry.get()).is_some()
+    }
+
+    /**
+     * Get the number of files loaded in the cache.
+     */
+    pub fn len(&self) -> usize {
+        self.entries.values().filter(|entry| entry.get().is_some()).count()
+    }
+
+    /// Check if no file is loaded in the cache.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /**
+     * Get the memory used by the samples of all the loaded files.
+     *
+     * # Return
+     * The size in bytes of all the OpenAL buffers held by the cache.
+     */
+    pub fn get_memory_usage(&self) -> usize {
+        self.entries.values()
+            .filter_map(|entry| entry.get())
+            .map(|data| data.borrow().get_memory_usage())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use std::rc::Rc;
+    use sound_cache::{SoundCache, CachePolicy};
+
+    #[test]
+    #[ignore]
+    fn soundcache_get_SHARED() -> () {
+        let mut cache = SoundCache::new();
+        let data1 = cache.get("res/shot.wav").unwrap();
+        let data2 = cache.get("res/shot.wav").unwrap();
+
+        assert!(Rc::ptr_eq(&data1, &data2));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    #[ignore]
+    fn soundcache_unload_OK() -> () {
+        let mut cache = SoundCache::new();
+        cache.preload(&["res/shot.wav"]).unwrap();
+
+        assert!(cache.unload("res/shot.wav"));
+        assert!(!cache.contains("res/shot.wav"));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    #[ignore]
+    fn soundcache_weak_FREED() -> () {
+        let mut cache = SoundCache::new_with_policy(CachePolicy::Weak);
+        {
+            let _data = cache.get("res/shot.wav").unwrap();
+            assert!(cache.contains("res/shot.wav"));
+        }
+
+        assert!(!cache.contains("res/shot.wav"));
+        assert_eq!(cache.get_memory_usage(), 0);
+    }
+
+    #[test]
+    #[ignore]
+    fn soundcache_get_FAIL() -> () {
+        let mut cache = SoundCache::new();
+
+        assert!(cache.get("toto.wav").is_err());
+    }
+}
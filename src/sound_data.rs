@@ -21,15 +21,28 @@
 
 //! The datas extracted from a sound file.
 
-use std::mem;
-use libc::c_void;
 use std::vec::Vec;
+use std::io::Cursor;
+use std::cmp;
 
 use openal::{ffi, al};
 use sndfile::{SndFile, SndInfo};
 use sndfile::OpenMode::Read;
 use internal::OpenAlData;
 use audio_tags::{Tags, AudioTags, get_sound_tags};
+use audio_tags;
+use sample::{Sample, Samples, SampleType};
+use sample_buffer::SampleBuffer;
+use resampler::resample;
+use time_stretch::time_stretch;
+use normalization::{Normalization, compute_base_gain};
+use load_options::{LoadOptions, validate_header, has_known_length};
+use sprite::{SpriteSheet, Region};
+use loader::Progress;
+use oneshot;
+
+/// The number of frames decoded at once while loading a file.
+const DECODE_CHUNK_FRAMES: usize = 65536;
 
 /**
  * Samples extracted from a file.
@@ -69,7 +82,18 @@ pub struct SoundData {
     /// The total samples count of the Sound
     nb_sample: i64,
     /// The OpenAl internal identifier for the buffer
-    al_buffer: u32
+    al_buffer: u32,
+    /// The named regions, with their frame bounds and OpenAL buffer
+    regions: Vec<(String, (u64, u64), u32)>,
+    /// The start and end frames of the loop
+    loop_points: Option<(u64, u64)>,
+    /// The decoded samples, if kept
+    samples: Option<Samples>,
+    /// The path of the file and its load options, to decode the samples
+    /// again
+    source: Option<(String, LoadOptions)>,
+    /// The gain bringing the sound to the normalized level
+    base_gain: f32
 }
 
 impl SoundData {
@@ -88,56 +112,689 @@ impl SoundData {
      * if there has been an error.
      */
     pub fn new(path: &str) -> Result<SoundData, String> {
+        SoundData::new_with_options(path, &LoadOptions::default())
+    }
+
+    /**
+     * Create a new SoundData with custom load options.
+     *
+     * # Arguments
+     * * `path` - The path of the file to load
+     * * `options` - The options used to decode the file
+     *
+     * # Return
+     * A `Result` containing Ok(SoundData) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new_with_options(path: &str, options: &LoadOptions) -> Result<SoundData, String> {
         check_openal_context!(Err("Invalid OpenAL context.".into()));
 
-        let mut file = match SndFile::new(path, Read) {
+        let file = match SndFile::new(path, Read) {
+            Ok(file) => file,
+            Err(err) => {
+                return Err(format!("Error while loading sound file: {}", err));
+            }
+        };
+
+        SoundData::from_file(file, Some(path), options)
+    }
+
+    /**
+     * Create a new SoundData from an encoded file held in memory.
+     *
+     * The bytes are decoded exactly like a file on disk would be, which makes
+     * it possible to load assets embedded with `include_bytes!` or extracted
+     * from a pack file.
+     *
+     * # Arguments
+     * * `bytes` - The content of the sound file
+     *
+     * # Return
+     * A `Result` containing Ok(SoundData) on success, Err(String)
+     * if there has been an error.
+     *
+     * # Example
+     * ```no_run
+     * use ears::SoundData;
+     *
+     * let bytes = std::fs::read("path/to/my/sound.ogg").unwrap();
+     * let snd_data = SoundData::from_bytes(&bytes).unwrap();
+     * ```
+     */
+    pub fn from_bytes(bytes: &[u8]) -> Result<SoundData, String> {
+        SoundData::from_bytes_with_options(bytes, &LoadOptions::default())
+    }
+
+    /**
+     * Create a new SoundData from an encoded file held in memory, with
+     * custom load options.
+     *
+     * # Arguments
+     * * `bytes` - The content of the sound file
+     * * `options` - The options used to decode the file
+     *
+     * # Return
+     * A `Result` containing Ok(SoundData) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn from_bytes_with_options(bytes: &[u8],
+                                   options: &LoadOptions) -> Result<SoundData, String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        let file = match SndFile::from_reader(Cursor::new(bytes.to_vec())) {
             Ok(file) => file,
             Err(err) => {
                 return Err(format!("Error while loading sound file: {}", err));
             }
         };
 
-        let infos = file.get_sndinfo();
+        SoundData::from_file(file, None, options)
+    }
 
-        let nb_sample = infos.channels as i64 * infos.frames;
+    /**
+     * Create a new SoundData from interleaved samples.
+     *
+     * The samples can be generated or decoded by the caller, they are
+     * uploaded as is in an OpenAL buffer. `i16`, `f32` (in the range
+     * [-1.0, 1.0]) and `u8` (centered on 128) samples are accepted.
+     * A SoundData created this way has empty tags.
+     *
+     * # Arguments
+     * * `samples` - The interleaved samples of all the channels
+     * * `channels` - The number of channels of the samples
+     * * `sample_rate` - The sample rate of the samples in Hz
+     *
+     * # Return
+     * A `Result` containing Ok(SoundData) on success, Err(String)
+     * if there has been an error.
+     *
+     * # Example
+     * ```no_run
+     * use ears::SoundData;
+     *
+     * // One second of a 440Hz sine wave
+     * let samples: Vec<f32> = (0..44100).map(|i| {
+     *     (i as f32 * 440. * 2. * std::f32::consts::PI / 44100.).sin()
+     * }).collect();
+     * let snd_data = SoundData::from_samples(&samples, 1, 44100).unwrap();
+     * ```
+     */
+    pub fn from_samples<T: Sample>(samples: &[T],
+                                   channels: i32,
+                                   sample_rate: i32) -> Result<SoundData, String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
 
-        let mut samples = vec![0i16; nb_sample as usize];
-        file.read_i16(&mut samples[..], nb_sample as i64);
+        if channels <= 0 || sample_rate <= 0 {
+            return Err("Invalid channel count or sample rate.".into());
+        }
+        if samples.len() % channels as usize != 0 {
+            return Err("The sample count is not a multiple of the channel count.".into());
+        }
 
         let mut buffer_id = 0;
-        let len = mem::size_of::<i16>() * (samples.len());
+        al::alGenBuffers(1, &mut buffer_id);
+        if let Err(err) = T::buffer_data(buffer_id, samples, channels, sample_rate) {
+            unsafe { ffi::alDeleteBuffers(1, &mut buffer_id); }
+            return Err(err);
+        }
+
+        Ok(SoundData {
+            sound_tags: audio_tags::empty(),
+            snd_info: SndInfo {
+                frames: (samples.len() / channels as usize) as i64,
+                samplerate: sample_rate,
+                channels: channels,
+                format: 0,
+                sections: 1,
+                seekable: 1
+            },
+            nb_sample: samples.len() as i64,
+            al_buffer: buffer_id,
+            regions: Vec::new(),
+            loop_points: None,
+            samples: None,
+            source: None,
+            base_gain: 1.
+        })
+    }
+
+    /**
+     * Get the memory used by the samples of the SoundData.
+     *
+     * # Return
+     * The size in bytes of the OpenAL buffer holding the samples.
+     */
+    pub fn get_memory_usage(&self) -> usize {
+        check_openal_context!(0);
+
+        let mut total = 0;
+        for buffer in Some(self.al_buffer).into_iter()
+                          .chain(self.regions.iter().map(|&(_, _, buffer)| buffer)) {
+            let mut size = 0;
+            al::alGetBufferi(buffer, ffi::AL_SIZE, &mut size);
+            total += size as usize;
+        }
+        total
+    }
+
+    /**
+     * Get the decoded samples of the SoundData.
+     *
+     * The samples kept with the `keep_samples` load option are returned
+     * directly, otherwise the file is decoded again with the same load
+     * options. A SoundData created from samples in memory, or from a reader,
+     * must be loaded with `keep_samples` for this call to succeed.
+     *
+     * # Return
+     * A `Result` containing Ok(SampleBuffer) on success, Err(String) if the
+     * samples were not kept and the SoundData wasn't loaded from a path, or
+     * if the file can't be decoded again.
+     */
+    pub fn get_sample_buffer(&self) -> Result<SampleBuffer, String> {
+        let channels = self.snd_info.channels;
+        let sample_rate = self.snd_info.samplerate;
 
-        // Retrieve format informations
-        let format =  match al::get_channels_format(infos.channels) {
-            Some(fmt) => fmt,
-            None => {
-                return Err("Unrecognized sound format.".into());
+        if let Some(ref samples) = self.samples {
+            return Ok(SampleBuffer::new(samples.to_f32(), channels, sample_rate));
+        }
+
+        let (path, options) = match self.source {
+            Some((ref path, ref options)) => (path, options),
+            None => return Err("The samples were not kept and can't be decoded again.".into())
+        };
+        let mut file = match SndFile::new(path, Read) {
+            Ok(file) => file,
+            Err(err) => {
+                return Err(format!("Error while loading sound file: {}", err));
             }
         };
 
-        al::alGenBuffers(1, &mut buffer_id);
-        al::alBufferData(buffer_id,
-                         format,
-                         samples.as_ptr() as *mut c_void,
-                         len as i32,
-                         infos.samplerate);
+        let decoded = decode_file(&mut file, options, &Progress::new());
+        file.close();
+        decoded.map(|(samples, read, infos)| {
+            SampleBuffer::new(samples.to_f32()[..read].to_vec(), infos.channels, infos.samplerate)
+        })
+    }
+
+    /**
+     * Render the SoundData at another speed, without changing its pitch.
+     *
+     * The samples are time-stretched into a new SoundData, keeping the tags,
+     * the normalization gain and the loop points, but not the regions.
+     *
+     * # Argument
+     * * `speed` - The speed, from 0.5 (twice slower) to 2. (twice faster)
+     *
+     * # Return
+     * A `Result` containing Ok(SoundData) on success, Err(String) if the
+     * speed is out of range or the samples can't be read again, see
+     * `get_sample_buffer`.
+     *
+     * # Example
+     * ```no_run
+     * use std::rc::Rc;
+     * use std::cell::RefCell;
+     * use ears::{SoundData, Sound, AudioController};
+     *
+     * let snd_data = SoundData::new("path/to/sentence.ogg").unwrap();
+     * let slow = snd_data.time_stretch(0.75).unwrap();
+     * let mut snd = Sound::new_with_data(Rc::new(RefCell::new(slow))).unwrap();
+     * snd.play();
+     * ```
+     */
+    pub fn time_stretch(&self, speed: f32) -> Result<SoundData, String> {
+        let buffer = match self.get_sample_buffer() {
+            Ok(buffer) => buffer,
+            Err(err)   => return Err(err)
+        };
+        let samples = match time_stretch(buffer.get_samples(),
+                                         buffer.get_channels(),
+                                         buffer.get_sample_rate(),
+                                         speed) {
+            Ok(samples) => samples,
+            Err(err)    => return Err(err)
+        };
+        let mut stretched = match SoundData::from_samples(&samples,
+                                                          buffer.get_channels(),
+                                                          buffer.get_sample_rate()) {
+            Ok(stretched) => stretched,
+            Err(err)      => return Err(err)
+        };
+
+        // The samples read again may have the sample rate of the file
+        let scale = |frames: u64| {
+            (scale_frames(frames, self.snd_info.samplerate, buffer.get_sample_rate()) as f64
+             / speed as f64).round() as u64
+        };
+        stretched.sound_tags = self.sound_tags.clone();
+        stretched.base_gain = self.base_gain;
+        if let Some((start, end)) = self.loop_points {
+            let region = Region::Frames(scale(start), scale(end));
+            if al::is_extension_present("AL_SOFT_loop_points") {
+                if let Err(err) = stretched.set_loop_points(Some(region)) {
+                    return Err(err);
+                }
+            } else {
+                stretched.loop_points = clamp_loop_points(region,
+                                                          stretched.snd_info.samplerate,
+                                                          stretched.snd_info.frames as u64);
+            }
+        }
+        Ok(stretched)
+    }
+
+    /**
+     * Get the gain bringing the SoundData to the normalized level.
+     *
+     * The gain comes from the `normalization` and `replay_gain` load
+     * options, and is applied under the volume of the Sounds playing the
+     * SoundData. The max volume of the Sounds is raised to let a gain above
+     * 1 be heard.
+     *
+     * # Return
+     * The base gain, 1. if the level of the file is kept.
+     */
+    pub fn get_base_gain(&self) -> f32 {
+        self.base_gain
+    }
+
+    /**
+     * Get the loop points of the SoundData.
+     *
+     * The loop points are read from the file when it's loaded, unless they
+     * are given in the LoadOptions.
+     *
+     * # Return
+     * The start and end frames of the loop, or None if the whole SoundData
+     * is looped.
+     */
+    pub fn get_loop_points(&self) -> Option<(u64, u64)> {
+        self.loop_points
+    }
+
+    /**
+     * Set the part of the SoundData repeated by the looping Sounds.
+     *
+     * Requires the AL_SOFT_loop_points extension, and can't be done while
+     * a Sound uses the SoundData.
+     *
+     * # Argument
+     * * `loop_points` - The bounds of the loop, or None to loop the whole
+     * SoundData.
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the loop points are invalid or
+     * can't be changed.
+     */
+    pub fn set_loop_points(&mut self, loop_points: Option<Region>) -> Result<(), String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        if !al::is_extension_present("AL_SOFT_loop_points") {
+            return Err("Loop points are not supported by the OpenAL implementation.".into());
+        }
+
+        let frames = self.snd_info.frames as u64;
+        let bounds = match loop_points {
+            Some(region) => match clamp_loop_points(region, self.snd_info.samplerate, frames) {
+                Some(bounds) => bounds,
+                None => return Err("The loop end must be after the loop start.".into())
+            },
+            None => (0, frames)
+        };
 
+        let values = [bounds.0 as i32, bounds.1 as i32];
+        al::alBufferiv(self.al_buffer, ffi::AL_LOOP_POINTS_SOFT, &values[0]);
         if let Some(err) = al::openal_has_error() {
-             return Err(format!("Internal OpenAL error: {}", err));
+            return Err(format!("Cannot set the loop points, the SoundData may be in use: {}", err));
+        }
+
+        self.loop_points = loop_points.map(|_| bounds);
+        Ok(())
+    }
+
+    /**
+     * Check if the SoundData has a region with the given name.
+     *
+     * # Argument
+     * * `name` - The name of the region
+     */
+    pub fn has_region(&self, name: &str) -> bool {
+        self.regions.iter().any(|&(ref n, _, _)| n == name)
+    }
+
+    /**
+     * Get the names of the regions of the SoundData.
+     *
+     * # Return
+     * The names of the regions, in definition order.
+     */
+    pub fn get_region_names(&self) -> Vec<String> {
+        self.regions.iter().map(|&(ref name, _, _)| name.clone()).collect()
+    }
+
+    /**
+     * Get the bounds of a region.
+     *
+     * # Argument
+     * * `name` - The name of the region
+     *
+     * # Return
+     * The start and end frames of the region, or None if there is no
+     * region with this name.
+     */
+    pub fn get_region(&self, name: &str) -> Option<(u64, u64)> {
+        self.regions.iter()
+            .find(|&&(ref n, _, _)| n == name)
+            .map(|&(_, bounds, _)| bounds)
+    }
+
+    /// Decode the whole file and upload the samples in an OpenAL buffer.
+    fn from_file(file: SndFile,
+                 path: Option<&str>,
+                 options: &LoadOptions) -> Result<SoundData, String> {
+        SoundData::from_file_with_progress(file, path, options, &Progress::new())
+    }
+
+    /// Decode the file chunk by chunk, reporting the progress.
+    fn from_file_with_progress(mut file: SndFile,
+                               path: Option<&str>,
+                               options: &LoadOptions,
+                               progress: &Progress) -> Result<SoundData, String> {
+        let known_length = has_known_length(&file.get_sndinfo());
+        let file_rate = file.get_sndinfo().samplerate;
+        let out_rate = options.get_sample_rate(file_rate);
+        let (mut samples, read, infos) = match decode_file(&mut file, options, progress) {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                file.close();
+                return Err(err);
+            }
+        };
+        let decoded = match options.normalization {
+            Normalization::Off => None,
+            _ => Some(samples.to_f32())
+        };
+        let base_gain = match compute_base_gain(&mut file,
+                                                options,
+                                                decoded.as_ref().map(|decoded| {
+                                                    (&decoded[..read], infos.channels, infos.samplerate)
+                                                })) {
+            Ok(base_gain) => base_gain,
+            Err(err)      => {
+                file.close();
+                return Err(err);
+            }
         };
 
-        let sound_data = SoundData {
+        let nb_sample = if known_length {
+            infos.channels as i64 * infos.frames
+        } else {
+            read as i64
+        };
+
+        let mut buffer_id = 0;
+        al::alGenBuffers(1, &mut buffer_id);
+        if let Err(err) = samples.buffer_data(buffer_id,
+                                              read,
+                                              infos.channels,
+                                              infos.samplerate) {
+            unsafe { ffi::alDeleteBuffers(1, &mut buffer_id); }
+            file.close();
+            return Err(err);
+        }
+
+        let frames = (read / infos.channels as usize) as u64;
+        let mut sprites = options.sprites.clone();
+        if options.cue_regions {
+            add_cue_regions(&mut sprites, &file, frames, file_rate, out_rate);
+        }
+
+        let mut sound_data = SoundData {
             sound_tags: get_sound_tags(&file),
             snd_info: infos,
             nb_sample: nb_sample,
-            al_buffer: buffer_id
+            al_buffer: buffer_id,
+            regions: Vec::new(),
+            loop_points: None,
+            samples: None,
+            source: path.map(|path| (path.to_string(), options.clone())),
+            base_gain: base_gain
+        };
+        let loop_points = match options.loop_points {
+            Some(region) => Ok(Some(region)),
+            None => file.get_loop_points().map(|loop_points| loop_points.map(|(start, end)| {
+                Region::Frames(scale_frames(start, file_rate, out_rate),
+                               scale_frames(end, file_rate, out_rate))
+            }))
         };
         file.close();
+        let loop_points = match loop_points {
+            Ok(loop_points) => loop_points,
+            Err(err)        => return Err(err)
+        };
+
+        if let Some(region) = loop_points {
+            if al::is_extension_present("AL_SOFT_loop_points") {
+                if let Err(err) = sound_data.set_loop_points(Some(region)) {
+                    return Err(err);
+                }
+            } else {
+                sound_data.loop_points = clamp_loop_points(region,
+                                                           sound_data.snd_info.samplerate,
+                                                           frames);
+            }
+        }
+
+        let channels = sound_data.snd_info.channels;
+        for &(ref name, region) in sprites.iter() {
+            let (start, end) = region.to_frames(sound_data.snd_info.samplerate);
+            let end = cmp::min(end, frames);
+            if start >= end {
+                return Err(format!("The region \"{}\" is empty or out of the sound.", name));
+            }
+
+            let mut region_buffer = 0;
+            al::alGenBuffers(1, &mut region_buffer);
+            if let Err(err) = samples.buffer_data_range(region_buffer,
+                                                        start as usize * channels as usize,
+                                                        end as usize * channels as usize,
+                                                        channels,
+                                                        sound_data.snd_info.samplerate) {
+                unsafe { ffi::alDeleteBuffers(1, &mut region_buffer); }
+                return Err(err);
+            }
+            sound_data.regions.push((name.clone(), (start, end), region_buffer));
+        }
+
+        if options.keep_samples {
+            samples.truncate(read);
+            sound_data.samples = Some(samples);
+        }
 
         Ok(sound_data)
     }
 }
 
+/**
+ * Decode a file and apply the conversions of the load options.
+ *
+ * # Return
+ * The samples, the count of samples decoded and the infos of the converted
+ * samples, or Err(String) if the file breaks the load options or the loading
+ * has been cancelled. The file is left open.
+ */
+fn decode_file(file: &mut SndFile,
+               options: &LoadOptions,
+               progress: &Progress) -> Result<(Samples, usize, SndInfo), String> {
+    let mut infos = file.get_sndinfo();
+    if let Err(err) = validate_header(&infos) {
+        return Err(err);
+    }
+
+    let out_channels = match options.channel_mix.get_channels(infos.channels) {
+        Ok(channels) => channels,
+        Err(err) => return Err(err)
+    };
+
+    let file_rate = infos.samplerate;
+    let out_rate = options.get_sample_rate(file_rate);
+    let converting = !options.channel_mix.is_keep() || out_rate != file_rate;
+
+    let sample_type = options.sample_type.resolve();
+    // Decode floats when the samples are converted
+    let decode_type = if converting { SampleType::Float32 } else { sample_type };
+    let channels = infos.channels as usize;
+    let chunk = DECODE_CHUNK_FRAMES * channels;
+
+    // The decoded samples and the converted ones, which can be bigger,
+    // must both fit in the limits
+    let file_channels = infos.channels;
+    let check_limits = |frames: u64| {
+        options.check_limits(frames, file_channels, file_rate, decode_type)
+               .and_then(|_| options.check_limits(scale_frames(frames, file_rate, out_rate),
+                                                  out_channels,
+                                                  out_rate,
+                                                  sample_type))
+    };
+
+    // Reject a length read in the header beyond the limits
+    let known_length = has_known_length(&infos);
+    let nb_sample = if known_length {
+        if let Err(err) = check_limits(infos.frames as u64) {
+            return Err(err);
+        }
+        Some(channels * infos.frames as usize)
+    } else {
+        None
+    };
+    progress.set_total(nb_sample.unwrap_or(0));
+
+    // Grow the buffer one chunk at a time, a header can't be trusted to
+    // allocate the whole length before the samples are read
+    let mut samples = Samples::new(decode_type, cmp::min(nb_sample.unwrap_or(chunk), chunk));
+    let mut read = 0;
+    loop {
+        if progress.is_cancelled() {
+            return Err("Loading cancelled.".into());
+        }
+        let end = match nb_sample {
+            Some(nb_sample) => cmp::min(read + chunk, nb_sample),
+            None            => read + chunk
+        };
+        if end <= read {
+            break;
+        }
+        if samples.len() < end {
+            samples.resize(end);
+        }
+        let count = samples.read_part(file, read, end);
+        let expected = end - read;
+        read += count;
+        progress.set_decoded(read);
+        if let Err(err) = check_limits((read / channels) as u64) {
+            return Err(err);
+        }
+        // Reached the end of the file, maybe earlier than announced
+        if count < expected {
+            break;
+        }
+    }
+    samples.truncate(read);
+    if !known_length {
+        infos.frames = (read / channels) as i64;
+    }
+    if converting {
+        let mut converted = options.channel_mix.apply(&samples.to_f32()[..read],
+                                                      infos.channels);
+        if out_rate != file_rate {
+            converted = resample(&converted, out_channels, file_rate, out_rate);
+        }
+        samples = Samples::from_f32(sample_type, &converted);
+        read = converted.len();
+        infos.channels = out_channels;
+        infos.samplerate = out_rate;
+        infos.frames = (read / out_channels as usize) as i64;
+    }
+    Ok((samples, read, infos))
+}
+
+/// Convert loop points to frames, clamping the end to the sound length.
+fn clamp_loop_points(region: Region, sample_rate: i32, frames: u64) -> Option<(u64, u64)> {
+    let (start, end) = region.to_frames(sample_rate);
+    let end = cmp::min(end, frames);
+    if start < end {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+/// Convert a position in frames from a sample rate to another.
+fn scale_frames(frames: u64, from_rate: i32, to_rate: i32) -> u64 {
+    if from_rate == to_rate {
+        frames
+    } else {
+        (frames as f64 * to_rate as f64 / from_rate as f64).round() as u64
+    }
+}
+
+/// Add a region running from each cue point of the file to the next one.
+fn add_cue_regions(sprites: &mut SpriteSheet,
+                   file: &SndFile,
+                   frames: u64,
+                   file_rate: i32,
+                   out_rate: i32) -> () {
+    let cues = file.get_cues();
+    for (i, cue) in cues.iter().enumerate() {
+        let end = cues.get(i + 1).map_or(frames, |next| scale_frames(next.position,
+                                                                      file_rate,
+                                                                      out_rate));
+        let name = if cue.name.is_empty() {
+            format!("cue{}", cue.index)
+        } else {
+            cue.name.clone()
+        };
+        if sprites.get(&name).is_none() {
+            sprites.add(&name, Region::Frames(scale_frames(cue.position, file_rate, out_rate),
+                                              end));
+        }
+    }
+}
+
+/**
+ * Get the OpenAL buffer holding the samples of a region.
+ *
+ * # Return
+ * The OpenAL internal identifier of the region buffer, or None if there is
+ * no region with this name.
+ */
+#[doc(hidden)]
+pub fn get_region_buffer(s_data: &SoundData, name: &str) -> Option<u32> {
+    s_data.regions.iter()
+        .find(|&&(ref n, _, _)| n == name)
+        .map(|&(_, _, buffer)| buffer)
+}
+
+/**
+ * Load a SoundData, reporting the decoding progress.
+ *
+ * Used by the Loader to load a SoundData in a background thread.
+ */
+#[doc(hidden)]
+pub fn load_with_progress(path: &str,
+                          options: &LoadOptions,
+                          progress: &Progress) -> Result<SoundData, String> {
+    check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+    let file = match SndFile::new(path, Read) {
+        Ok(file) => file,
+        Err(err) => {
+            return Err(format!("Error while loading sound file: {}", err));
+        }
+    };
+
+    SoundData::from_file_with_progress(file, Some(path), options, progress)
+}
 
 /**
  * Get the sound file infos.
@@ -175,9 +832,16 @@ impl AudioTags for SoundData {
 impl Drop for SoundData {
     /// Destroy all the resources attached to the SoundData
     fn drop(&mut self) -> () {
+        oneshot::release_buffer(self.al_buffer);
         unsafe {
             ffi::alDeleteBuffers(1, &mut self.al_buffer);
         }
+        for &mut (_, _, ref mut buffer) in self.regions.iter_mut() {
+            oneshot::release_buffer(*buffer);
+            unsafe {
+                ffi::alDeleteBuffers(1, buffer);
+            }
+        }
     }
 }
 
@@ -186,7 +850,12 @@ mod test {
     #![allow(non_snake_case)]
 
     #[allow(unused_variables)]
-    use sound_data::SoundData;
+    use sound_data::{SoundData, get_sndinfo};
+    use load_options::LoadOptions;
+    use sprite::Region;
+    use channel_mix::{ChannelMix, Downmix};
+    use normalization::Normalization;
+    use sample::SampleType;
 
     #[test]
     #[ignore]
@@ -203,4 +872,184 @@ mod test {
         #![allow(unused_variables)]
         let snd_data = SoundData::new("toto.wav").unwrap();
     }
+
+    #[test]
+    #[ignore]
+    fn sounddata_create_with_options_OK() -> () {
+        #![allow(unused_variables)]
+        let mut options = LoadOptions::default();
+        options.sample_type = SampleType::Float32;
+        let snd_data = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+        options.sample_type = SampleType::UInt8;
+        let snd_data = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_from_bytes_OK() -> () {
+        #![allow(unused_variables)]
+        let snd_data = SoundData::from_bytes(include_bytes!("../res/shot.wav")).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    #[should_panic]
+    fn sounddata_from_bytes_FAIL() -> () {
+        #![allow(unused_variables)]
+        let snd_data = SoundData::from_bytes(b"not a sound file").unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_from_samples_OK() -> () {
+        #![allow(unused_variables)]
+        let snd_data = SoundData::from_samples(&[0i16; 4410], 1, 44100).unwrap();
+        let snd_data = SoundData::from_samples(&[0f32; 4410], 2, 44100).unwrap();
+        let snd_data = SoundData::from_samples(&[128u8; 4410], 1, 22050).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    #[should_panic]
+    fn sounddata_from_samples_FAIL() -> () {
+        #![allow(unused_variables)]
+        let snd_data = SoundData::from_samples(&[0i16; 3], 2, 44100).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_regions_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.sprites.add("start", Region::Frames(0, 100));
+        options.sprites.add("rest", Region::Seconds(0.01, 100.));
+        let snd_data = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+
+        assert!(snd_data.has_region("start"));
+        assert_eq!(snd_data.get_region("start"), Some((0, 100)));
+        assert_eq!(snd_data.get_region_names(), vec!["start".to_string(), "rest".to_string()]);
+    }
+
+    #[test]
+    #[ignore]
+    #[should_panic]
+    fn sounddata_regions_FAIL() -> () {
+        #![allow(unused_variables)]
+        let mut options = LoadOptions::default();
+        options.sprites.add("empty", Region::Frames(100, 100));
+        let snd_data = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_loop_points_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.loop_points = Some(Region::Frames(100, 1000));
+        let mut snd_data = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+        assert_eq!(snd_data.get_loop_points(), Some((100, 1000)));
+
+        snd_data.set_loop_points(None).unwrap();
+        assert_eq!(snd_data.get_loop_points(), None);
+    }
+
+    #[test]
+    #[ignore]
+    #[should_panic]
+    fn sounddata_loop_points_FAIL() -> () {
+        let mut snd_data = SoundData::new("res/shot.wav").unwrap();
+        snd_data.set_loop_points(Some(Region::Frames(1000, 100))).unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_sample_buffer_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.keep_samples = true;
+        let kept = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+        let decoded = SoundData::new("res/shot.wav").unwrap();
+
+        assert_eq!(kept.get_sample_buffer().unwrap().get_frames(),
+                   decoded.get_sample_buffer().unwrap().get_frames());
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_sample_buffer_CONVERTED() -> () {
+        let mut options = LoadOptions::default();
+        options.channel_mix = ChannelMix::Mono(Downmix::Average);
+        options.resample_rate = Some(48000);
+        let snd_data = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+        let buffer = snd_data.get_sample_buffer().unwrap();
+
+        assert_eq!(buffer.get_channels(), 1);
+        assert_eq!(buffer.get_sample_rate(), 48000);
+        assert_eq!(buffer.get_frames() as i64, get_sndinfo(&snd_data).frames);
+    }
+
+    #[test]
+    #[ignore]
+    #[should_panic]
+    fn sounddata_sample_buffer_FAIL() -> () {
+        let snd_data = SoundData::from_samples(&[0i16; 4410], 1, 44100).unwrap();
+        snd_data.get_sample_buffer().unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_channel_mix_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.channel_mix = ChannelMix::Mono(Downmix::EqualPower);
+        let mono = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+        assert_eq!(get_sndinfo(&mono).channels, 1);
+
+        options.channel_mix = ChannelMix::Duplicate(2);
+        let stereo = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+        assert_eq!(get_sndinfo(&stereo).channels, 2);
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_resample_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.resample_rate = Some(48000);
+        let snd_data = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+
+        assert_eq!(get_sndinfo(&snd_data).samplerate, 48000);
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_normalization_OK() -> () {
+        let mut options = LoadOptions::default();
+        options.normalization = Normalization::Peak(-6.);
+        let snd_data = SoundData::new_with_options("res/shot.wav", &options).unwrap();
+
+        assert!(snd_data.get_base_gain() != 1.);
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_limits_converted_FAIL() -> () {
+        let snd_data = SoundData::new("res/shot.wav").unwrap();
+        let infos = get_sndinfo(&snd_data);
+        let mut options = LoadOptions::default();
+        options.max_bytes = Some((infos.frames * infos.channels as i64 * 2) as usize);
+
+        assert!(SoundData::new_with_options("res/shot.wav", &options).is_ok());
+        options.channel_mix = ChannelMix::Duplicate(8);
+        assert!(SoundData::new_with_options("res/shot.wav", &options).is_err());
+        options.channel_mix = ChannelMix::Keep;
+        options.resample_rate = Some(infos.samplerate * 2);
+        assert!(SoundData::new_with_options("res/shot.wav", &options).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn sounddata_time_stretch_OK() -> () {
+        let snd_data = SoundData::new("res/shot.wav").unwrap();
+        let frames = get_sndinfo(&snd_data).frames;
+        let slow = snd_data.time_stretch(0.5).unwrap();
+
+        assert_eq!(get_sndinfo(&slow).frames, frames * 2);
+        assert!(snd_data.time_stretch(4.).is_err());
+    }
 }
@@ -0,0 +1,474 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Named regions of a SoundData, used to pack many sounds in one file.
+
+use std::fs::File;
+use std::io::Read;
+use std::str::Chars;
+use std::iter::Peekable;
+
+/// The bounds of a region, as start and end positions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Region {
+    /// Start and end positions in frames
+    Frames(u64, u64),
+    /// Start and end positions in seconds
+    Seconds(f32, f32)
+}
+
+impl Region {
+    /**
+     * Convert the region bounds to frames.
+     *
+     * # Argument
+     * * `sample_rate` - The sample rate of the SoundData
+     *
+     * # Return
+     * The start and end frames of the region.
+     */
+    pub fn to_frames(&self, sample_rate: i32) -> (u64, u64) {
+        match *self {
+            Region::Frames(start, end) => (start, end),
+            Region::Seconds(start, end) => {
+                let rate = sample_rate as f64;
+                ((start.max(0.) as f64 * rate).round() as u64,
+                 (end.max(0.) as f64 * rate).round() as u64)
+            }
+        }
+    }
+}
+
+/**
+ * A set of named regions.
+ *
+ * The regions are given to a SoundData through the LoadOptions, then a
+ * Sound can play a single region of the SoundData.
+ *
+ * Sprite sheets can be defined in code or loaded from a CSV file with one
+ * `name,start,end[,unit]` line per region, where the unit is `seconds`
+ * (the default) or `frames`, or from a JSON file like
+ * `{ "click": { "start": 0.0, "end": 0.25 }, "pop": [0.25, 0.5] }` where an
+ * object may also hold a `"unit"` key.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{SoundData, Sound, LoadOptions, Region, AudioController};
+ * use std::rc::Rc;
+ * use std::cell::RefCell;
+ *
+ * let mut options = LoadOptions::default();
+ * options.sprites.add("click", Region::Seconds(0., 0.25));
+ * options.sprites.add("pop", Region::Frames(11025, 22050));
+ *
+ * let data = SoundData::new_with_options("path/to/ui.wav", &options).unwrap();
+ * let mut click = Sound::new_with_region(Rc::new(RefCell::new(data)), "click").unwrap();
+ * click.play();
+ * ```
+ */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpriteSheet {
+    regions: Vec<(String, Region)>
+}
+
+impl SpriteSheet {
+    /// Create an empty SpriteSheet.
+    pub fn new() -> SpriteSheet {
+        SpriteSheet { regions: Vec::new() }
+    }
+
+    /**
+     * Load a SpriteSheet from a JSON or CSV file.
+     *
+     * Files with a `.json` extension are parsed as JSON, any other file as
+     * CSV.
+     *
+     * # Argument
+     * * `path` - The path of the sprite definition file
+     *
+     * # Return
+     * A `Result` containing Ok(SpriteSheet) on success, Err(String)
+     * if the file can't be read or parsed.
+     */
+    pub fn from_file(path: &str) -> Result<SpriteSheet, String> {
+        let mut content = String::new();
+        let read = File::open(path).and_then(|mut file| file.read_to_string(&mut content));
+        if let Err(err) = read {
+            return Err(format!("Error while reading sprite file: {}", err));
+        }
+
+        if path.to_lowercase().ends_with(".json") {
+            SpriteSheet::parse_json(&content)
+        } else {
+            SpriteSheet::parse_csv(&content)
+        }
+    }
+
+    /**
+     * Parse a CSV sprite definition.
+     *
+     * Each line is `name,start,end[,unit]`, empty lines and lines starting
+     * with `#` are ignored.
+     */
+    pub fn parse_csv(content: &str) -> Result<SpriteSheet, String> {
+        let mut sheet = SpriteSheet::new();
+
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+            if fields.len() < 3 || fields.len() > 4 {
+                return Err(format!("Invalid sprite definition at line {}.", number + 1));
+            }
+            let unit = if fields.len() == 4 { fields[3] } else { "seconds" };
+            match parse_region(fields[1], fields[2], unit) {
+                Some(region) => sheet.add(fields[0], region),
+                None => return Err(format!("Invalid sprite region at line {}.", number + 1))
+            }
+        }
+
+        Ok(sheet)
+    }
+
+    /**
+     * Parse a JSON sprite definition.
+     *
+     * The document is an object mapping each name to either a
+     * `[start, end]` array in seconds or an object with `start`, `end` and
+     * an optional `unit` key.
+     */
+    pub fn parse_json(content: &str) -> Result<SpriteSheet, String> {
+        let mut parser = JsonParser { chars: content.chars().peekable() };
+        let value = match parser.parse_document() {
+            Some(value) => value,
+            None => return Err("Invalid JSON sprite definition.".into())
+        };
+
+        let entries = match value {
+            Json::Object(entries) => entries,
+            _ => return Err("A JSON sprite definition must be an object.".into())
+        };
+
+        let mut sheet = SpriteSheet::new();
+        for (name, value) in entries {
+            let region = match value {
+                Json::Array(ref bounds) if bounds.len() == 2 => {
+                    match (&bounds[0], &bounds[1]) {
+                        (&Json::Number(start), &Json::Number(end)) =>
+                            Some(Region::Seconds(start as f32, end as f32)),
+                        _ => None
+                    }
+                },
+                Json::Object(ref fields) => {
+                    let mut start = None;
+                    let mut end = None;
+                    let mut unit = "seconds".to_string();
+                    for &(ref key, ref field) in fields {
+                        match (key.as_str(), field) {
+                            ("start", &Json::Number(n)) => start = Some(n),
+                            ("end", &Json::Number(n)) => end = Some(n),
+                            ("unit", &Json::String(ref s)) => unit = s.clone(),
+                            _ => {}
+                        }
+                    }
+                    match (start, end) {
+                        (Some(start), Some(end)) =>
+                            parse_region(&start.to_string(), &end.to_string(), &unit),
+                        _ => None
+                    }
+                },
+                _ => None
+            };
+            match region {
+                Some(region) => sheet.add(&name, region),
+                None => return Err(format!("Invalid sprite region \"{}\".", name))
+            }
+        }
+
+        Ok(sheet)
+    }
+
+    /**
+     * Add a region, replacing any region with the same name.
+     *
+     * # Arguments
+     * * `name` - The name of the region
+     * * `region` - The bounds of the region
+     */
+    pub fn add(&mut self, name: &str, region: Region) -> () {
+        match self.regions.iter().position(|&(ref n, _)| n == name) {
+            Some(index) => self.regions[index].1 = region,
+            None => self.regions.push((name.to_string(), region))
+        }
+    }
+
+    /// Get the region with the given name.
+    pub fn get(&self, name: &str) -> Option<Region> {
+        self.regions.iter().find(|&&(ref n, _)| n == name).map(|&(_, region)| region)
+    }
+
+    /// Get the names of the regions, in definition order.
+    pub fn names(&self) -> Vec<String> {
+        self.regions.iter().map(|&(ref name, _)| name.clone()).collect()
+    }
+
+    /// Get the number of regions.
+    pub fn len(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Check if the SpriteSheet holds no region.
+    pub fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    /// Iterate over the names and regions.
+    pub fn iter(&self) -> ::std::slice::Iter<'_, (String, Region)> {
+        self.regions.iter()
+    }
+}
+
+/// Build a region from its textual bounds and unit.
+fn parse_region(start: &str, end: &str, unit: &str) -> Option<Region> {
+    match unit {
+        "frames" => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) => Some(Region::Frames(start, end)),
+            _ => None
+        },
+        "seconds" => match (start.parse::<f32>(), end.parse::<f32>()) {
+            (Ok(start), Ok(end)) if start >= 0. && end >= 0. => Some(Region::Seconds(start, end)),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// The subset of JSON values used by the sprite definitions.
+enum Json {
+    Object(Vec<(String, Json)>),
+    Array(Vec<Json>),
+    String(String),
+    Number(f64),
+    Literal
+}
+
+/// A small recursive descent JSON parser.
+struct JsonParser<'a> {
+    chars: Peekable<Chars<'a>>
+}
+
+impl<'a> JsonParser<'a> {
+    fn parse_document(&mut self) -> Option<Json> {
+        let value = self.parse_value();
+        self.skip_whitespace();
+        match self.chars.next() {
+            None => value,
+            Some(_) => None
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> () {
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Option<()> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(c) if c == expected => Some(()),
+            _ => None
+        }
+    }
+
+    fn parse_value(&mut self) -> Option<Json> {
+        self.skip_whitespace();
+        match self.chars.peek().cloned() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => self.parse_string().map(Json::String),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            Some(c) if c.is_alphabetic() => {
+                let mut word = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if !c.is_alphabetic() {
+                        break;
+                    }
+                    word.push(c);
+                    self.chars.next();
+                }
+                match word.as_str() {
+                    "true" | "false" | "null" => Some(Json::Literal),
+                    _ => None
+                }
+            },
+            _ => None
+        }
+    }
+
+    fn parse_object(&mut self) -> Option<Json> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.chars.next();
+            return Some(Json::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            self.expect(':')?;
+            let value = self.parse_value()?;
+            entries.push((key, value));
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some('}') => return Some(Json::Object(entries)),
+                _ => return None
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Option<Json> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.chars.next();
+            return Some(Json::Array(values));
+        }
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(']') => return Some(Json::Array(values)),
+                _ => return None
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Option<String> {
+        if self.chars.next() != Some('"') {
+            return None;
+        }
+        let mut string = String::new();
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(string),
+                '\\' => match self.chars.next()? {
+                    'n' => string.push('\n'),
+                    't' => string.push('\t'),
+                    'r' => string.push('\r'),
+                    'u' => {
+                        let code: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        let code = u32::from_str_radix(&code, 16).ok()?;
+                        string.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                    },
+                    c => string.push(c)
+                },
+                c => string.push(c)
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<Json> {
+        let mut number = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !(c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E') {
+                break;
+            }
+            number.push(c);
+            self.chars.next();
+        }
+        number.parse::<f64>().ok().map(Json::Number)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use sprite::{SpriteSheet, Region};
+
+    #[test]
+    fn sprite_region_to_frames() -> () {
+        assert_eq!(Region::Frames(10, 20).to_frames(44100), (10, 20));
+        assert_eq!(Region::Seconds(0.5, 1.).to_frames(44100), (22050, 44100));
+    }
+
+    #[test]
+    fn sprite_parse_csv_OK() -> () {
+        let sheet = SpriteSheet::parse_csv("# name,start,end\n\
+                                            click, 0, 0.25\n\
+                                            \n\
+                                            pop,11025,22050,frames\n").unwrap();
+
+        assert_eq!(sheet.names(), vec!["click".to_string(), "pop".to_string()]);
+        assert_eq!(sheet.get("click"), Some(Region::Seconds(0., 0.25)));
+        assert_eq!(sheet.get("pop"), Some(Region::Frames(11025, 22050)));
+    }
+
+    #[test]
+    fn sprite_parse_csv_FAIL() -> () {
+        assert!(SpriteSheet::parse_csv("click,0").is_err());
+        assert!(SpriteSheet::parse_csv("click,0,a").is_err());
+        assert!(SpriteSheet::parse_csv("click,0,1,bars").is_err());
+    }
+
+    #[test]
+    fn sprite_parse_json_OK() -> () {
+        let sheet = SpriteSheet::parse_json(r#"{
+            "click": { "start": 0, "end": 0.25 },
+            "pop": { "start": 11025, "end": 22050, "unit": "frames" },
+            "bell": [0.5, 1.5]
+        }"#).unwrap();
+
+        assert_eq!(sheet.len(), 3);
+        assert_eq!(sheet.get("click"), Some(Region::Seconds(0., 0.25)));
+        assert_eq!(sheet.get("pop"), Some(Region::Frames(11025, 22050)));
+        assert_eq!(sheet.get("bell"), Some(Region::Seconds(0.5, 1.5)));
+    }
+
+    #[test]
+    fn sprite_parse_json_FAIL() -> () {
+        assert!(SpriteSheet::parse_json("[0, 1]").is_err());
+        assert!(SpriteSheet::parse_json(r#"{ "click": [0] }"#).is_err());
+        assert!(SpriteSheet::parse_json(r#"{ "click": [0, 1] "#).is_err());
+    }
+
+    #[test]
+    fn sprite_add_replaces() -> () {
+        let mut sheet = SpriteSheet::new();
+        sheet.add("click", Region::Frames(0, 10));
+        sheet.add("click", Region::Frames(5, 10));
+
+        assert_eq!(sheet.len(), 1);
+        assert_eq!(sheet.get("click"), Some(Region::Frames(5, 10)));
+    }
+}
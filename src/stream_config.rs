@@ -0,0 +1,201 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! The configuration and the statistics of the streaming of a Music.
+
+use std::time::Duration;
+
+/**
+ * The configuration of the streaming of a Music.
+ *
+ * A Music is decoded in chunks queued on its OpenAL source, and a single
+ * thread shared by all the Musics wakes up regularly to refill the chunks
+ * already played. More or longer chunks protect against underruns, e.g. when
+ * the application stalls, at the cost of latency when the Music is started
+ * and of memory.
+ *
+ * # Example
+ * ```no_run
+ * use std::time::Duration;
+ * use ears::{Music, LoadOptions, StreamConfig, AudioController};
+ *
+ * let mut options = LoadOptions::default();
+ * options.stream_config = StreamConfig {
+ *     buffer_count: 6,
+ *     chunk_duration: Duration::from_millis(100),
+ *     poll_interval: Duration::from_millis(20)
+ * };
+ * let mut msc = Music::new_with_options("path/to/music.ogg", &options).unwrap();
+ * msc.play();
+ * ```
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StreamConfig {
+    /// The number of chunks queued on the source, at least 2
+    pub buffer_count: usize,
+    /// The duration of the audio decoded in each chunk
+    pub chunk_duration: Duration,
+    /// The time between two refills of the Music by the streaming thread
+    pub poll_interval: Duration
+}
+
+impl Default for StreamConfig {
+    fn default() -> StreamConfig {
+        StreamConfig {
+            buffer_count: 4,
+            chunk_duration: Duration::from_millis(250),
+            poll_interval: Duration::from_millis(50)
+        }
+    }
+}
+
+impl StreamConfig {
+    /**
+     * Check that the configuration can stream without starving.
+     *
+     * # Return
+     * Ok(()) if the configuration is valid, Err(String) describing the
+     * invalid value otherwise.
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        if self.buffer_count < 2 {
+            return Err(format!("A Music needs at least 2 buffers, got {}.", self.buffer_count));
+        }
+        if self.chunk_duration == Duration::from_millis(0) {
+            return Err("The chunk duration of a Music can't be null.".into());
+        }
+        if self.buffer_count > u32::max_value() as usize {
+            return Err(format!("Too many buffers for a Music, got {}.", self.buffer_count));
+        }
+        // The buffers left playing while the one played is refilled
+        let ahead = match self.chunk_duration.checked_mul(self.buffer_count as u32 - 1) {
+            Some(ahead) => ahead,
+            None => return Err("The buffers of a Music are too long.".into())
+        };
+        if self.poll_interval >= ahead {
+            return Err("The poll interval is too long to refill the buffers in time.".into());
+        }
+        Ok(())
+    }
+
+    /**
+     * Get the number of samples decoded in each chunk.
+     *
+     * Always a whole number of frames, and at least one frame.
+     *
+     * # Arguments
+     * * `sample_rate` - The sample rate of the file
+     * * `channels` - The number of channels of the file
+     */
+    #[doc(hidden)]
+    pub fn get_chunk_samples(&self, sample_rate: i32, channels: i32) -> usize {
+        let seconds = self.chunk_duration.as_secs() as f64
+                      + self.chunk_duration.subsec_nanos() as f64 / 1e9;
+        let frames = (seconds * sample_rate as f64).round() as usize;
+        if frames == 0 { channels as usize } else { frames * channels as usize }
+    }
+}
+
+/// An underrun of a Music, when its queued chunks ran out before being refilled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Underrun {
+    /// The frame of the file the streaming thread was decoding
+    pub position: u64,
+    /// True if the Music was restarted, false if it stayed stopped
+    pub recovered: bool
+}
+
+/// The statistics of the underruns of a Music.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UnderrunStats {
+    /// The number of underruns
+    pub count: usize,
+    /// The number of underruns after which the Music was restarted
+    pub recovered: usize
+}
+
+impl UnderrunStats {
+    /// Count an underrun.
+    #[doc(hidden)]
+    pub fn add(&mut self, underrun: &Underrun) -> () {
+        self.count += 1;
+        if underrun.recovered {
+            self.recovered += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use std::time::Duration;
+    use stream_config::{StreamConfig, Underrun, UnderrunStats};
+
+    #[test]
+    fn streamconfig_validate_OK() -> () {
+        assert!(StreamConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn streamconfig_validate_FAIL() -> () {
+        let mut config = StreamConfig::default();
+        config.buffer_count = 1;
+        assert!(config.validate().is_err());
+
+        let mut config = StreamConfig::default();
+        config.chunk_duration = Duration::from_millis(0);
+        assert!(config.validate().is_err());
+
+        let mut config = StreamConfig::default();
+        config.poll_interval = Duration::from_secs(1);
+        assert!(config.validate().is_err());
+
+        let mut config = StreamConfig::default();
+        config.chunk_duration = Duration::from_secs(u64::max_value());
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn streamconfig_get_chunk_samples_OK() -> () {
+        let config = StreamConfig::default();
+        assert_eq!(config.get_chunk_samples(44100, 2), 11025 * 2);
+        assert_eq!(config.get_chunk_samples(48000, 1), 12000);
+    }
+
+    #[test]
+    fn streamconfig_get_chunk_samples_MIN() -> () {
+        let mut config = StreamConfig::default();
+        config.chunk_duration = Duration::from_millis(1);
+        assert_eq!(config.get_chunk_samples(100, 2), 2);
+    }
+
+    #[test]
+    fn underrunstats_add_OK() -> () {
+        let mut stats = UnderrunStats::default();
+        stats.add(&Underrun { position: 100, recovered: true });
+        stats.add(&Underrun { position: 200, recovered: false });
+
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.recovered, 1);
+    }
+}
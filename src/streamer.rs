@@ -0,0 +1,472 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! The thread refilling the buffers of all the streamed sources.
+//!
+//! The streams register with a single thread, which wakes up when the
+//! earliest of them must be refilled and sleeps while none is registered.
+
+use std::thread;
+use std::thread::ThreadId;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Mutex, MutexGuard, Condvar};
+use std::time::{Duration, Instant};
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+
+lazy_static! {
+    static ref STREAMER: Streamer = Streamer {
+        streams: Mutex::new(Streams {
+            entries: Vec::new(),
+            next_id: 0,
+            running: false,
+            thread: None,
+            updating: Vec::new(),
+            cancelled: Vec::new()
+        }),
+        update: Mutex::new(()),
+        wakeup: Condvar::new(),
+        updated: Condvar::new()
+    };
+}
+
+/// A source whose buffers are refilled by the streaming thread.
+pub trait Stream: Send {
+    /**
+     * Refill the buffers already played.
+     *
+     * # Return
+     * False once the stream is over, to drop it.
+     */
+    fn update(&mut self) -> bool;
+
+    /// Get the time to wait between two updates.
+    fn get_poll_interval(&self) -> Duration;
+}
+
+/**
+ * The samples of a source streamed chunk after chunk, decoded from a file or
+ * generated.
+ */
+pub trait Producer: Send {
+    /**
+     * Upload the next chunk in an OpenAL buffer.
+     *
+     * # Return
+     * False if the stream has nothing more to play, the buffer isn't queued
+     * then.
+     */
+    fn produce(&mut self, buffer: u32) -> bool;
+
+    /// Called when the oldest queued buffer, played, is unqueued.
+    fn on_unqueued(&mut self) -> () {}
+
+    /**
+     * Called when every queued buffer was played before being refilled.
+     *
+     * # Argument
+     * * `recovered` - True if the source was restarted
+     */
+    fn on_underrun(&mut self, _recovered: bool) -> () {}
+}
+
+/**
+ * A source whose buffers are queued and refilled by a Producer.
+ *
+ * The source is restarted when it ran out of buffers while the Producer had
+ * more to play, and the stream is over once it did play everything.
+ */
+pub struct QueueStream<P> {
+    al_source: u32,
+    producer: P,
+    poll_interval: Duration
+}
+
+impl<P: Producer> QueueStream<P> {
+    /**
+     * Fill and queue the buffers, and play the source.
+     *
+     * # Return
+     * None if the Producer had nothing to play.
+     */
+    pub fn start(al_source: u32,
+                 buffers: &[u32],
+                 producer: P,
+                 poll_interval: Duration) -> Option<QueueStream<P>> {
+        let mut stream = QueueStream {
+            al_source: al_source,
+            producer: producer,
+            poll_interval: poll_interval
+        };
+        let mut queued = 0;
+        for buffer in buffers.iter() {
+            if !stream.producer.produce(*buffer) {
+                break;
+            }
+            al::alSourceQueueBuffers(al_source, 1, buffer);
+            queued += 1;
+        }
+        if queued == 0 {
+            return None;
+        }
+        al::alSourcePlay(al_source);
+        Some(stream)
+    }
+}
+
+impl<P: Producer> Stream for QueueStream<P> {
+    fn update(&mut self) -> bool {
+        let status = al::alGetState(self.al_source);
+        if status == ffi::AL_PAUSED {
+            return true;
+        }
+        // The source stops by itself once every queued buffer is played
+        let starved = status == ffi::AL_STOPPED;
+
+        let mut processed = 0;
+        let mut buffer = 0;
+        let mut refilled = 0;
+        al::alGetSourcei(self.al_source, ffi::AL_BUFFERS_PROCESSED, &mut processed);
+        while processed > 0 {
+            al::alSourceUnqueueBuffers(self.al_source, 1, &mut buffer);
+            self.producer.on_unqueued();
+            if self.producer.produce(buffer) {
+                al::alSourceQueueBuffers(self.al_source, 1, &buffer);
+                refilled += 1;
+            }
+            processed -= 1;
+        }
+
+        if starved {
+            // Everything was played: it's the end of the stream
+            if refilled == 0 {
+                return false;
+            }
+            // Otherwise the refill was late
+            al::alSourcePlay(self.al_source);
+            self.producer.on_underrun(al::openal_has_error().is_none());
+        }
+        true
+    }
+
+    fn get_poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+}
+
+impl<P> Drop for QueueStream<P> {
+    /// Release the buffers of the source.
+    fn drop(&mut self) -> () {
+        al::alSourceStop(self.al_source);
+        al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+    }
+}
+
+/// A function run by the streaming thread at a given time.
+struct Timer<F> {
+    time: Instant,
+    action: Option<F>
+}
+
+impl<F: FnOnce() + Send> Stream for Timer<F> {
+    fn update(&mut self) -> bool {
+        if Instant::now() < self.time {
+            return true;
+        }
+        if let Some(action) = self.action.take() {
+            action();
+        }
+        false
+    }
+
+    fn get_poll_interval(&self) -> Duration {
+        let now = Instant::now();
+        if self.time > now {
+            self.time - now
+        } else {
+            Duration::from_millis(0)
+        }
+    }
+}
+
+/// The identifier of a registered stream.
+pub type StreamId = usize;
+
+struct Entry {
+    id: StreamId,
+    stream: Box<dyn Stream>,
+    next_update: Instant
+}
+
+struct Streams {
+    entries: Vec<Entry>,
+    next_id: StreamId,
+    /// True once the streaming thread is spawned
+    running: bool,
+    /// The streaming thread, once spawned
+    thread: Option<ThreadId>,
+    /// The streams taken out of the entries to be updated
+    updating: Vec<StreamId>,
+    /// The streams unregistered while they were updated
+    cancelled: Vec<StreamId>
+}
+
+struct Streamer {
+    streams: Mutex<Streams>,
+    /// Held while a stream is updated
+    update: Mutex<()>,
+    /// Wake up the thread when a stream is registered
+    wakeup: Condvar,
+    /// Wake up the unregister calls waiting for the end of an update
+    updated: Condvar
+}
+
+/// Lock the streams, a panic while they were locked leaving them usable.
+fn lock_streams() -> MutexGuard<'static, Streams> {
+    STREAMER.streams.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/**
+ * Register a stream, updated from now on until it's over or unregistered.
+ *
+ * # Return
+ * The identifier used to unregister the stream.
+ */
+pub fn register(stream: Box<dyn Stream>) -> StreamId {
+    let mut streams = lock_streams();
+    let id = streams.next_id;
+    streams.next_id += 1;
+    let next_update = Instant::now() + stream.get_poll_interval();
+    streams.entries.push(Entry {
+        id: id,
+        stream: stream,
+        next_update: next_update
+    });
+    if !streams.running {
+        streams.running = true;
+        streams.thread = Some(thread::spawn(run).thread().id());
+    }
+    STREAMER.wakeup.notify_one();
+    id
+}
+
+/**
+ * Unregister a stream and drop it.
+ *
+ * The stream isn't being updated once this returns, it does nothing if the
+ * stream is already over. Called by a stream being updated, the stream
+ * unregistered is dropped once the update is over.
+ */
+pub fn unregister(id: StreamId) -> () {
+    let entry = {
+        let mut streams = lock_streams();
+        match streams.entries.iter().position(|entry| entry.id == id) {
+            Some(index) => Some(streams.entries.remove(index)),
+            None        => {
+                // Wait for the end of the update, which drops the stream
+                if streams.updating.contains(&id) {
+                    streams.cancelled.push(id);
+                    if streams.thread != Some(thread::current().id()) {
+                        while streams.updating.contains(&id) {
+                            streams = STREAMER.updated.wait(streams)
+                                                      .unwrap_or_else(|err| err.into_inner());
+                        }
+                    }
+                }
+                None
+            }
+        }
+    };
+    // Drop the stream out of the lock
+    drop(entry);
+}
+
+/**
+ * Run a function on the streaming thread at a given time.
+ *
+ * The function runs as a stream update, so it must not call synchronized.
+ *
+ * # Return
+ * The identifier used to unregister the timer, cancelling the function.
+ */
+pub fn schedule<F: FnOnce() + Send + 'static>(time: Instant, action: F) -> StreamId {
+    register(Box::new(Timer {
+        time: time,
+        action: Some(action)
+    }))
+}
+
+/**
+ * Run a function while no stream is updated.
+ *
+ * Used to read the state of a source consistently with the state of its
+ * stream. It can't be called from a stream update, a Generator or a
+ * scheduled function.
+ */
+pub fn synchronized<T, F: FnOnce() -> T>(function: F) -> T {
+    let _update = STREAMER.update.lock().unwrap_or_else(|err| err.into_inner());
+    function()
+}
+
+/// Check if a stream is still updated.
+pub fn is_registered(id: StreamId) -> bool {
+    let streams = lock_streams();
+    (streams.entries.iter().any(|entry| entry.id == id) || streams.updating.contains(&id))
+        && !streams.cancelled.contains(&id)
+}
+
+/**
+ * Update a stream.
+ *
+ * # Return
+ * False if the stream is over or panicked.
+ */
+fn update(stream: &mut Box<dyn Stream>) -> bool {
+    let _update = STREAMER.update.lock().unwrap_or_else(|err| err.into_inner());
+    panic::catch_unwind(AssertUnwindSafe(|| stream.update())).unwrap_or(false)
+}
+
+/// The loop of the streaming thread.
+fn run() -> () {
+    if let Err(err) = OpenAlData::check_al_context() {
+        println!("{}", err);
+    }
+
+    let mut streams = lock_streams();
+    loop {
+        // Update the due streams out of the lock, so they can be registered
+        // and unregistered meanwhile
+        let now = Instant::now();
+        let (due, waiting): (Vec<Entry>, Vec<Entry>) = streams.entries.drain(..)
+                                                          .partition(|entry| entry.next_update <= now);
+        streams.entries = waiting;
+        streams.updating = due.iter().map(|entry| entry.id).collect();
+        drop(streams);
+
+        let mut kept = Vec::new();
+        let mut over = Vec::new();
+        for mut entry in due.into_iter() {
+            if update(&mut entry.stream) {
+                entry.next_update = now + entry.stream.get_poll_interval();
+                kept.push(entry);
+            } else {
+                over.push(entry);
+            }
+        }
+        streams = lock_streams();
+        for entry in kept.into_iter() {
+            if streams.cancelled.contains(&entry.id) {
+                over.push(entry);
+            } else {
+                streams.entries.push(entry);
+            }
+        }
+        drop(streams);
+
+        // Drop the streams out of the lock, before waking up unregister
+        drop(over);
+        streams = lock_streams();
+        streams.updating.clear();
+        streams.cancelled.clear();
+        STREAMER.updated.notify_all();
+
+        streams = match streams.entries.iter().map(|entry| entry.next_update).min() {
+            Some(next_update) => {
+                let now = Instant::now();
+                let timeout = if next_update > now {
+                    next_update - now
+                } else {
+                    Duration::from_millis(0)
+                };
+                STREAMER.wakeup.wait_timeout(streams, timeout)
+                               .unwrap_or_else(|err| err.into_inner()).0
+            },
+            None => STREAMER.wakeup.wait(streams).unwrap_or_else(|err| err.into_inner())
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use streamer;
+    use streamer::Stream;
+
+    struct Counter {
+        updates: Arc<AtomicUsize>,
+        panics: bool
+    }
+
+    impl Stream for Counter {
+        fn update(&mut self) -> bool {
+            self.updates.fetch_add(1, Ordering::SeqCst);
+            if self.panics {
+                panic!("stream failure");
+            }
+            // A slow update, like decoding a chunk
+            thread::sleep(Duration::from_millis(20));
+            true
+        }
+
+        fn get_poll_interval(&self) -> Duration {
+            Duration::from_millis(1)
+        }
+    }
+
+    #[test]
+    fn streamer_panic_OK() -> () {
+        let updates = Arc::new(AtomicUsize::new(0));
+        let id = streamer::register(Box::new(Counter { updates: updates.clone(), panics: true }));
+        thread::sleep(Duration::from_millis(100));
+
+        assert_eq!(updates.load(Ordering::SeqCst), 1);
+        assert!(!streamer::is_registered(id));
+
+        // The streamer still works
+        let fired = Arc::new(AtomicBool::new(false));
+        let flag = fired.clone();
+        streamer::schedule(Instant::now(), move || flag.store(true, Ordering::SeqCst));
+        thread::sleep(Duration::from_millis(100));
+        assert!(fired.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn streamer_unregister_OK() -> () {
+        let updates = Arc::new(AtomicUsize::new(0));
+        let id = streamer::register(Box::new(Counter { updates: updates.clone(), panics: false }));
+        thread::sleep(Duration::from_millis(50));
+
+        streamer::unregister(id);
+        let count = updates.load(Ordering::SeqCst);
+        assert!(count > 0);
+        assert!(!streamer::is_registered(id));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(updates.load(Ordering::SeqCst), count);
+    }
+}
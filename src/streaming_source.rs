@@ -0,0 +1,740 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! A source playing the chunks of samples queued by the application.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+
+use internal::OpenAlData;
+use openal::{ffi, al};
+use states::State;
+use states::State::{Initial, Playing, Paused, Stopped};
+use audio_controller::AudioController;
+use sample::Sample;
+use sound_data;
+use sound_data::SoundData;
+
+/// A buffer queued on a StreamingSource.
+struct Queued {
+    buffer: u32,
+    /// The SoundData owning the buffer, None for a buffer of the source
+    sound_data: Option<Rc<RefCell<SoundData>>>
+}
+
+/**
+ * Play chunks of samples queued one after the other.
+ *
+ * The application queues the chunks, e.g. decoded voice or the audio track
+ * of a video, and recycles the ones played. The chunks follow each other
+ * without a gap, as long as a new one is queued before the previous ones are
+ * all played, otherwise the source stops and must be played again.
+ *
+ * All the chunks queued at the same time must have the same channel count
+ * and sample type.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{StreamingSource, AudioController};
+ *
+ * let mut source = StreamingSource::new().unwrap();
+ * let chunk = vec![0i16; 4410];
+ * source.queue(&chunk, 1, 44100).unwrap();
+ * source.queue(&chunk, 1, 44100).unwrap();
+ * source.play();
+ *
+ * loop {
+ *     // Keep two chunks ahead of the playback
+ *     if source.get_queued() - source.get_processed() < 2 {
+ *         source.queue(&chunk, 1, 44100).unwrap();
+ *     }
+ * }
+ * ```
+ */
+pub struct StreamingSource {
+    /// The internal OpenAL source identifier
+    al_source: u32,
+    /// The buffers created by the source
+    al_buffers: Vec<u32>,
+    /// The buffers of the source ready to be filled
+    free: Vec<u32>,
+    /// The buffers queued, in the order they are played
+    queued: VecDeque<Queued>
+}
+
+impl StreamingSource {
+    /**
+     * Create a new StreamingSource.
+     *
+     * # Return
+     * A `Result` containing Ok(StreamingSource) on success, Err(String)
+     * if there has been an error.
+     */
+    pub fn new() -> Result<StreamingSource, String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        let mut source_id = 0;
+        al::alGenSources(1, &mut source_id);
+
+        if let Some(err) = al::openal_has_error() {
+            return Err(format!("Internal OpenAL error: {}", err));
+        };
+
+        Ok(StreamingSource {
+            al_source: source_id,
+            al_buffers: Vec::new(),
+            free: Vec::new(),
+            queued: VecDeque::new()
+        })
+    }
+
+    /**
+     * Queue a chunk of samples.
+     *
+     * The buffers already played are recycled first, a new buffer is
+     * created only if none is free.
+     *
+     * # Arguments
+     * * `samples` - The interleaved samples, `i16`, `f32` or `u8`
+     * * `channels` - The channel count of the samples
+     * * `sample_rate` - The sample rate of the samples
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the chunk can't be queued, e.g. if
+     * its format differs from the chunks already queued.
+     */
+    pub fn queue<S: Sample>(&mut self,
+                            samples: &[S],
+                            channels: i32,
+                            sample_rate: i32) -> Result<(), String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        self.recycle();
+        let buffer = match self.free.pop() {
+            Some(buffer) => buffer,
+            None => {
+                let mut buffer = 0;
+                al::alGenBuffers(1, &mut buffer);
+                if let Some(err) = al::openal_has_error() {
+                    return Err(format!("Internal OpenAL error: {}", err));
+                }
+                self.al_buffers.push(buffer);
+                buffer
+            }
+        };
+
+        if let Err(err) = S::buffer_data(buffer, samples, channels, sample_rate) {
+            self.free.push(buffer);
+            return Err(err);
+        }
+        self.queue_buffer(buffer, None)
+    }
+
+    /**
+     * Queue the samples of a SoundData, or of one of its regions.
+     *
+     * The SoundData is kept alive until its samples are played and
+     * recycled. Its normalization gain isn't applied.
+     *
+     * # Arguments
+     * * `sound_data` - The SoundData to play
+     * * `region` - The name of the region to play, or None for the whole
+     * SoundData
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the region doesn't exist or the
+     * format differs from the chunks already queued.
+     */
+    pub fn queue_sound_data(&mut self,
+                            sound_data: Rc<RefCell<SoundData>>,
+                            region: Option<&str>) -> Result<(), String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        let buffer = {
+            let data = sound_data.borrow();
+            match region {
+                Some(name) => match sound_data::get_region_buffer(&*data, name) {
+                    Some(buffer) => buffer,
+                    None => return Err(format!("The SoundData has no region named {}.", name))
+                },
+                None => sound_data::get_buffer(&*data)
+            }
+        };
+        self.recycle();
+        self.queue_buffer(buffer, Some(sound_data))
+    }
+
+    fn queue_buffer(&mut self,
+                    buffer: u32,
+                    sound_data: Option<Rc<RefCell<SoundData>>>) -> Result<(), String> {
+        al::alSourceQueueBuffers(self.al_source, 1, &buffer);
+        if let Some(err) = al::openal_has_error() {
+            if sound_data.is_none() {
+                self.free.push(buffer);
+            }
+            return Err(format!("Internal OpenAL error: {}", err));
+        }
+        self.queued.push_back(Queued {
+            buffer: buffer,
+            sound_data: sound_data
+        });
+        Ok(())
+    }
+
+    /**
+     * Get the number of chunks queued, including the ones already played
+     * but not yet recycled.
+     */
+    pub fn get_queued(&self) -> usize {
+        check_openal_context!(0);
+
+        let mut queued = 0;
+        al::alGetSourcei(self.al_source, ffi::AL_BUFFERS_QUEUED, &mut queued);
+        queued as usize
+    }
+
+    /// Get the number of chunks played and not yet recycled.
+    pub fn get_processed(&self) -> usize {
+        check_openal_context!(0);
+
+        let mut processed = 0;
+        al::alGetSourcei(self.al_source, ffi::AL_BUFFERS_PROCESSED, &mut processed);
+        processed as usize
+    }
+
+    /**
+     * Unqueue the chunks already played, to reuse their buffers.
+     *
+     * Queuing a chunk recycles the chunks played, calling it is only needed
+     * to release the SoundDatas played sooner.
+     *
+     * # Return
+     * The number of chunks recycled.
+     */
+    pub fn recycle(&mut self) -> usize {
+        check_openal_context!(0);
+
+        let processed = self.get_processed();
+        for _ in 0..processed {
+            let mut buffer = 0;
+            al::alSourceUnqueueBuffers(self.al_source, 1, &mut buffer);
+            self.release(buffer);
+        }
+        processed
+    }
+
+    /// Take back a buffer unqueued from the source.
+    fn release(&mut self, buffer: u32) -> () {
+        if let Some(index) = self.queued.iter().position(|queued| queued.buffer == buffer) {
+            if let Some(queued) = self.queued.remove(index) {
+                if queued.sound_data.is_none() {
+                    self.free.push(buffer);
+                }
+            }
+        }
+    }
+}
+
+impl AudioController for StreamingSource {
+    /**
+     * Play or resume the StreamingSource.
+     *
+     * A StreamingSource stopped because every chunk was played restarts
+     * with the chunks queued since.
+     */
+    fn play(&mut self) -> () {
+        check_openal_context!(());
+
+        if self.get_state() == Stopped {
+            self.recycle();
+        }
+        al::alSourcePlay(self.al_source);
+    }
+
+    /**
+     * Pause the StreamingSource.
+     */
+    fn pause(&mut self) -> () {
+        check_openal_context!(());
+
+        al::alSourcePause(self.al_source)
+    }
+
+    /**
+     * Stop the StreamingSource.
+     *
+     * The chunks queued and not yet played are dropped.
+     */
+    fn stop(&mut self) -> () {
+        check_openal_context!(());
+
+        al::alSourceStop(self.al_source);
+        self.recycle();
+    }
+
+    /**
+     * Check if the StreamingSource is playing or not.
+     *
+     * # Return
+     * True if the StreamingSource is playing, false otherwise.
+     */
+    fn is_playing(&self) -> bool {
+        match self.get_state() {
+            Playing     => true,
+            _           => false
+        }
+    }
+
+    /**
+     * Get the current state of the StreamingSource
+     *
+     * # Return
+     * The state of the streaming source as a variant of the enum State
+     */
+    fn get_state(&self) -> State {
+        check_openal_context!(Initial);
+
+        let state  = al::alGetState(self.al_source);
+
+        match state {
+            ffi::AL_INITIAL => Initial,
+            ffi::AL_PLAYING => Playing,
+            ffi::AL_PAUSED  => Paused,
+            ffi::AL_STOPPED => Stopped,
+            _               => unreachable!()
+        }
+    }
+
+    /**
+     * Set the volume of the StreamingSource.
+     *
+     * A value of 1.0 means unattenuated. Each division by 2 equals an attenuation
+     * of about -6dB. Each multiplicaton by 2 equals an amplification of about
+     * +6dB.
+     *
+     * # Argument
+     * * `volume` - The volume of the StreamingSource, should be between 0. and 1.
+     */
+    fn set_volume(&mut self, volume: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_GAIN, volume);
+    }
+
+    /**
+     * Get the volume of the StreamingSource.
+     *
+     * # Return
+     * The volume of the StreamingSource between 0. and 1.
+     */
+    fn get_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut volume : f32 = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_GAIN, &mut volume);
+        volume
+    }
+
+    /**
+     * Set the minimal volume for a StreamingSource.
+     *
+     * The minimum volume allowed for a streaming source, after distance and cone
+     * attenation is applied (if applicable).
+     *
+     * # Argument
+     * * `min_volume` - The new minimal volume of the StreamingSource should be
+     * between 0. and 1.
+     */
+    fn set_min_volume(&mut self, min_volume: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_MIN_GAIN, min_volume);
+    }
+
+    /**
+     * Get the minimal volume of the StreamingSource.
+     *
+     * # Return
+     * The minimal volume of the StreamingSource between 0. and 1.
+     */
+    fn get_min_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut volume : f32 = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_MIN_GAIN, &mut volume);
+        volume
+    }
+
+    /**
+     * Set the maximal volume for a StreamingSource.
+     *
+     * The maximum volume allowed for a StreamingSource, after distance and cone
+     * attenation is applied (if applicable).
+     *
+     * # Argument
+     * * `max_volume` - The new maximal volume of the StreamingSource should be
+     * between 0. and 1.
+     */
+    fn set_max_volume(&mut self, max_volume: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_MAX_GAIN, max_volume);
+    }
+
+    /**
+     * Get the maximal volume of the StreamingSource.
+     *
+     * # Return
+     * The maximal volume of the StreamingSource between 0. and 1.
+     */
+    fn get_max_volume(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut volume : f32 = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_MAX_GAIN, &mut volume);
+        volume
+    }
+
+    /**
+     * Set the StreamingSource looping or not
+     *
+     * A StreamingSource plays the chunks queued once, it never loops.
+     *
+     * # Arguments
+     * `looping` - Ignored.
+     */
+    fn set_looping(&mut self, _looping: bool) -> () {}
+
+    /**
+     * Check if the StreamingSource is looping or not
+     *
+     * # Return
+     * Always false.
+     */
+    fn is_looping(&self) -> bool {
+        false
+    }
+
+    /**
+     * Set the pitch of the StreamingSource.
+     *
+     * A multiplier for the frequency (sample rate) of the StreamingSource's buffer.
+     *
+     * Default pitch is 1.0.
+     *
+     * # Argument
+     * * `new_pitch` - The new pitch of the StreamingSource in the range [0.5 - 2.0]
+     */
+    fn set_pitch(&mut self, pitch: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_PITCH, pitch)
+    }
+
+    /**
+     * Set the pitch of the StreamingSource.
+     *
+     * # Return
+     * The pitch of the StreamingSource in the range [0.5 - 2.0]
+     */
+    fn get_pitch(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut pitch = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_PITCH, &mut pitch);
+        pitch
+    }
+
+    /**
+     * Set the position of the StreamingSource relative to the listener or absolute.
+     *
+     * Default position is absolute.
+     *
+     * # Argument
+     * `relative` - True to set StreamingSource relative to the listener false to set the
+     * StreamingSource position absolute.
+     */
+    fn set_relative(&mut self, relative: bool) -> () {
+        check_openal_context!(());
+
+        match relative {
+            true    => al::alSourcei(self.al_source,
+                                     ffi::AL_SOURCE_RELATIVE,
+                                     ffi::ALC_TRUE as i32),
+            false   => al::alSourcei(self.al_source,
+                                     ffi::AL_SOURCE_RELATIVE,
+                                     ffi::ALC_FALSE as i32)
+        };
+    }
+
+    /**
+     * Is the StreamingSource relative to the listener or not?
+     *
+     * # Return
+     * True if the StreamingSource is relative to the listener false otherwise
+     */
+    fn is_relative(&mut self) -> bool {
+        check_openal_context!(false);
+
+        let mut boolean = 0;
+        al::alGetSourcei(self.al_source, ffi::AL_SOURCE_RELATIVE, &mut boolean);
+        match boolean as i8 {
+            ffi::ALC_TRUE  => true,
+            ffi::ALC_FALSE => false,
+            _              => unreachable!()
+        }
+    }
+
+    /**
+     * Set the StreamingSource location in three dimensional space.
+     *
+     * OpenAL, like OpenGL, uses a right handed coordinate system, where in a
+     * frontal default view X (thumb) points right, Y points up (index finger),
+     * and Z points towards the viewer/camera (middle finger).
+     * To switch from a left handed coordinate system, flip the sign on the Z
+     * coordinate.
+     *
+     * Default position is [0., 0., 0.].
+     *
+     * # Argument
+     * * `position` - A three dimensional vector of f32 containing the position
+     * of the listener [x, y, z].
+     */
+    fn set_position(&mut self, position: [f32; 3]) -> () {
+        check_openal_context!(());
+
+        al::alSourcefv(self.al_source, ffi::AL_POSITION, &position[0]);
+    }
+
+    /**
+     * Get the position of the StreamingSource in three dimensional space.
+     *
+     * # Return
+     * A three dimensional vector of f32 containing the position of the
+     * listener [x, y, z].
+     */
+    fn get_position(&self) -> [f32; 3] {
+        check_openal_context!([0.; 3]);
+
+        let mut position : [f32; 3] = [0.; 3];
+        al::alGetSourcefv(self.al_source, ffi::AL_POSITION, &mut position[0]);
+        position
+    }
+
+    /**
+     * Set the direction of the StreamingSource.
+     *
+     * Specifies the current direction in local space.
+     *
+     * The default direction is: [0., 0., 0.]
+     *
+     * # Argument
+     * `direction` - The new direction of the StreamingSource.
+     */
+    fn set_direction(&mut self, direction: [f32; 3]) -> () {
+        check_openal_context!(());
+
+        al::alSourcefv(self.al_source, ffi::AL_DIRECTION, &direction[0]);
+    }
+
+    /**
+     * Get the direction of the StreamingSource.
+     *
+     * # Return
+     * The current direction of the StreamingSource.
+     */
+    fn get_direction(&self)  -> [f32; 3] {
+        check_openal_context!([0.; 3]);
+
+        let mut direction : [f32; 3] = [0.; 3];
+        al::alGetSourcefv(self.al_source, ffi::AL_DIRECTION, &mut direction[0]);
+        direction
+    }
+
+    /**
+     * Set the maximum distance of the StreamingSource.
+     *
+     * The distance above which the source is not attenuated any further with a
+     * clamped distance model, or where attenuation reaches 0.0 gain for linear
+     * distance models with a default rolloff factor.
+     *
+     * The default maximum distance is +inf.
+     *
+     * # Argument
+     * `max_distance` - The new maximum distance in the range [0., +inf]
+     */
+    fn set_max_distance(&mut self, max_distance: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_MAX_DISTANCE, max_distance);
+    }
+
+    /**
+     * Get the maximum distance of the StreamingSource.
+     *
+     * # Return
+     * The maximum distance of the StreamingSource in the range [0., +inf]
+     */
+    fn get_max_distance(&self) -> f32 {
+        check_openal_context!(0.);
+
+        let mut max_distance = 0.;
+        al::alGetSourcef(self.al_source, ffi::AL_MAX_DISTANCE, &mut max_distance);
+        max_distance
+    }
+
+    /**
+     * Set the reference distance of the StreamingSource.
+     *
+     * The distance in units that no attenuation occurs.
+     * At 0.0, no distance attenuation ever occurs on non-linear
+     * attenuation models.
+     *
+     * The default distance reference is 1.
+     *
+     * # Argument
+     * * `ref_distance` - The new reference distance of the StreamingSource.
+     */
+    fn set_reference_distance(&mut self, ref_distance: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_REFERENCE_DISTANCE, ref_distance);
+    }
+
+    /**
+     * Get the reference distance of the StreamingSource.
+     *
+     * # Return
+     * The current reference distance of the StreamingSource.
+     */
+    fn get_reference_distance(&self) -> f32 {
+        check_openal_context!(1.);
+
+        let mut ref_distance = 0.;
+        al::alGetSourcef(self.al_source,
+                         ffi::AL_REFERENCE_DISTANCE,
+                         &mut ref_distance);
+        ref_distance
+    }
+
+    /**
+     * Set the attenuation of a StreamingSource.
+     *
+     * Multiplier to exaggerate or diminish distance attenuation.
+     * At 0.0, no distance attenuation ever occurs.
+     *
+     * The default attenuation is 1.
+     *
+     * # Arguments
+     * `attenuation` - The new attenuation for the StreamingSource in the range [0., 1.].
+     */
+    fn set_attenuation(&mut self, attenuation: f32) -> () {
+        check_openal_context!(());
+
+        al::alSourcef(self.al_source, ffi::AL_ROLLOFF_FACTOR, attenuation);
+    }
+
+    /**
+     * Get the attenuation of a StreamingSource.
+     *
+     * # Return
+     * The current attenuation for the StreamingSource in the range [0., 1.].
+     */
+    fn get_attenuation(&self) -> f32 {
+        check_openal_context!(1.);
+
+        let mut attenuation = 0.;
+        al::alGetSourcef(self.al_source,
+                         ffi::AL_ROLLOFF_FACTOR,
+                         &mut attenuation);
+        attenuation
+    }
+}
+
+impl Drop for StreamingSource {
+    /// Destroy all the resources of the StreamingSource.
+    fn drop(&mut self) -> () {
+        unsafe {
+            al::alSourceStop(self.al_source);
+            al::alSourcei(self.al_source, ffi::AL_BUFFER, 0);
+            if !self.al_buffers.is_empty() {
+                ffi::alDeleteBuffers(self.al_buffers.len() as i32, &mut self.al_buffers[0]);
+            }
+            ffi::alDeleteSources(1, &mut self.al_source);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use streaming_source::StreamingSource;
+    use sound_data::SoundData;
+    use states::State::Playing;
+    use audio_controller::AudioController;
+
+    #[test]
+    #[ignore]
+    fn streamingsource_queue_OK() -> () {
+        let mut source = StreamingSource::new().unwrap();
+        source.queue(&[0i16; 441], 1, 44100).unwrap();
+        source.queue(&[0i16; 441], 1, 44100).unwrap();
+
+        assert_eq!(source.get_queued(), 2);
+        source.play();
+        assert_eq!(source.get_state(), Playing);
+    }
+
+    #[test]
+    #[ignore]
+    fn streamingsource_queue_FAIL() -> () {
+        let mut source = StreamingSource::new().unwrap();
+        source.queue(&[0i16; 441], 1, 44100).unwrap();
+
+        assert!(source.queue(&[0f32; 441], 1, 44100).is_err());
+        assert!(source.queue(&[0i16; 882], 2, 44100).is_err());
+        assert_eq!(source.get_queued(), 1);
+    }
+
+    #[test]
+    #[ignore]
+    fn streamingsource_queue_sound_data_FAIL() -> () {
+        let data = Rc::new(RefCell::new(SoundData::new("res/shot.wav").unwrap()));
+        let mut source = StreamingSource::new().unwrap();
+
+        assert!(source.queue_sound_data(data, Some("missing")).is_err());
+    }
+
+    #[test]
+    #[ignore]
+    fn streamingsource_recycle_OK() -> () {
+        let mut source = StreamingSource::new().unwrap();
+        source.queue(&[0i16; 441], 1, 44100).unwrap();
+        source.stop();
+
+        assert_eq!(source.get_queued(), 0);
+    }
+}
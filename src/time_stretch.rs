@@ -0,0 +1,393 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+
+//! Change the speed of the samples without changing their pitch.
+
+use std::cmp;
+use std::f64::consts::PI;
+
+/// The slowest speed supported.
+pub const MIN_SPEED: f32 = 0.5;
+/// The fastest speed supported.
+pub const MAX_SPEED: f32 = 2.;
+
+/// Duration of the hop between two segments, in seconds.
+const HOP_DURATION: f64 = 0.02;
+/// Only every few frames are compared when looking for the best segment.
+const SEARCH_STEP: usize = 4;
+
+/**
+ * A WSOLA time-stretcher converting interleaved float samples.
+ *
+ * The samples are cut in overlapping segments of 40 ms, which are played
+ * closer together to speed up or further apart to slow down. Each segment is
+ * moved by up to 10 ms to the place most similar to the end of the previous
+ * one, so the waveforms overlap without phase jumps and the pitch is kept.
+ *
+ * Like the Resampler, the TimeStretcher keeps the end of the previous
+ * samples between calls to process, so a stream can be stretched chunk by
+ * chunk.
+ */
+#[derive(Clone, Debug)]
+pub struct TimeStretcher {
+    channels: usize,
+    speed: f64,
+    /// The frames between two segments in the output
+    hop: usize,
+    /// The greatest move of a segment from its nominal place, in frames
+    tolerance: usize,
+    /// The Hann window of a segment, two hops long
+    window: Vec<f32>,
+    /// The input frames still needed
+    buffer: Vec<f32>,
+    /// The input frame at the start of the buffer
+    buffer_start: u64,
+    /// The nominal input frame of the next segment
+    position: f64,
+    /// The input frame of the previous segment
+    previous: Option<u64>,
+    /// The windowed end of the previous segment, overlapping the next one
+    tail: Vec<f32>,
+    /// The output frames still to drop, covering the silence before the start
+    skip: usize,
+    /// The number of output frames expected for the input received
+    frames_expected: f64,
+    /// The number of output frames produced since the start
+    frames_out: u64
+}
+
+impl TimeStretcher {
+    /**
+     * Create a new TimeStretcher, playing at normal speed.
+     *
+     * # Arguments
+     * * `channels` - The number of interleaved channels
+     * * `sample_rate` - The sample rate of the samples
+     */
+    pub fn new(channels: i32, sample_rate: i32) -> TimeStretcher {
+        let hop = cmp::max((sample_rate as f64 * HOP_DURATION).round() as usize, 16);
+        let window = (0..2 * hop).map(|i| {
+            (0.5 - 0.5 * (PI * i as f64 / hop as f64).cos()) as f32
+        }).collect();
+
+        let mut stretcher = TimeStretcher {
+            channels: if channels < 1 { 1 } else { channels as usize },
+            speed: 1.,
+            hop: hop,
+            tolerance: hop / 2,
+            window: window,
+            buffer: Vec::new(),
+            buffer_start: 0,
+            position: 0.,
+            previous: None,
+            tail: Vec::new(),
+            skip: 0,
+            frames_expected: 0.,
+            frames_out: 0
+        };
+        stretcher.reset();
+        stretcher
+    }
+
+    /**
+     * Set the speed of the samples processed from now on.
+     *
+     * # Argument
+     * * `speed` - The speed, from 0.5 (twice slower) to 2 (twice faster)
+     *
+     * # Return
+     * Ok(()) on success, Err(String) if the speed is out of range.
+     */
+    pub fn set_speed(&mut self, speed: f32) -> Result<(), String> {
+        if let Err(err) = validate_speed(speed) {
+            return Err(err);
+        }
+        self.speed = speed as f64;
+        Ok(())
+    }
+
+    /// Get the speed of the samples.
+    pub fn get_speed(&self) -> f32 {
+        self.speed as f32
+    }
+
+    /// Forget the previous samples, to start stretching another stream.
+    pub fn reset(&mut self) -> () {
+        // The first segment starts a hop before the samples, on silence
+        self.buffer = vec![0.; self.hop * self.channels];
+        self.buffer_start = 0;
+        self.position = 0.;
+        self.previous = None;
+        self.tail = vec![0.; self.hop * self.channels];
+        self.skip = self.hop;
+        self.frames_expected = 0.;
+        self.frames_out = 0;
+    }
+
+    /**
+     * Stretch the next samples of the stream.
+     *
+     * The output is delayed by a few segments: call flush at the end of the
+     * stream to get the last samples.
+     *
+     * # Argument
+     * * `samples` - Interleaved samples, made of whole frames
+     *
+     * # Return
+     * The stretched interleaved samples available so far.
+     */
+    pub fn process(&mut self, samples: &[f32]) -> Vec<f32> {
+        self.frames_expected += (samples.len() / self.channels) as f64 / self.speed;
+        self.buffer.extend_from_slice(samples);
+        self.produce(None)
+    }
+
+    /**
+     * Stretch the samples still held by the TimeStretcher at the end of the
+     * stream, then reset it.
+     *
+     * # Return
+     * The last stretched interleaved samples.
+     */
+    pub fn flush(&mut self) -> Vec<f32> {
+        let expected = self.frames_expected.round() as u64;
+        let padding = vec![0.; (5 * self.hop + self.tolerance) * self.channels];
+        self.buffer.extend_from_slice(&padding);
+        let out = self.produce(Some(expected));
+        self.reset();
+        out
+    }
+
+    /// Overlap the segments whose search range is covered by the buffer.
+    fn produce(&mut self, limit: Option<u64>) -> Vec<f32> {
+        let channels = self.channels;
+        let hop = self.hop;
+        let mut out = Vec::new();
+
+        loop {
+            if let Some(limit) = limit {
+                if self.frames_out >= limit {
+                    break;
+                }
+            }
+            let nominal = self.position.round() as u64;
+            let first = cmp::max(nominal.saturating_sub(self.tolerance as u64), self.buffer_start);
+            let last = cmp::max(nominal + self.tolerance as u64, first);
+            let needed = cmp::max(last, self.previous.map_or(0, |previous| previous + hop as u64))
+                         + 2 * hop as u64;
+            if needed > self.buffer_start + (self.buffer.len() / channels) as u64 {
+                break;
+            }
+
+            let start = match self.previous {
+                Some(previous) => self.find_segment(first, last, nominal, previous + hop as u64),
+                None           => cmp::max(nominal, self.buffer_start)
+            };
+
+            // Overlap the start of the segment with the end of the previous one
+            let offset = (start - self.buffer_start) as usize * channels;
+            let mut frames = Vec::with_capacity(hop * channels);
+            for i in 0..hop * channels {
+                let frame = i / channels;
+                frames.push(self.tail[i] + self.window[frame] * self.buffer[offset + i]);
+                self.tail[i] = self.window[hop + frame] * self.buffer[offset + hop * channels + i];
+            }
+            let skipped = cmp::min(self.skip, hop);
+            self.skip -= skipped;
+            out.extend_from_slice(&frames[skipped * channels..]);
+            self.frames_out += (hop - skipped) as u64;
+
+            self.previous = Some(start);
+            self.position += hop as f64 * self.speed;
+
+            // Drop the frames no segment will reach anymore
+            let next = (self.position.round() as u64).saturating_sub(self.tolerance as u64);
+            let keep_from = cmp::min(next, start + hop as u64);
+            if keep_from > self.buffer_start {
+                let drained = cmp::min((keep_from - self.buffer_start) as usize,
+                                       self.buffer.len() / channels);
+                self.buffer.drain(..drained * channels);
+                self.buffer_start += drained as u64;
+            }
+        }
+
+        if let Some(limit) = limit {
+            let excess = self.frames_out.saturating_sub(limit) as usize;
+            let len = out.len().saturating_sub(excess * channels);
+            out.truncate(len);
+        }
+        out
+    }
+
+    /**
+     * Find the segment between `first` and `last` most similar to the
+     * continuation of the previous segment, starting at `natural`.
+     */
+    fn find_segment(&self, first: u64, last: u64, nominal: u64, natural: u64) -> u64 {
+        let reference = self.mix(natural);
+        let similarity = |start: u64| {
+            let candidate = self.mix(start);
+            let mut correlation = 0.;
+            let mut energy = 0.;
+            for (r, c) in reference.iter().zip(candidate.iter()) {
+                correlation += r * c;
+                energy += c * c;
+            }
+            correlation / (energy + 1e-9f32).sqrt()
+        };
+
+        // The nominal place wins the ties, keeping the normal speed intact
+        let nominal = cmp::min(cmp::max(nominal, first), last);
+        let mut best = nominal;
+        let mut best_similarity = similarity(nominal);
+        for start in first..last + 1 {
+            let value = similarity(start);
+            if value > best_similarity {
+                best = start;
+                best_similarity = value;
+            }
+        }
+        best
+    }
+
+    /// Mix the channels of a hop of the buffer, keeping every few frames.
+    fn mix(&self, start: u64) -> Vec<f32> {
+        let offset = (start - self.buffer_start) as usize;
+        (0..self.hop).step_by(SEARCH_STEP).map(|frame| {
+            let index = (offset + frame) * self.channels;
+            self.buffer[index..index + self.channels].iter().sum()
+        }).collect()
+    }
+}
+
+/**
+ * Check that a speed can be played by a TimeStretcher.
+ *
+ * # Return
+ * Ok(()) if the speed is between MIN_SPEED and MAX_SPEED, Err(String)
+ * otherwise.
+ */
+#[doc(hidden)]
+pub fn validate_speed(speed: f32) -> Result<(), String> {
+    if speed >= MIN_SPEED && speed <= MAX_SPEED {
+        Ok(())
+    } else {
+        Err(format!("Invalid speed {}, it must be between {} and {}.",
+                    speed, MIN_SPEED, MAX_SPEED))
+    }
+}
+
+/**
+ * Change the speed of a whole sound without changing its pitch.
+ *
+ * # Arguments
+ * * `samples` - The interleaved samples
+ * * `channels` - The number of channels
+ * * `sample_rate` - The sample rate of the samples
+ * * `speed` - The speed, from 0.5 (twice slower) to 2 (twice faster)
+ *
+ * # Return
+ * The stretched interleaved samples, or Err(String) if the speed is out of
+ * range.
+ */
+pub fn time_stretch(samples: &[f32],
+                    channels: i32,
+                    sample_rate: i32,
+                    speed: f32) -> Result<Vec<f32>, String> {
+    if let Err(err) = validate_speed(speed) {
+        return Err(err);
+    }
+    if speed == 1. {
+        return Ok(samples.to_vec());
+    }
+    let mut stretcher = TimeStretcher::new(channels, sample_rate);
+    stretcher.set_speed(speed).unwrap();
+    let mut out = stretcher.process(samples);
+    out.extend(stretcher.flush());
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use std::f32::consts::PI;
+    use time_stretch::{TimeStretcher, time_stretch};
+
+    fn sine(frequency: f32, frames: usize, sample_rate: i32) -> Vec<f32> {
+        (0..frames).map(|i| {
+            (i as f32 * frequency * 2. * PI / sample_rate as f32).sin() * 0.5
+        }).collect()
+    }
+
+    /// Estimate the frequency of a mono signal from its rising zero crossings.
+    fn frequency(samples: &[f32], sample_rate: i32) -> f32 {
+        let crossings = samples.windows(2).filter(|w| w[0] < 0. && w[1] >= 0.).count();
+        crossings as f32 * sample_rate as f32 / samples.len() as f32
+    }
+
+    #[test]
+    fn time_stretch_normal_speed_OK() -> () {
+        let samples = sine(440., 8000, 8000);
+        let mut stretcher = TimeStretcher::new(1, 8000);
+        let mut out = stretcher.process(&samples[..3000]);
+        out.extend(stretcher.process(&samples[3000..]));
+        out.extend(stretcher.flush());
+
+        assert_eq!(out.len(), samples.len());
+        for (o, s) in out.iter().zip(samples.iter()) {
+            assert!((o - s).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn time_stretch_length_OK() -> () {
+        let samples: Vec<f32> = sine(300., 16000, 16000).into_iter()
+                                                        .flat_map(|s| vec![s, -s])
+                                                        .collect();
+
+        assert_eq!(time_stretch(&samples, 2, 16000, 2.).unwrap().len(), 16000);
+        assert_eq!(time_stretch(&samples, 2, 16000, 0.5).unwrap().len(), 64000);
+        assert_eq!(time_stretch(&samples, 2, 16000, 0.8).unwrap().len(), 40000);
+    }
+
+    #[test]
+    fn time_stretch_keeps_pitch_OK() -> () {
+        let samples = sine(440., 16000, 16000);
+
+        for &speed in &[0.5, 0.75, 1.5, 2.] {
+            let out = time_stretch(&samples, 1, 16000, speed).unwrap();
+            let heard = frequency(&out[1000..out.len() - 1000], 16000);
+            assert!((heard - 440.).abs() < 10., "{} Hz at speed {}", heard, speed);
+        }
+    }
+
+    #[test]
+    fn time_stretch_speed_FAIL() -> () {
+        let mut stretcher = TimeStretcher::new(1, 44100);
+
+        assert!(stretcher.set_speed(0.25).is_err());
+        assert!(stretcher.set_speed(3.).is_err());
+        assert_eq!(stretcher.get_speed(), 1.);
+        assert!(time_stretch(&[0.; 4], 1, 44100, 0.).is_err());
+    }
+}
@@ -0,0 +1,554 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2013 Jeremy Letang (letang.jeremy@gmail.com)
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy of
+// this software and associated documentation files (the "Software"), to deal in
+// the Software without restriction, including without limitation the rights to
+// use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software is furnished to do so,
+// subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS
+// FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR
+// COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER
+// IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN
+// CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE.
+
+//! Share a limited pool of OpenAL sources between many sounds.
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::time::Instant;
+
+use internal::OpenAlData;
+use sound_data;
+use sound_data::SoundData;
+use normalization::gain_headroom;
+use openal::{ffi, al};
+use listener;
+
+/// Voices quieter than this are virtualized even if a source is free.
+const INAUDIBLE_VOLUME: f32 = 0.001;
+
+/**
+ * The parameters of a voice played by a VoiceManager.
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoiceParams {
+    /// Voices with a higher priority are never stolen by lower ones (default 0)
+    pub priority: i32,
+    /// The volume of the voice (default 1.)
+    pub volume: f32,
+    /// The pitch of the voice (default 1.)
+    pub pitch: f32,
+    /// The position of the voice (default [0., 0., 0.])
+    pub position: [f32; 3],
+    /// Is the position relative to the listener (default false)
+    pub relative: bool,
+    /// Does the voice loop (default false)
+    pub looping: bool
+}
+
+impl Default for VoiceParams {
+    fn default() -> VoiceParams {
+        VoiceParams {
+            priority: 0,
+            volume: 1.,
+            pitch: 1.,
+            position: [0.; 3],
+            relative: false,
+            looping: false
+        }
+    }
+}
+
+/// Identifier of a voice played by a VoiceManager.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VoiceId {
+    index: usize,
+    generation: u32
+}
+
+struct Voice {
+    data: Rc<RefCell<SoundData>>,
+    params: VoiceParams,
+    /// The OpenAL source, None while the voice is virtual
+    source: Option<u32>,
+    /// The playback position in seconds
+    offset: f32,
+    /// The duration of the SoundData in seconds
+    duration: f32
+}
+
+struct Slot {
+    generation: u32,
+    voice: Option<Voice>
+}
+
+/**
+ * Play many sounds with a fixed number of OpenAL sources.
+ *
+ * Each voice gets a priority. When there are more voices than sources, the
+ * lowest priority and quietest voices are virtualized: they lose their
+ * source but keep their logical playback position, and resume at the right
+ * position when they get a source again. Inaudible voices are virtualized
+ * too.
+ *
+ * `update` must be called regularly, e.g. once per frame.
+ *
+ * # Example
+ * ```no_run
+ * use ears::{SoundData, VoiceManager, VoiceParams};
+ * use std::rc::Rc;
+ * use std::cell::RefCell;
+ *
+ * let mut voices = VoiceManager::new(32).unwrap();
+ * let shot = Rc::new(RefCell::new(SoundData::new("path/to/shot.ogg").unwrap()));
+ *
+ * let mut params = VoiceParams::default();
+ * params.priority = 10;
+ * params.position = [10., 0., 5.];
+ * let id = voices.play(shot.clone(), params);
+ *
+ * while voices.is_playing(id) {
+ *     voices.update();
+ * }
+ * ```
+ */
+pub struct VoiceManager {
+    /// All the sources owned by the manager
+    sources: Vec<u32>,
+    /// The sources not used by a voice
+    free_sources: Vec<u32>,
+    /// The voices, real or virtual
+    slots: Vec<Slot>,
+    /// The last time the virtual voices were advanced
+    last_update: Instant
+}
+
+impl VoiceManager {
+    /**
+     * Create a new VoiceManager.
+     *
+     * # Argument
+     * * `max_voices` - The number of OpenAL sources to allocate. Less
+     * sources are allocated if the device runs out of them.
+     *
+     * # Return
+     * A `Result` containing Ok(VoiceManager) on success, Err(String)
+     * if no source can be allocated.
+     */
+    pub fn new(max_voices: usize) -> Result<VoiceManager, String> {
+        check_openal_context!(Err("Invalid OpenAL context.".into()));
+
+        let mut sources = Vec::with_capacity(max_voices);
+        for _ in 0..max_voices {
+            let mut source_id = 0;
+            al::alGenSources(1, &mut source_id);
+            if al::openal_has_error().is_some() {
+                break;
+            }
+            sources.push(source_id);
+        }
+        if sources.is_empty() {
+            return Err("Cannot allocate any OpenAL source.".into());
+        }
+
+        Ok(VoiceManager {
+            free_sources: sources.clone(),
+            sources: sources,
+            slots: Vec::new(),
+            last_update: Instant::now()
+        })
+    }
+
+    /**
+     * Play a SoundData.
+     *
+     * The voice steals the source of a lower priority or quieter voice if
+     * all the sources are used, otherwise it starts virtual.
+     *
+     * # Arguments
+     * * `data` - The SoundData to play
+     * * `params` - The parameters of the voice
+     *
+     * # Return
+     * The identifier of the voice.
+     */
+    pub fn play(&mut self, data: Rc<RefCell<SoundData>>, params: VoiceParams) -> VoiceId {
+        let duration = {
+            let data = data.borrow();
+            let infos = sound_data::get_sndinfo(&*data);
+            if infos.samplerate > 0 {
+                infos.frames as f32 / infos.samplerate as f32
+            } else {
+                0.
+            }
+        };
+        let voice = Voice {
+            data: data,
+            params: params,
+            source: None,
+            offset: 0.,
+            duration: duration
+        };
+
+        let index = match self.slots.iter().position(|slot| slot.voice.is_none()) {
+            Some(index) => {
+                self.slots[index].voice = Some(voice);
+                index
+            },
+            None => {
+                self.slots.push(Slot { generation: 0, voice: Some(voice) });
+                self.slots.len() - 1
+            }
+        };
+
+        self.allocate_sources();
+        VoiceId {
+            index: index,
+            generation: self.slots[index].generation
+        }
+    }
+
+    /**
+     * Update the voices.
+     *
+     * Free the voices which finished playing, advance the position of the
+     * virtual voices, and give the sources to the most important voices.
+     */
+    pub fn update(&mut self) -> () {
+        check_openal_context!(());
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_update);
+        let elapsed = elapsed.as_secs() as f32 + elapsed.subsec_nanos() as f32 * 1e-9;
+        self.last_update = now;
+
+        for index in 0..self.slots.len() {
+            let finished = match self.slots[index].voice {
+                Some(ref mut voice) => match voice.source {
+                    Some(source) => {
+                        al::alGetSourcef(source, ffi::AL_SEC_OFFSET, &mut voice.offset);
+                        al::alGetState(source) == ffi::AL_STOPPED
+                    },
+                    None => {
+                        voice.offset += elapsed * voice.params.pitch;
+                        if voice.offset < voice.duration {
+                            false
+                        } else if voice.params.looping && voice.duration > 0. {
+                            voice.offset %= voice.duration;
+                            false
+                        } else {
+                            true
+                        }
+                    }
+                },
+                None => false
+            };
+            if finished {
+                self.remove(index);
+            }
+        }
+
+        self.allocate_sources();
+    }
+
+    /**
+     * Stop a voice and free its source.
+     *
+     * # Argument
+     * * `id` - The identifier of the voice
+     */
+    pub fn stop(&mut self, id: VoiceId) -> () {
+        if self.get_voice(id).is_some() {
+            self.remove(id.index);
+        }
+    }
+
+    /// Stop all the voices.
+    pub fn stop_all(&mut self) -> () {
+        for index in 0..self.slots.len() {
+            if self.slots[index].voice.is_some() {
+                self.remove(index);
+            }
+        }
+    }
+
+    /**
+     * Check if a voice is still playing, really or virtually.
+     *
+     * # Argument
+     * * `id` - The identifier of the voice
+     */
+    pub fn is_playing(&self, id: VoiceId) -> bool {
+        self.get_voice(id).is_some()
+    }
+
+    /**
+     * Check if a voice is virtual, i.e. playing without a source.
+     *
+     * # Argument
+     * * `id` - The identifier of the voice
+     *
+     * # Return
+     * True if the voice is playing without a source, false if it has a
+     * source or is not playing anymore.
+     */
+    pub fn is_virtual(&self, id: VoiceId) -> bool {
+        match self.get_voice(id) {
+            Some(voice) => voice.source.is_none(),
+            None        => false
+        }
+    }
+
+    /**
+     * Set the volume of a voice.
+     *
+     * # Arguments
+     * * `id` - The identifier of the voice
+     * * `volume` - The new volume of the voice
+     */
+    pub fn set_volume(&mut self, id: VoiceId, volume: f32) -> () {
+        if let Some(voice) = self.get_voice_mut(id) {
+            voice.params.volume = volume;
+            if let Some(source) = voice.source {
+                al::alSourcef(source, ffi::AL_GAIN, volume * voice.data.borrow().get_base_gain());
+            }
+        }
+    }
+
+    /**
+     * Set the pitch of a voice.
+     *
+     * # Arguments
+     * * `id` - The identifier of the voice
+     * * `pitch` - The new pitch of the voice
+     */
+    pub fn set_pitch(&mut self, id: VoiceId, pitch: f32) -> () {
+        if let Some(voice) = self.get_voice_mut(id) {
+            voice.params.pitch = pitch;
+            if let Some(source) = voice.source {
+                al::alSourcef(source, ffi::AL_PITCH, pitch);
+            }
+        }
+    }
+
+    /**
+     * Set the position of a voice.
+     *
+     * # Arguments
+     * * `id` - The identifier of the voice
+     * * `position` - The new position of the voice
+     */
+    pub fn set_position(&mut self, id: VoiceId, position: [f32; 3]) -> () {
+        if let Some(voice) = self.get_voice_mut(id) {
+            voice.params.position = position;
+            if let Some(source) = voice.source {
+                al::alSourcefv(source, ffi::AL_POSITION, &position[0]);
+            }
+        }
+    }
+
+    /// Get the number of voices playing, really or virtually.
+    pub fn get_voice_count(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.voice.is_some()).count()
+    }
+
+    /// Get the number of voices playing with a source.
+    pub fn get_real_voice_count(&self) -> usize {
+        self.sources.len() - self.free_sources.len()
+    }
+
+    fn get_voice(&self, id: VoiceId) -> Option<&Voice> {
+        match self.slots.get(id.index) {
+            Some(slot) if slot.generation == id.generation => slot.voice.as_ref(),
+            _ => None
+        }
+    }
+
+    fn get_voice_mut(&mut self, id: VoiceId) -> Option<&mut Voice> {
+        match self.slots.get_mut(id.index) {
+            Some(slot) if slot.generation == id.generation => slot.voice.as_mut(),
+            _ => None
+        }
+    }
+
+    fn remove(&mut self, index: usize) -> () {
+        let slot = &mut self.slots[index];
+        if let Some(voice) = slot.voice.take() {
+            if let Some(source) = voice.source {
+                al::alSourceStop(source);
+                al::alSourcei(source, ffi::AL_BUFFER, 0);
+                self.free_sources.push(source);
+            }
+        }
+        slot.generation = slot.generation.wrapping_add(1);
+    }
+
+    /// Give the sources to the most important audible voices.
+    fn allocate_sources(&mut self) -> () {
+        let listener_position = listener::get_position();
+
+        let mut ranking: Vec<(usize, i32, f32)> = self.slots.iter().enumerate()
+            .filter_map(|(index, slot)| slot.voice.as_ref().map(|voice| {
+                (index, voice.params.priority, audibility(&voice.params, listener_position))
+            }))
+            .collect();
+        ranking.sort_by(|a, b| {
+            b.1.cmp(&a.1).then(b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal))
+        });
+
+        let mut wanted = vec![false; self.slots.len()];
+        for (rank, &(index, _, audibility)) in ranking.iter().enumerate() {
+            wanted[index] = rank < self.sources.len() && audibility > INAUDIBLE_VOLUME;
+        }
+
+        // Free the sources first so they can be given to other voices
+        for (slot, wanted) in self.slots.iter_mut().zip(wanted.iter()) {
+            if let Some(ref mut voice) = slot.voice {
+                if !*wanted {
+                    if let Some(source) = voice.source.take() {
+                        al::alGetSourcef(source, ffi::AL_SEC_OFFSET, &mut voice.offset);
+                        al::alSourceStop(source);
+                        al::alSourcei(source, ffi::AL_BUFFER, 0);
+                        self.free_sources.push(source);
+                    }
+                }
+            }
+        }
+
+        for (slot, wanted) in self.slots.iter_mut().zip(wanted.iter()) {
+            if let Some(ref mut voice) = slot.voice {
+                if *wanted && voice.source.is_none() {
+                    if let Some(source) = self.free_sources.pop() {
+                        start_voice(voice, source);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Bind a voice to a source and play it from its current position.
+fn start_voice(voice: &mut Voice, source: u32) -> () {
+    al::alSourcei(source,
+                  ffi::AL_BUFFER,
+                  sound_data::get_buffer(&*voice.data.borrow()) as i32);
+    al::alSourcef(source,
+                  ffi::AL_GAIN,
+                  voice.params.volume * voice.data.borrow().get_base_gain());
+    al::alSourcef(source, ffi::AL_MAX_GAIN, gain_headroom(voice.data.borrow().get_base_gain()));
+    al::alSourcef(source, ffi::AL_PITCH, voice.params.pitch);
+    al::alSourcefv(source, ffi::AL_POSITION, &voice.params.position[0]);
+    al::alSourcei(source,
+                  ffi::AL_SOURCE_RELATIVE,
+                  if voice.params.relative { ffi::ALC_TRUE } else { ffi::ALC_FALSE } as i32);
+    al::alSourcei(source,
+                  ffi::AL_LOOPING,
+                  if voice.params.looping { ffi::ALC_TRUE } else { ffi::ALC_FALSE } as i32);
+    al::alSourcef(source, ffi::AL_SEC_OFFSET, voice.offset);
+    al::alSourcePlay(source);
+    voice.source = Some(source);
+}
+
+/**
+ * Estimate how loud a voice is heard by the listener.
+ *
+ * Use the default OpenAL distance model: inverse distance clamped with a
+ * reference distance and a rolloff factor of 1.
+ */
+fn audibility(params: &VoiceParams, listener_position: [f32; 3]) -> f32 {
+    let mut distance = 0.;
+    for i in 0..3 {
+        let delta = if params.relative {
+            params.position[i]
+        } else {
+            params.position[i] - listener_position[i]
+        };
+        distance += delta * delta;
+    }
+    let distance = distance.sqrt();
+
+    if distance <= 1. {
+        params.volume
+    } else {
+        params.volume / distance
+    }
+}
+
+impl Drop for VoiceManager {
+    /// Stop all the voices and destroy the sources.
+    fn drop(&mut self) -> () {
+        self.stop_all();
+        for source in self.sources.iter_mut() {
+            unsafe {
+                ffi::alDeleteSources(1, source);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    #![allow(non_snake_case)]
+
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use voice_manager::{VoiceManager, VoiceParams, audibility};
+    use sound_data::SoundData;
+
+    #[test]
+    fn voicemanager_audibility_DISTANCE() -> () {
+        let mut params = VoiceParams::default();
+        params.position = [4., 0., 0.];
+
+        assert_eq!(audibility(&params, [0., 0., 0.]), 0.25);
+        assert_eq!(audibility(&params, [4., 0., 0.]), 1.);
+    }
+
+    #[test]
+    fn voicemanager_audibility_RELATIVE() -> () {
+        let mut params = VoiceParams::default();
+        params.position = [0., 0., 2.];
+        params.relative = true;
+        params.volume = 0.5;
+
+        assert_eq!(audibility(&params, [0., 0., 2.]), 0.25);
+    }
+
+    #[test]
+    #[ignore]
+    fn voicemanager_play_OK() -> () {
+        let mut voices = VoiceManager::new(2).unwrap();
+        let data = Rc::new(RefCell::new(SoundData::new("res/shot.wav").unwrap()));
+        let id = voices.play(data, VoiceParams::default());
+
+        assert!(voices.is_playing(id));
+        assert!(!voices.is_virtual(id));
+        voices.stop(id);
+        assert!(!voices.is_playing(id));
+    }
+
+    #[test]
+    #[ignore]
+    fn voicemanager_steal_LOWEST_PRIORITY() -> () {
+        let mut voices = VoiceManager::new(1).unwrap();
+        let data = Rc::new(RefCell::new(SoundData::new("res/shot.wav").unwrap()));
+        let low = voices.play(data.clone(), VoiceParams::default());
+        let mut params = VoiceParams::default();
+        params.priority = 1;
+        let high = voices.play(data, params);
+
+        assert!(voices.is_virtual(low));
+        assert!(!voices.is_virtual(high));
+        assert_eq!(voices.get_voice_count(), 2);
+        assert_eq!(voices.get_real_voice_count(), 1);
+    }
+}